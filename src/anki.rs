@@ -1,101 +1,1569 @@
-use crate::config::Anki;
-use anyhow::{Result, anyhow};
-use log::debug;
+use crate::config::{self, Anki, DuplicateAction, FieldMode, MediaTransfer, NewNote};
+use crate::offline::{OfflineQueue, PendingUpdate};
+use crate::utils::window::get_foreground_window_name;
+use anyhow::{Context, Result, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use log::{debug, error, info, warn};
 use reqwest::Client;
 use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 离线队列的重试间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// 离线队列中的记录因连接以外的原因失败这么多次后不再重试
+const MAX_FAILED_ATTEMPTS: u32 = 3;
+/// 最多可以撤销的写入次数
+const UNDO_HISTORY: usize = 20;
+/// 不修改 Anki 数据的请求，超时后重试不会产生重复的写入
+const READ_ONLY_ACTIONS: &[&str] = &[
+    "version",
+    "findNotes",
+    "notesInfo",
+    "modelNames",
+    "modelFieldNames",
+    "getActiveProfile",
+    "getMediaFilesNames",
+    "getMediaDirPath",
+];
+
+/// 采集时确定的内容，排队或暂存的写入仍按采集时的游戏与时间填写
+#[derive(Clone, Debug, Default)]
+pub struct Capture {
+    /// 采集时的游戏窗口标题
+    pub game: String,
+    /// 按采集时渲染好的其他字段，包括来源字段，总是覆盖写入
+    pub extra_fields: BTreeMap<String, String>,
+}
+
+/// 一次字段写入，撤销时据此恢复
+struct UpdateRecord {
+    /// 写入的各张卡片及其写入前各字段的内容
+    notes: Vec<(u64, BTreeMap<String, String>)>,
+    /// 随这次写入存入的媒体文件
+    media: Vec<String>,
+}
+
+/// 写入了的卡片、写入前的字段内容、写入的字段内容
+type WrittenNote = (u64, HashMap<String, String>, BTreeMap<String, String>);
+
 #[derive(Clone)]
 pub struct AnkiClient {
     pub client: Client,
     pub anki_url: String,
+    pub profile: Option<String>,
+    pub api_key: Option<String>,
+    /// 连接失败或超时后的重试次数
+    pub retries: u32,
+    /// 第一次重试前的等待时间，之后每次加倍
+    pub retry_backoff: Duration,
+    pub archive_dir: Option<PathBuf>,
+    pub fallback_dir: Option<PathBuf>,
+    pub media_transfer: MediaTransfer,
+    pub verify_media: bool,
+    pub note_query: String,
+    pub max_note_age: Option<Duration>,
+    pub note_count: usize,
+    pub new_note: Option<NewNote>,
+    pub duplicate_media: DuplicateAction,
+    pub tags: Vec<String>,
+    pub browse_after_update: bool,
+    pub protect_non_empty_fields: bool,
+    /// 来源字段与其模板
+    pub source_field: Option<(String, String)>,
+    pub sync_after_updates: Option<u32>,
+    /// 上次同步后成功写入的次数
+    unsynced: Arc<AtomicU32>,
+    /// 本次运行存入的媒体，内容哈希到文件名
+    session_media: Arc<Mutex<HashMap<u64, String>>>,
+    /// 被沿用的媒体文件名及沿用次数，删除时只减少次数
+    reused_media: Arc<Mutex<HashMap<String, usize>>>,
+    history: Arc<Mutex<VecDeque<UpdateRecord>>>,
+    /// 锁定的目标卡片
+    locked_note: Arc<Mutex<Option<u64>>>,
+    /// 本次运行给各卡片加过的标签，标签不变时不再重复发送
+    tagged: Arc<Mutex<HashMap<u64, String>>>,
+    offline: Option<Arc<OfflineQueue>>,
+    /// 暂存模式下的暂存区
+    staging: Option<Arc<OfflineQueue>>,
 }
 
 impl AnkiClient {
-    pub fn new(cfg: &Anki) -> Self {
-        Self {
-            client: Client::new(),
+    /// 证书或代理等设置无效时返回错误，而不是换用默认设置继续连接
+    pub fn new(cfg: &Anki) -> Result<Self> {
+        Ok(Self {
+            client: build_client(cfg).context("Failed to configure HTTP client")?,
             anki_url: cfg.anki_connect_url.to_string(),
+            profile: (!cfg.profile.is_empty()).then(|| cfg.profile.clone()),
+            api_key: (!cfg.api_key.is_empty()).then(|| cfg.api_key.clone()),
+            retries: cfg.retries,
+            retry_backoff: Duration::from_millis(cfg.retry_backoff_ms),
+            archive_dir: (!cfg.archive_dir.is_empty()).then(|| config::data_path(&cfg.archive_dir)),
+            fallback_dir: (!cfg.fallback_dir.is_empty())
+                .then(|| config::data_path(&cfg.fallback_dir)),
+            media_transfer: cfg.media_transfer.clone(),
+            verify_media: cfg.verify_media,
+            // 标签查找不限定添加日期，最新卡片仍取 ID 最大的一张
+            note_query: if cfg.target_tag.is_empty() {
+                cfg.note_query.clone()
+            } else {
+                format!("\"tag:{}\"", escape_search(&cfg.target_tag))
+            },
+            max_note_age: (cfg.max_note_age_minutes > 0)
+                .then(|| Duration::from_secs(cfg.max_note_age_minutes * 60)),
+            note_count: cfg.note_count,
+            new_note: cfg.new_note.enabled.then(|| cfg.new_note.clone()),
+            duplicate_media: cfg.duplicate_media.clone(),
+            tags: cfg.tags.clone(),
+            browse_after_update: cfg.browse_after_update,
+            protect_non_empty_fields: cfg.protect_non_empty_fields,
+            source_field: (!cfg.source_field.is_empty())
+                .then(|| (cfg.source_field.clone(), cfg.source_template.clone())),
+            sync_after_updates: (cfg.sync_after_updates > 0).then_some(cfg.sync_after_updates),
+            unsynced: Arc::new(AtomicU32::new(0)),
+            session_media: Arc::new(Mutex::new(HashMap::new())),
+            reused_media: Arc::new(Mutex::new(HashMap::new())),
+            history: Arc::new(Mutex::new(VecDeque::new())),
+            locked_note: Arc::new(Mutex::new(None)),
+            tagged: Arc::new(Mutex::new(HashMap::new())),
+            offline: cfg
+                .offline_queue
+                .then(|| config::get_config_directory().ok())
+                .flatten()
+                .map(|dir| Arc::new(OfflineQueue::new(dir.join("queue")))),
+            staging: cfg
+                .staging
+                .then(|| config::get_config_directory().ok())
+                .flatten()
+                .map(|dir| Arc::new(OfflineQueue::new(dir.join("staging")))),
+        })
+    }
+
+    /// AnkiConnect 无法连接时把这次写入放入离线队列，返回是否已入队
+    ///
+    /// `note_ids` 为空时，重试时写入那时的目标卡片。
+    pub fn queue_if_unreachable(
+        &self,
+        error: &(dyn std::error::Error + 'static),
+        note_ids: Option<&[u64]>,
+        media: &[(&str, &[u8])],
+        fields: &[(String, String)],
+        mode: &FieldMode,
+        capture: &Capture,
+    ) -> bool {
+        let Some(queue) = &self.offline else {
+            return false;
+        };
+        if !is_unreachable(error) {
+            return false;
+        }
+        match queue.push(note_ids, media, fields, mode, capture) {
+            Ok(()) => {
+                warn!(
+                    "AnkiConnect is unreachable, queued {} file(s) for retry",
+                    media.len()
+                );
+                true
+            }
+            Err(e) => {
+                error!("Failed to queue update for retry: {e}");
+                false
+            }
         }
     }
 
-    pub async fn get_latest_note_id(&self) -> Result<u64> {
+    /// 写入失败且没有进入离线队列时，把媒体保存到备用目录，返回是否已保存
+    ///
+    /// 每次写入一个子目录，附带 update.json 记录原本要写入的卡片与字段，之后可以手动补上。
+    pub fn save_fallback(
+        &self,
+        error: &(dyn std::error::Error + 'static),
+        note_ids: Option<&[u64]>,
+        media: &[(&str, &[u8])],
+        fields: &[(String, String)],
+        capture: &Capture,
+    ) -> bool {
+        let Some(fallback_dir) = &self.fallback_dir else {
+            return false;
+        };
+        let dir = fallback_dir.join(chrono::Local::now().format("%Y%m%d_%H%M%S_%3f").to_string());
+        let sidecar = json!({
+            "error": error.to_string(),
+            "noteIds": note_ids,
+            "noteQuery": self.note_query,
+            "media": media.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            "fields": fields.iter().cloned().collect::<BTreeMap<_, _>>(),
+            "game": capture.game,
+            "extraFields": capture.extra_fields,
+        });
+        let result = fs::create_dir_all(&dir)
+            .and_then(|_| {
+                media
+                    .iter()
+                    .try_for_each(|(filename, data)| fs::write(dir.join(filename), data))
+            })
+            .and_then(|_| {
+                let content = serde_json::to_string_pretty(&sidecar)?;
+                fs::write(dir.join("update.json"), content)
+            });
+        match result {
+            Ok(()) => {
+                warn!(
+                    "Failed to write to Anki, capture saved to {}",
+                    dir.display()
+                );
+                true
+            }
+            Err(e) => {
+                error!("Failed to save capture to fallback directory: {e}");
+                false
+            }
+        }
+    }
+
+    /// 启动时立即重试一次离线队列，之后定期重试
+    pub fn start_offline_retry(self: &Arc<Self>) {
+        if self.offline.is_none() {
+            return;
+        }
+        let anki = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(RETRY_INTERVAL);
+            loop {
+                ticker.tick().await;
+                anki.flush_offline().await;
+            }
+        });
+    }
+
+    /// 按入队顺序重试，AnkiConnect 仍无法连接时保留剩余记录
+    pub async fn flush_offline(&self) {
+        let Some(queue) = &self.offline else {
+            return;
+        };
+        let entries = match queue.entries() {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("Failed to load offline queue: {e}");
+                return;
+            }
+        };
+        for entry in entries {
+            match self.apply_pending(queue, &entry, None).await {
+                Ok(note_id) => {
+                    info!("Queued update {} written to note {note_id}", entry.id);
+                    if let Err(e) = queue.remove(entry.id) {
+                        error!("Failed to remove queued update {}: {e}", entry.id);
+                    }
+                }
+                Err(e) if is_unreachable(&*e) => {
+                    debug!("AnkiConnect is still unreachable, keeping offline queue");
+                    return;
+                }
+                // 例如字段不存在或卡片已删除，多次失败后移到 failed 目录，以免每次都重试
+                Err(e) => {
+                    error!("Failed to apply queued update {}: {e}", entry.id);
+                    self.give_up_after_failures(queue, &entry, &e.to_string());
+                }
+            }
+        }
+    }
+
+    /// 记下一次失败，达到 [`MAX_FAILED_ATTEMPTS`] 次时把记录移出离线队列
+    fn give_up_after_failures(&self, queue: &OfflineQueue, entry: &PendingUpdate, error: &str) {
+        let attempts = match queue.record_failure(entry.id) {
+            Ok(attempts) => attempts,
+            Err(e) => {
+                error!("Failed to update offline queue: {e}");
+                return;
+            }
+        };
+        if attempts < MAX_FAILED_ATTEMPTS {
+            return;
+        }
+        match queue.move_to_failed(entry.id, error) {
+            Ok(dir) => warn!(
+                "Gave up on queued update {} after {attempts} attempts, saved to {}",
+                entry.id,
+                dir.display()
+            ),
+            Err(e) => error!("Failed to move queued update {}: {e}", entry.id),
+        }
+    }
+
+    /// 写入一条记录；记录没有指定卡片时写入 `fallback`，也为空时写入当前的目标卡片
+    async fn apply_pending(
+        &self,
+        queue: &OfflineQueue,
+        entry: &PendingUpdate,
+        fallback: Option<&[u64]>,
+    ) -> Result<u64> {
+        let note_ids = match (entry.note_ids(), fallback) {
+            (Some(ids), _) => ids,
+            (None, Some(ids)) => ids.to_vec(),
+            (None, None) => self.resolve_note_ids().await?,
+        };
+        let mut fields = entry.fields.clone();
+        for filename in &entry.media {
+            let data = fs::read(queue.media_path(entry, filename))?;
+            let stored = self.store_media(filename, &data).await?;
+            // 沿用了已有的相同文件时，字段中的文件名随之替换
+            if &stored != filename {
+                for (_, value) in &mut fields {
+                    *value = value.replace(filename.as_str(), &stored);
+                }
+            }
+        }
+        self.write_note_fields(&note_ids, &fields, &entry.field_mode, &entry.capture())
+            .await?;
+        Ok(note_ids[0])
+    }
+
+    /// 暂存模式下截图、录音不写入 Anki，等按下提交热键时再一起写入
+    pub fn is_staging(&self) -> bool {
+        self.staging.is_some()
+    }
+
+    /// 把一次写入放入暂存区；`note_ids` 为空时提交时写入那时的目标卡片
+    pub fn stage(
+        &self,
+        note_ids: Option<&[u64]>,
+        media: &[(&str, &[u8])],
+        fields: &[(String, String)],
+        mode: &FieldMode,
+        capture: &Capture,
+    ) -> Result<()> {
+        let Some(staging) = &self.staging else {
+            return Err(anyhow!("未启用暂存模式"));
+        };
+        staging.push(note_ids, media, fields, mode, capture)?;
+        info!(
+            "Staged {} file(s), press the commit hotkey to write them to Anki",
+            media.len()
+        );
+        Ok(())
+    }
+
+    /// 把暂存区的所有内容写入同一张目标卡片，目标卡片在提交时才确定
+    pub async fn commit_staged(&self) -> Result<()> {
+        let Some(staging) = &self.staging else {
+            return Err(anyhow!("未启用暂存模式"));
+        };
+        let entries = staging.entries()?;
+        if entries.is_empty() {
+            return Err(anyhow!("暂存区是空的"));
+        }
+        let latest = if entries.iter().any(|entry| entry.note_id.is_none()) {
+            Some(self.resolve_note_ids().await?)
+        } else {
+            None
+        };
+        for entry in &entries {
+            // 出错时保留剩余的记录，之后可以再次提交
+            let note_id = self
+                .apply_pending(staging, entry, latest.as_deref())
+                .await?;
+            debug!("Staged update {} written to note {note_id}", entry.id);
+            staging.remove(entry.id)?;
+        }
+        info!("Committed {} staged update(s)", entries.len());
+        Ok(())
+    }
+
+    /// 发送一个 AnkiConnect 请求并返回响应，连接失败时按配置退避重试
+    ///
+    /// 超时的请求可能已经被 Anki 执行，只有 [`READ_ONLY_ACTIONS`] 中的请求在超时后重试。
+    async fn request(&self, request_body: Value) -> Result<Value> {
+        let read_only = request_body["action"]
+            .as_str()
+            .is_some_and(|action| READ_ONLY_ACTIONS.contains(&action));
+        let request_body = self.with_key(request_body);
+        let mut delay = self.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            let result = match self
+                .client
+                .post(&self.anki_url)
+                .header("Content-Type", "application/json; charset=UTF-8")
+                .json(&request_body)
+                .send()
+                .await
+            {
+                Ok(response) => response.json::<Value>().await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Err(e)
+                    if attempt < self.retries
+                        && (e.is_connect() || read_only && e.is_timeout()) =>
+                {
+                    attempt += 1;
+                    debug!(
+                        "AnkiConnect request failed: {e}, retry {attempt}/{} in {delay:?}",
+                        self.retries
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    /// 配置了 apiKey 时把它加入请求体，AnkiConnect 会拒绝没有 key 的请求
+    fn with_key(&self, mut request_body: Value) -> Value {
+        if let Some(key) = &self.api_key {
+            request_body["key"] = json!(key);
+        }
+        request_body
+    }
+
+    /// 启动时检查 AnkiConnect 是否可用，以及配置的字段是否存在于目标笔记类型中
+    ///
+    /// 检查结果只记录日志，不阻止程序启动。
+    pub async fn health_check(&self, fields: &[String]) -> Result<()> {
+        let version = match self.version().await {
+            Ok(version) => version,
+            Err(e) if is_unreachable(&*e) => {
+                return Err(anyhow!(
+                    "无法连接 AnkiConnect（{}），请确认 Anki 已运行并安装了 AnkiConnect 插件: {e}",
+                    self.anki_url
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+        info!("AnkiConnect is reachable, version {version}");
+        if version < 6 {
+            warn!("AnkiConnect version {version} is too old, please update the add-on");
+        }
+        self.ensure_profile().await?;
+
+        let model = self.target_model().await?;
+        let model_fields = self.model_field_names(&model).await?;
+        let missing: Vec<&String> = fields
+            .iter()
+            .filter(|field| !model_fields.contains(field))
+            .collect();
+        if missing.is_empty() {
+            info!("All configured fields exist on note type {model}");
+            return Ok(());
+        }
+        for field in &missing {
+            match closest_field(field, &model_fields) {
+                Some(best) => error!("笔记类型 {model} 中没有字段 {field}，是否应为 '{best}'？"),
+                None => error!("笔记类型 {model} 中没有字段 {field}，请检查配置中的字段名"),
+            }
+        }
+        Err(anyhow!(
+            "笔记类型 {model} 缺少 {} 个配置的字段，现有字段: {}",
+            missing.len(),
+            model_fields.join(", ")
+        ))
+    }
+
+    /// 配置的字段所属的笔记类型：新建模式下为配置的笔记类型，否则为最新卡片的笔记类型
+    pub async fn target_model(&self) -> Result<String> {
+        match &self.new_note {
+            Some(new_note) => Ok(new_note.model_name.clone()),
+            None => {
+                let note_id = self
+                    .get_latest_note_id()
+                    .await
+                    .map_err(|e| anyhow!("无法找到目标卡片（查询: {}）: {e}", self.note_query))?;
+                self.get_note_model(note_id).await
+            }
+        }
+    }
+
+    /// AnkiConnect 的 API 版本
+    pub async fn version(&self) -> Result<u64> {
         let request_body = json!({
-            "action": "findNotes",
+            "action": "version",
+            "version": 6
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!(
+                "Failed to get AnkiConnect version: {}",
+                data["error"]
+            ));
+        }
+        data["result"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("无法获取 AnkiConnect 版本"))
+    }
+
+    /// 卡片所属的笔记类型
+    pub async fn get_note_model(&self, note_id: u64) -> Result<String> {
+        let request_body = json!({
+            "action": "notesInfo",
             "version": 6,
             "params": {
-                "query": "added:1"
+                "notes": [note_id]
             }
         });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!("Failed to get note info: {}", data["error"]));
+        }
+        data["result"][0]["modelName"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| anyhow!("Note {note_id} not found"))
+    }
 
-        let response = self
-            .client
-            .post(&self.anki_url)
-            .header("Content-Type", "application/json; charset=UTF-8")
-            .json(&request_body)
-            .send()
-            .await?;
+    /// 所有笔记类型的名称
+    pub async fn model_names(&self) -> Result<Vec<String>> {
+        let request_body = json!({
+            "action": "modelNames",
+            "version": 6
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!("Failed to get note types: {}", data["error"]));
+        }
+        let names = data["result"]
+            .as_array()
+            .ok_or_else(|| anyhow!("无法获取笔记类型列表"))?;
+        Ok(names
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect())
+    }
 
-        let data: Value = response.json().await?;
-        let results = data["result"]
+    /// 新建笔记类型，`templates` 为卡片模板的名称、正面与背面
+    pub async fn create_model(
+        &self,
+        name: &str,
+        fields: &[&str],
+        css: &str,
+        templates: &[(&str, String, String)],
+    ) -> Result<()> {
+        let card_templates: Vec<Value> = templates
+            .iter()
+            .map(|(name, front, back)| {
+                json!({
+                    "Name": name,
+                    "Front": front,
+                    "Back": back
+                })
+            })
+            .collect();
+        let request_body = json!({
+            "action": "createModel",
+            "version": 6,
+            "params": {
+                "modelName": name,
+                "inOrderFields": fields,
+                "css": css,
+                "cardTemplates": card_templates
+            }
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!(
+                "Failed to create note type {name}: {}",
+                data["error"]
+            ));
+        }
+        debug!("Note type created: {name}");
+        Ok(())
+    }
+
+    /// 笔记类型的所有字段名
+    pub async fn model_field_names(&self, model: &str) -> Result<Vec<String>> {
+        let request_body = json!({
+            "action": "modelFieldNames",
+            "version": 6,
+            "params": {
+                "modelName": model
+            }
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!(
+                "Failed to get fields of {model}: {}",
+                data["error"]
+            ));
+        }
+        let names = data["result"]
             .as_array()
-            .ok_or_else(|| anyhow!("无法获取搜索结果"))?;
+            .ok_or_else(|| anyhow!("无法获取笔记类型 {model} 的字段"))?;
+        Ok(names
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect())
+    }
 
-        if results.is_empty() {
-            return Err(anyhow!("没有找到任何卡片"));
+    /// 截图、录音要写入的卡片：默认为最新添加的卡片，新建模式下新建一张，锁定时为锁定的卡片
+    ///
+    /// 至少有一张，目标卡片在最前；配置了 noteCount 时之后是一并写入的更早的几张卡片。
+    pub async fn resolve_note_ids(&self) -> Result<Vec<u64>> {
+        if let Some(note_id) = *self.locked_note.lock().unwrap() {
+            return Ok(vec![note_id]);
         }
+        self.ensure_profile().await?;
+        if let Some(new_note) = &self.new_note {
+            return Ok(vec![self.add_note(new_note).await?]);
+        }
+        let note_ids = self.get_latest_note_ids(self.note_count.max(1)).await?;
+        if note_ids.len() > 1 {
+            debug!(
+                "Note {} also updates notes {:?}",
+                note_ids[0],
+                &note_ids[1..]
+            );
+        }
+        Ok(note_ids)
+    }
 
-        let mut note_ids: Vec<u64> = results.iter().filter_map(|v| v.as_u64()).collect();
-        note_ids.sort_by(|a, b| b.cmp(a));
+    /// 锁定当前最新的卡片，之后的截图、录音都写入它，直到再次调用解除锁定
+    ///
+    /// 避免晚到的录音写进之后添加的另一张卡片。
+    pub async fn toggle_note_lock(&self) -> Result<()> {
+        if let Some(note_id) = self.locked_note.lock().unwrap().take() {
+            info!("Unlocked note {note_id}");
+            return Ok(());
+        }
+        self.ensure_profile().await?;
+        let note_id = self.get_latest_note_id().await?;
+        *self.locked_note.lock().unwrap() = Some(note_id);
+        info!("Locked note {note_id}");
+        Ok(())
+    }
+
+    /// 配置了用户配置文件时确认 Anki 当前打开的是它，不是时先尝试切换，切换失败则报错
+    pub async fn ensure_profile(&self) -> Result<()> {
+        let Some(profile) = &self.profile else {
+            return Ok(());
+        };
+        let active = self.get_active_profile().await?;
+        if &active == profile {
+            return Ok(());
+        }
+        warn!("Anki profile {active} is active, switching to {profile}");
+        self.load_profile(profile).await?;
+        let active = self.get_active_profile().await?;
+        if &active != profile {
+            return Err(anyhow!(
+                "Anki 当前的用户配置文件是 {active}，不是配置的 {profile}，未写入卡片"
+            ));
+        }
+        info!("Switched Anki profile to {profile}");
+        Ok(())
+    }
+
+    pub async fn get_active_profile(&self) -> Result<String> {
+        let request_body = json!({
+            "action": "getActiveProfile",
+            "version": 6
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!("Failed to get active profile: {}", data["error"]));
+        }
+        data["result"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| anyhow!("无法获取 Anki 当前的用户配置文件"))
+    }
+
+    pub async fn load_profile(&self, profile: &str) -> Result<()> {
+        let request_body = json!({
+            "action": "loadProfile",
+            "version": 6,
+            "params": {
+                "name": profile
+            }
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() || data["result"] == json!(false) {
+            return Err(anyhow!(
+                "Failed to load profile {profile}: {}",
+                data["error"]
+            ));
+        }
+        Ok(())
+    }
+
+    /// 按配置的牌组、笔记类型与字段新建卡片，返回卡片 ID
+    pub async fn add_note(&self, new_note: &NewNote) -> Result<u64> {
+        let fields = render_fields(&new_note.fields, &get_foreground_window_name());
+        let request_body = json!({
+            "action": "addNote",
+            "version": 6,
+            "params": {
+                "note": {
+                    "deckName": new_note.deck_name,
+                    "modelName": new_note.model_name,
+                    "fields": fields,
+                    "tags": new_note.tags,
+                    // 同一游戏的卡片首字段往往相同
+                    "options": {
+                        "allowDuplicate": true
+                    }
+                }
+            }
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!("Failed to add note: {}", data["error"]));
+        }
+        let note_id = data["result"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("无法获取新卡片的ID"))?;
+        debug!("Note added: ID {note_id}, deck: {}", new_note.deck_name);
+        Ok(note_id)
+    }
+
+    pub async fn get_latest_note_id(&self) -> Result<u64> {
+        let note_ids = self.get_latest_note_ids(1).await?;
         note_ids
             .first()
             .copied()
             .ok_or_else(|| anyhow!("无法获取最新的卡片ID"))
     }
 
+    /// 最新的 `n` 张卡片，从新到旧；超过 maxNoteAgeMinutes 的卡片不计入
+    pub async fn get_latest_note_ids(&self, n: usize) -> Result<Vec<u64>> {
+        let mut note_ids = self.find_notes(&self.note_query).await?;
+        if note_ids.is_empty() {
+            return Err(anyhow!("没有找到任何卡片"));
+        }
+
+        note_ids.sort_by(|a, b| b.cmp(a));
+        note_ids.truncate(n);
+
+        // 卡片 ID 即添加时的毫秒时间戳
+        if let Some(max_age) = self.max_note_age {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+            let age = |note_id: u64| Duration::from_millis(now.saturating_sub(note_id));
+            let newest = age(note_ids[0]);
+            if newest > max_age {
+                return Err(anyhow!(
+                    "最新的卡片已添加 {} 分钟，超过了 {} 分钟的限制",
+                    newest.as_secs() / 60,
+                    max_age.as_secs() / 60
+                ));
+            }
+            note_ids.retain(|&note_id| age(note_id) <= max_age);
+        }
+        Ok(note_ids)
+    }
+
     pub async fn update_note_field(&self, note_id: u64, field: &str, value: &str) -> Result<()> {
+        let fields = BTreeMap::from([(field.to_string(), value.to_string())]);
+        self.update_note_fields(note_id, &fields).await
+    }
+
+    pub async fn find_notes(&self, query: &str) -> Result<Vec<u64>> {
+        let request_body = json!({
+            "action": "findNotes",
+            "version": 6,
+            "params": {
+                "query": query
+            }
+        });
+        let data = self.request(request_body).await?;
+        let results = data["result"]
+            .as_array()
+            .ok_or_else(|| anyhow!("无法获取搜索结果"))?;
+        Ok(results.iter().filter_map(|v| v.as_u64()).collect())
+    }
+
+    /// 一次请求更新多个字段
+    pub async fn update_note_fields(
+        &self,
+        note_id: u64,
+        fields: &BTreeMap<String, String>,
+    ) -> Result<()> {
         let request_body = json!({
             "action": "updateNoteFields",
             "version": 6,
             "params": {
                 "note": {
                     "id": note_id,
-                    "fields": {
-                        field: value
-                    }
+                    "fields": fields
                 }
             }
         });
-        let response = self
-            .client
-            .post(&self.anki_url)
-            .header("Content-Type", "application/json; charset=UTF-8")
-            .json(&request_body)
-            .send()
-            .await?;
-        let data: Value = response.json().await?;
+        let data = self.request(request_body).await?;
         if data["error"].is_null() {
-            debug!("Note updated successfully: ID {note_id}, Field: {field}, Value: {value}");
+            debug!("Note updated successfully: ID {note_id}, Fields: {fields:?}");
             Ok(())
         } else {
             Err(anyhow!("Failed to update note: {}", data["error"]))
         }
     }
 
+    pub async fn get_note_field(&self, note_id: u64, field: &str) -> Result<String> {
+        let mut fields = self.get_note_fields(note_id).await?;
+        match fields.remove(field) {
+            Some(value) => Ok(value),
+            None => Err(self.missing_field_error(note_id, field).await),
+        }
+    }
+
+    /// 字段不存在时的错误，附上笔记类型中最接近的字段名
+    async fn missing_field_error(&self, note_id: u64, field: &str) -> anyhow::Error {
+        let names = match self.get_note_model(note_id).await {
+            Ok(model) => self.model_field_names(&model).await,
+            Err(e) => Err(e),
+        };
+        match names {
+            Ok(names) => match closest_field(field, &names) {
+                Some(best) => {
+                    anyhow!("Note {note_id} has no field {field}, did you mean '{best}'?")
+                }
+                None => anyhow!(
+                    "Note {note_id} has no field {field}, available fields: {}",
+                    names.join(", ")
+                ),
+            },
+            Err(e) => {
+                debug!("Failed to get field names of note {note_id}: {e}");
+                anyhow!("Note {note_id} has no field {field}")
+            }
+        }
+    }
+
+    /// 读取卡片所有字段的当前内容
+    pub async fn get_note_fields(&self, note_id: u64) -> Result<HashMap<String, String>> {
+        let request_body = json!({
+            "action": "notesInfo",
+            "version": 6,
+            "params": {
+                "notes": [note_id]
+            }
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!("Failed to get note info: {}", data["error"]));
+        }
+        let fields = data["result"][0]["fields"]
+            .as_object()
+            .ok_or_else(|| anyhow!("Note {note_id} not found"))?;
+        Ok(fields
+            .iter()
+            .filter_map(|(name, field)| Some((name.clone(), field["value"].as_str()?.to_string())))
+            .collect())
+    }
+
+    /// 在采集时记下游戏窗口并渲染其他字段，来源字段随每次采集一起更新
+    pub fn capture(&self, templates: &BTreeMap<String, String>) -> Capture {
+        let mut templates = templates.clone();
+        if let Some((field, template)) = &self.source_field {
+            templates
+                .entry(field.clone())
+                .or_insert_with(|| template.clone());
+        }
+        let game = get_foreground_window_name();
+        let extra_fields = render_fields(&templates, &game);
+        Capture { game, extra_fields }
+    }
+
+    /// 按字段模式写入：追加模式会先读取字段现有内容再拼接，写入后按采集时的游戏加上配置的标签
+    pub async fn write_note_field(
+        &self,
+        note_ids: &[u64],
+        field: &str,
+        value: &str,
+        mode: &FieldMode,
+        game: &str,
+    ) -> Result<()> {
+        let fields = [(field.to_string(), value.to_string())];
+        let capture = Capture {
+            game: game.to_string(),
+            ..Capture::default()
+        };
+        self.write_note_fields(note_ids, &fields, mode, &capture)
+            .await
+    }
+
+    /// 一次写入多个字段：`fields` 按字段模式写入，[`Capture`] 中渲染好的其他字段覆盖写入，标签按其中的游戏填写
+    ///
+    /// `note_ids` 为 [`resolve_note_ids`](Self::resolve_note_ids) 找到的卡片，目标卡片在最前。
+    /// 写入前的内容会记入历史，供撤销使用。
+    pub async fn write_note_fields(
+        &self,
+        note_ids: &[u64],
+        fields: &[(String, String)],
+        mode: &FieldMode,
+        capture: &Capture,
+    ) -> Result<()> {
+        let extra = &capture.extra_fields;
+        // 同一字段的多项内容依次拼接
+        let mut merged: BTreeMap<String, String> = BTreeMap::new();
+        for (field, value) in fields {
+            merged.entry(field.clone()).or_default().push_str(value);
+        }
+        let Some(&note_id) = note_ids.first() else {
+            return Ok(());
+        };
+        if merged.is_empty() && extra.is_empty() {
+            return Ok(());
+        }
+
+        let mut written = Vec::new();
+        let mut skipped = Vec::new();
+        for &target in note_ids {
+            match self
+                .write_single_note(target, &merged, mode, extra, &mut skipped)
+                .await
+            {
+                Ok(Some(note)) => written.push(note),
+                Ok(None) => {}
+                Err(e) if target == note_id => return Err(e),
+                // 其他卡片写入失败不影响目标卡片
+                Err(e) => warn!("Failed to update note {target}: {e}"),
+            }
+        }
+        self.delete_orphaned_media(&skipped, &written).await;
+        if written.is_empty() {
+            return Ok(());
+        }
+        self.record_update(&written);
+        // 标签只是方便查找，失败不影响已写入的字段
+        for (target, _, _) in &written {
+            if let Err(e) = self.add_tags(*target, &capture.game).await {
+                warn!("Failed to tag note {target}: {e}");
+            }
+        }
+        if self.browse_after_update
+            && let Err(e) = self.gui_browse(note_id).await
+        {
+            warn!("Failed to open note {note_id} in browser: {e}");
+        }
+        if let Some(every) = self.sync_after_updates {
+            let count = self.unsynced.fetch_add(1, Ordering::Relaxed) + 1;
+            if count >= every {
+                self.sync_pending().await;
+            }
+        }
+        Ok(())
+    }
+
+    /// 写入一张卡片，没有需要写入的字段时返回 None；被保护而跳过的内容放入 `skipped`
+    async fn write_single_note(
+        &self,
+        note_id: u64,
+        merged: &BTreeMap<String, String>,
+        mode: &FieldMode,
+        extra: &BTreeMap<String, String>,
+        skipped: &mut Vec<String>,
+    ) -> Result<Option<WrittenNote>> {
+        let mut values = extra.clone();
+        let current = self.get_note_fields(note_id).await?;
+        // AnkiConnect 会静默忽略不存在的字段，写入前先检查
+        if let Some(field) = merged
+            .keys()
+            .chain(values.keys())
+            .find(|field| !current.contains_key(*field))
+        {
+            return Err(self.missing_field_error(note_id, field).await);
+        }
+        for (field, value) in merged {
+            let current = &current[field];
+            let value = match mode {
+                FieldMode::Overwrite => value.clone(),
+                FieldMode::Append => format!("{current}{value}"),
+            };
+            values.insert(field.clone(), value);
+        }
+        // 只保护句子、图片与音频字段，extraFields 中的来源等字段总是覆盖写入
+        if self.protect_non_empty_fields && matches!(mode, FieldMode::Overwrite) {
+            values.retain(|field, value| {
+                let existing = current.get(field).map_or("", |v| v.trim());
+                if existing.is_empty() || !merged.contains_key(field) {
+                    return true;
+                }
+                warn!("Skipped non-empty field {field} of note {note_id}");
+                skipped.push(std::mem::take(value));
+                false
+            });
+            // 媒体字段都被跳过时，不单独写入其他字段
+            if !merged.is_empty() && !values.keys().any(|field| merged.contains_key(field)) {
+                return Ok(None);
+            }
+        }
+        self.update_note_fields(note_id, &values).await?;
+        Ok(Some((note_id, current, values)))
+    }
+
+    /// 有尚未同步的写入时同步一次，退出时也会调用
+    pub async fn sync_pending(&self) {
+        if self.sync_after_updates.is_none() || self.unsynced.swap(0, Ordering::Relaxed) == 0 {
+            return;
+        }
+        if let Err(e) = self.sync().await {
+            warn!("Failed to sync Anki: {e}");
+        }
+    }
+
+    /// 触发 Anki 同步，让 AnkiDroid 尽快拿到媒体
+    pub async fn sync(&self) -> Result<()> {
+        let request_body = json!({
+            "action": "sync",
+            "version": 6
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!("Failed to sync: {}", data["error"]));
+        }
+        info!("Anki synced");
+        Ok(())
+    }
+
+    /// 删除只被跳过的内容引用、没有写入任何卡片的本次运行存入的媒体
+    async fn delete_orphaned_media(&self, skipped: &[String], written: &[WrittenNote]) {
+        if skipped.is_empty() {
+            return;
+        }
+        let orphaned: Vec<String> = self
+            .session_media
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|name| skipped.iter().any(|value| value.contains(name.as_str())))
+            .filter(|name| {
+                !written
+                    .iter()
+                    .flat_map(|(_, _, values)| values.values())
+                    .any(|value| value.contains(name.as_str()))
+            })
+            .cloned()
+            .collect();
+        for file in orphaned {
+            if let Err(e) = self.delete_media(&file).await {
+                warn!("Failed to delete media {file}: {e}");
+            }
+        }
+    }
+
+    /// 记下写入前的字段内容，以及写入后才出现在字段中的本次运行存入的媒体
+    fn record_update(&self, written: &[WrittenNote]) {
+        let notes: Vec<(u64, BTreeMap<String, String>)> = written
+            .iter()
+            .map(|(note_id, current, values)| {
+                let previous = values
+                    .keys()
+                    .map(|field| {
+                        (
+                            field.clone(),
+                            current.get(field).cloned().unwrap_or_default(),
+                        )
+                    })
+                    .collect();
+                (*note_id, previous)
+            })
+            .collect();
+        let media = self
+            .session_media
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|name| {
+                written
+                    .iter()
+                    .zip(&notes)
+                    .any(|((_, _, values), (_, previous))| {
+                        values.iter().any(|(field, value)| {
+                            value.contains(name.as_str())
+                                && !previous[field].contains(name.as_str())
+                        })
+                    })
+            })
+            .cloned()
+            .collect();
+        let mut history = self.history.lock().unwrap();
+        if history.len() >= UNDO_HISTORY {
+            history.pop_front();
+        }
+        history.push_back(UpdateRecord { notes, media });
+    }
+
+    /// 撤销最近一次字段写入：恢复写入前的内容，并删除随之存入、已不再被引用的媒体
+    pub async fn undo_last_update(&self) -> Result<()> {
+        let Some(record) = self.history.lock().unwrap().pop_back() else {
+            return Err(anyhow!("没有可以撤销的写入"));
+        };
+        for (note_id, previous) in &record.notes {
+            if let Err(e) = self.update_note_fields(*note_id, previous).await {
+                // 恢复失败时放回历史，之后可以再试
+                self.history.lock().unwrap().push_back(record);
+                return Err(e);
+            }
+        }
+        for file in &record.media {
+            if let Err(e) = self.delete_media(file).await {
+                warn!("Failed to delete media {file}: {e}");
+            }
+        }
+        info!(
+            "Undid last update on {} note(s), {} media file(s)",
+            record.notes.len(),
+            record.media.len()
+        );
+        Ok(())
+    }
+
+    /// 在 Anki 的浏览器中打开卡片
+    pub async fn gui_browse(&self, note_id: u64) -> Result<()> {
+        let request_body = json!({
+            "action": "guiBrowse",
+            "version": 6,
+            "params": {
+                "query": format!("nid:{note_id}")
+            }
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!("Failed to browse note: {}", data["error"]));
+        }
+        debug!("Note opened in browser: ID {note_id}");
+        Ok(())
+    }
+
+    /// 加上配置的标签，`{game}` 替换为采集时的游戏窗口标题
+    async fn add_tags(&self, note_id: u64, game: &str) -> Result<()> {
+        if self.tags.is_empty() {
+            return Ok(());
+        }
+        // Anki 的标签以空格分隔，窗口标题中的空白替换为下划线
+        let game: String = game.split_whitespace().collect::<Vec<_>>().join("_");
+        let tags: Vec<String> = self
+            .tags
+            .iter()
+            .map(|tag| tag.replace("{game}", &game))
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        let tags = tags.join(" ");
+        // 同一张卡片第一次写入时加标签，之后只在标签变化（例如换了游戏）时再加
+        if self.tagged.lock().unwrap().get(&note_id) == Some(&tags) {
+            return Ok(());
+        }
+        let request_body = json!({
+            "action": "addTags",
+            "version": 6,
+            "params": {
+                "notes": [note_id],
+                "tags": tags
+            }
+        });
+        let data = self.request(request_body).await?;
+        if !data["error"].is_null() {
+            return Err(anyhow!("Failed to add tags: {}", data["error"]));
+        }
+        debug!("Note tagged: ID {note_id}, tags: {tags}");
+        self.tagged.lock().unwrap().insert(note_id, tags);
+        Ok(())
+    }
+
+    /// 写入前检查重复，避免误按两次热键时同一张卡片被写入两遍；返回 true 表示应跳过
+    ///
+    /// 字段里已有本次运行写入的媒体，或同样内容的媒体已经存入过，都视为重复。
+    pub async fn is_duplicate_media(&self, note_id: u64, field: &str, data: &[u8]) -> bool {
+        if let DuplicateAction::Ignore = self.duplicate_media {
+            return false;
+        }
+        let reason = match self.find_duplicate(note_id, field, data).await {
+            Ok(Some(reason)) => reason,
+            Ok(None) => return false,
+            Err(e) => {
+                warn!("Failed to check duplicate media on note {note_id}: {e}");
+                return false;
+            }
+        };
+        match self.duplicate_media {
+            DuplicateAction::Skip => {
+                warn!("Skipped writing to {field} of note {note_id}: {reason}");
+                true
+            }
+            _ => {
+                warn!("Possible duplicate in {field} of note {note_id}: {reason}");
+                false
+            }
+        }
+    }
+
+    async fn find_duplicate(
+        &self,
+        note_id: u64,
+        field: &str,
+        data: &[u8],
+    ) -> Result<Option<String>> {
+        if let Some(existing) = self.session_media.lock().unwrap().get(&media_hash(data)) {
+            return Ok(Some(format!(
+                "identical media already stored as {existing}"
+            )));
+        }
+        let current = self.get_note_field(note_id, field).await?;
+        let session_media = self.session_media.lock().unwrap();
+        Ok(session_media
+            .values()
+            .find(|name| current.contains(name.as_str()))
+            .map(|name| format!("field already contains {name} from this session")))
+    }
+
+    /// 把媒体文件存入 Anki 并存档，本地模式写入媒体目录，上传模式通过 storeMediaFile 发送
+    ///
+    /// 内容相同的文件已经存入过时（例如误按两次热键）沿用已有的文件，不再写入一份；
+    /// 返回实际使用的文件名，写入字段时要用它。
+    pub async fn store_media(&self, filename: &str, data: &[u8]) -> Result<String> {
+        if let Some(existing) = self.existing_media(data).await {
+            debug!("Identical media already stored as {existing}, reusing it for {filename}");
+            *self
+                .reused_media
+                .lock()
+                .unwrap()
+                .entry(existing.clone())
+                .or_default() += 1;
+            return Ok(existing);
+        }
+        match self.media_transfer {
+            MediaTransfer::Local => {
+                let file_path = Path::new(&self.get_media_dir().await?).join(filename);
+                fs::write(&file_path, data)?;
+                debug!("Media saved to file: {}", file_path.display());
+                if let Err(e) = self.verify_media(filename).await {
+                    // 写到了 Anki 已不再使用的目录，留着也没有用
+                    fs::remove_file(&file_path).ok();
+                    return Err(e);
+                }
+                self.archive_media(&file_path);
+            }
+            MediaTransfer::Upload => {
+                let request_body = json!({
+                    "action": "storeMediaFile",
+                    "version": 6,
+                    "params": {
+                        "filename": filename,
+                        "data": STANDARD.encode(data)
+                    }
+                });
+                let data_len = data.len();
+                let response = self.request(request_body).await?;
+                if !response["error"].is_null() {
+                    return Err(anyhow!("Failed to store media file: {}", response["error"]));
+                }
+                debug!("Media uploaded: {filename} ({data_len} bytes)");
+                self.verify_media(filename).await?;
+                self.archive_data(filename, data);
+            }
+        }
+        self.remember_media(filename, data);
+        Ok(filename.to_string())
+    }
+
+    /// 媒体目录中匹配通配符 `pattern` 的文件名
+    pub async fn media_file_names(&self, pattern: &str) -> Result<Vec<String>> {
+        let request_body = json!({
+            "action": "getMediaFilesNames",
+            "version": 6,
+            "params": {
+                "pattern": pattern
+            }
+        });
+        let response = self.request(request_body).await?;
+        if !response["error"].is_null() {
+            return Err(anyhow!("Failed to list media files: {}", response["error"]));
+        }
+        let names = response["result"]
+            .as_array()
+            .ok_or_else(|| anyhow!("无法获取媒体文件列表"))?;
+        Ok(names
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect())
+    }
+
+    /// 确认 Anki 能看到刚存入的媒体文件，避免字段引用一个不存在的文件
+    pub async fn verify_media(&self, filename: &str) -> Result<()> {
+        if !self.verify_media {
+            return Ok(());
+        }
+        let found = self
+            .media_file_names(filename)
+            .await?
+            .iter()
+            .any(|name| name == filename);
+        if !found {
+            return Err(anyhow!(
+                "媒体文件 {filename} 存入后在 Anki 中找不到，媒体目录可能已失效（例如切换了用户配置文件）"
+            ));
+        }
+        debug!("Media verified: {filename}");
+        Ok(())
+    }
+
+    /// 本次运行已存入的内容相同的媒体；本地模式下确认媒体目录中的文件仍然相同
+    async fn existing_media(&self, data: &[u8]) -> Option<String> {
+        let existing = self
+            .session_media
+            .lock()
+            .unwrap()
+            .get(&media_hash(data))
+            .cloned()?;
+        match self.media_transfer {
+            MediaTransfer::Local => {
+                let media_dir = self.get_media_dir().await.ok()?;
+                let current = fs::read(Path::new(&media_dir).join(&existing)).ok()?;
+                (current == data).then_some(existing)
+            }
+            MediaTransfer::Upload => Some(existing),
+        }
+    }
+
+    /// 记录本次运行存入的媒体，供重复检查；直接写入媒体目录的录制也要调用
+    pub fn remember_media(&self, filename: &str, data: &[u8]) {
+        self.session_media
+            .lock()
+            .unwrap()
+            .insert(media_hash(data), filename.to_string());
+    }
+
+    /// 删除已存入的媒体文件，用于重拍、重录时丢弃上一份
+    pub async fn delete_media(&self, filename: &str) -> Result<()> {
+        // 文件还被别的截图、录音沿用时只减少引用
+        {
+            let mut reused = self.reused_media.lock().unwrap();
+            if let Some(count) = reused.get_mut(filename) {
+                *count -= 1;
+                if *count == 0 {
+                    reused.remove(filename);
+                }
+                debug!("Media {filename} is still in use, keeping it");
+                return Ok(());
+            }
+        }
+        self.session_media
+            .lock()
+            .unwrap()
+            .retain(|_, name| name != filename);
+        match self.media_transfer {
+            MediaTransfer::Local => {
+                let file_path = Path::new(&self.get_media_dir().await?).join(filename);
+                fs::remove_file(&file_path)?;
+            }
+            MediaTransfer::Upload => {
+                let request_body = json!({
+                    "action": "deleteMediaFile",
+                    "version": 6,
+                    "params": {
+                        "filename": filename
+                    }
+                });
+                let response = self.request(request_body).await?;
+                if !response["error"].is_null() {
+                    return Err(anyhow!(
+                        "Failed to delete media file: {}",
+                        response["error"]
+                    ));
+                }
+            }
+        }
+        debug!("Media deleted: {filename}");
+        Ok(())
+    }
+
+    /// 本地模式下媒体文件在媒体目录中的路径，供需要边写边存的录制使用；上传模式返回 None
+    pub async fn local_media_path(&self, filename: &str) -> Result<Option<PathBuf>> {
+        match self.media_transfer {
+            MediaTransfer::Local => {
+                Ok(Some(Path::new(&self.get_media_dir().await?).join(filename)))
+            }
+            MediaTransfer::Upload => Ok(None),
+        }
+    }
+
+    /// 把已写入媒体目录的文件存档到本地，删卡后仍能找回；失败只记录日志
+    ///
+    /// 优先创建硬链接以节省空间，跨磁盘等无法链接时改为复制。
+    pub fn archive_media(&self, file: &Path) {
+        let Some(name) = file.file_name() else {
+            return;
+        };
+        let Some(target) = self.archive_target(Path::new(name)) else {
+            return;
+        };
+        let result = fs::hard_link(file, &target).or_else(|_| fs::copy(file, &target).map(|_| ()));
+        match result {
+            Ok(()) => debug!("Archived {} to {}", file.display(), target.display()),
+            Err(e) => error!("Failed to archive {}: {e}", file.display()),
+        }
+    }
+
+    /// 上传模式下本机没有媒体文件，直接把数据写入存档
+    fn archive_data(&self, filename: &str, data: &[u8]) {
+        let Some(target) = self.archive_target(Path::new(filename)) else {
+            return;
+        };
+        match fs::write(&target, data) {
+            Ok(()) => debug!("Archived {filename} to {}", target.display()),
+            Err(e) => error!("Failed to archive {filename}: {e}"),
+        }
+    }
+
+    /// 存档路径：存档目录下按日期分的子目录，未配置存档目录或无法创建时返回 None
+    fn archive_target(&self, name: &Path) -> Option<PathBuf> {
+        let archive_dir = self.archive_dir.as_ref()?;
+        let dir = archive_dir.join(chrono::Local::now().format("%Y-%m-%d").to_string());
+        if let Err(e) = fs::create_dir_all(&dir) {
+            error!("Failed to create archive directory {}: {e}", dir.display());
+            return None;
+        }
+        Some(dir.join(name))
+    }
+
     pub async fn get_media_dir(&self) -> Result<String> {
         let request_body = json!({
             "action": "getMediaDirPath",
             "version": 6
         });
-        let response: Value = self
-            .client
-            .post(&self.anki_url)
-            .json(&request_body)
-            .send()
-            .await?
-            .json()
-            .await?;
+        let response = self.request(request_body).await?;
         response["result"]
             .as_str()
             .ok_or_else(|| anyhow!("Failed to get media directory"))
             .map(|s| s.to_string())
     }
 }
+
+/// 按配置的超时与 TLS 设置创建 HTTP 客户端
+fn build_client(cfg: &Anki) -> Result<Client> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(cfg.request_timeout_secs))
+        .danger_accept_invalid_certs(cfg.accept_invalid_certs);
+    if !cfg.ca_cert.is_empty() {
+        let pem = fs::read(&cfg.ca_cert)
+            .with_context(|| format!("Failed to read CA certificate: {}", cfg.ca_cert))?;
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+    }
+    if !cfg.client_cert.is_empty() {
+        let der = fs::read(&cfg.client_cert)
+            .with_context(|| format!("Failed to read client certificate: {}", cfg.client_cert))?;
+        let identity = reqwest::Identity::from_pkcs12_der(&der, &cfg.client_cert_password)?;
+        builder = builder.identity(identity);
+    }
+    Ok(builder.build()?)
+}
+
+/// 渲染字段模板，`{source}`、`{game}` 替换为游戏窗口标题，`{time}` 替换为当前时间，`{date}` 替换为当前日期
+fn render_fields(templates: &BTreeMap<String, String>, source: &str) -> BTreeMap<String, String> {
+    if templates.is_empty() {
+        return BTreeMap::new();
+    }
+    let now = chrono::Local::now();
+    let time = now.format("%Y-%m-%d %H:%M:%S").to_string();
+    let date = now.format("%Y-%m-%d").to_string();
+    templates
+        .iter()
+        .map(|(field, template)| {
+            let value = template
+                .replace("{source}", source)
+                .replace("{game}", source)
+                .replace("{time}", &time)
+                .replace("{date}", &date);
+            (field.clone(), value)
+        })
+        .collect()
+}
+
+/// 大小写不敏感的编辑距离足够小时，返回最接近的字段名
+fn closest_field<'a>(field: &str, names: &'a [String]) -> Option<&'a str> {
+    let field = field.to_lowercase();
+    let max_distance = field.chars().count() / 3 + 1;
+    names
+        .iter()
+        .map(|name| (edit_distance(&field, &name.to_lowercase()), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name.as_str())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// 按模板生成写入字段的媒体内容，`{file}` 替换为媒体文件名，`{game}` 替换为采集时的游戏窗口标题
+///
+/// 也可以写成 `{{file}}`、`{{game}}`。
+pub fn render_media_value(template: &str, filename: &str, game: &str) -> String {
+    template
+        .replace("{{file}}", filename)
+        .replace("{{game}}", game)
+        .replace("{file}", filename)
+        .replace("{game}", game)
+}
+
+/// 转义 Anki 搜索中的特殊字符，结果放在双引号中按字面匹配
+pub fn escape_search(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '"' | '*' | '_' | ':') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn media_hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 错误是否因为连不上 AnkiConnect，例如 Anki 没有运行
+///
+/// 超时不算：请求可能已经送达并执行，例如存入了媒体或追加了字段，重放会重复写入。
+pub fn is_unreachable(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<reqwest::Error>()
+            && e.is_connect()
+        {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_single_char_edits() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("abc", "abc"), 0);
+        // 按字符而不是字节计算
+        assert_eq!(edit_distance("句子", "句"), 1);
+    }
+
+    #[test]
+    fn closest_field_ignores_case_and_small_typos() {
+        let names = ["Picture".to_string(), "Sentence".to_string()];
+        assert_eq!(closest_field("picture", &names), Some("Picture"));
+        assert_eq!(closest_field("Sentense", &names), Some("Sentence"));
+        assert_eq!(closest_field("Audio", &names), None);
+    }
+}
@@ -0,0 +1,57 @@
+use log::warn;
+use std::collections::VecDeque;
+
+/// 带容量上限的采样缓冲，写满后丢弃最旧的数据
+pub struct SampleBuffer {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    overflowed: bool,
+}
+
+impl SampleBuffer {
+    /// 按内存上限（MB）创建缓冲，容量向下对齐到整帧
+    pub fn with_memory_cap(max_mb: usize, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        let capacity = max_mb * 1024 * 1024 / size_of::<f32>() / channels * channels;
+        Self {
+            samples: VecDeque::new(),
+            capacity: capacity.max(channels),
+            overflowed: false,
+        }
+    }
+
+    /// 追加整帧采样，超出容量时从头部丢弃
+    pub fn extend(&mut self, samples: &[f32]) {
+        if samples.len() >= self.capacity {
+            self.samples.clear();
+            self.samples
+                .extend(&samples[samples.len() - self.capacity..]);
+            self.on_overflow();
+            return;
+        }
+        let excess = (self.samples.len() + samples.len()).saturating_sub(self.capacity);
+        if excess > 0 {
+            self.samples.drain(..excess);
+            self.on_overflow();
+        }
+        self.samples.extend(samples);
+    }
+
+    fn on_overflow(&mut self) {
+        if !self.overflowed {
+            self.overflowed = true;
+            warn!("Audio buffer is full, dropping the oldest samples");
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.overflowed = false;
+    }
+
+    /// 取出全部采样并清空缓冲，不做整体复制
+    pub fn take(&mut self) -> Vec<f32> {
+        self.overflowed = false;
+        Vec::from(std::mem::take(&mut self.samples))
+    }
+}
@@ -0,0 +1,177 @@
+use super::{
+    AudioRecorder, Res, SILENCE_THRESHOLD,
+    buffer::SampleBuffer,
+    encode::{AudioMetadata, AudioStream},
+    split::SilenceStripper,
+};
+use crate::config::{self, AudioRecord, AudioSource, CondensedAudio};
+use log::{debug, error, info};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::JoinHandle;
+
+/// 两次搬运之间采集缓冲的内存上限
+const CAPTURE_BUFFER_MB: usize = 16;
+
+/// 整个游戏过程的后台录音，去除静音后在结束时导出为一个“浓缩音频”文件
+pub struct CondensedRecorder {
+    cfg: CondensedAudio,
+    sample_rate: u32,
+    channels: u16,
+    is_recording: Arc<Mutex<bool>>,
+    capture_buffer: Arc<Mutex<SampleBuffer>>,
+    condenser: Arc<Mutex<Option<Condenser>>>,
+    drain_task: JoinHandle<()>,
+}
+
+/// 去除静音后立刻送进编码器，内存里只留编码器状态和已编码的数据
+struct Condenser {
+    stripper: SilenceStripper,
+    stream: AudioStream,
+    /// 已保留的采样数
+    kept: usize,
+}
+
+impl Condenser {
+    fn push(&mut self, samples: &[f32]) -> Res<()> {
+        self.stripper.push(samples);
+        let output = self.stripper.take_output();
+        Self::encode(&mut self.stream, &mut self.kept, &output)
+    }
+
+    fn encode(stream: &mut AudioStream, kept: &mut usize, samples: &[f32]) -> Res<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        *kept += samples.len();
+        stream.push(samples)
+    }
+
+    /// 收尾并返回文件内容与保留的采样数
+    fn finish(mut self) -> Res<(Vec<u8>, usize)> {
+        let rest = self.stripper.finish();
+        Self::encode(&mut self.stream, &mut self.kept, &rest)?;
+        Ok((self.stream.finish()?, self.kept))
+    }
+}
+
+impl CondensedRecorder {
+    pub fn start(cfg: CondensedAudio, audio_cfg: AudioRecord) -> Res<Self> {
+        let channels = 2;
+        let sample_rate = audio_cfg.sample_rate;
+        // 编码参数沿用录音配置，仅格式由浓缩音频配置决定
+        let encode_cfg = AudioRecord {
+            format: cfg.format.clone(),
+            ..audio_cfg
+        };
+        let is_recording = Arc::new(Mutex::new(true));
+        let capture_buffer = Arc::new(Mutex::new(SampleBuffer::with_memory_cap(
+            CAPTURE_BUFFER_MB,
+            channels,
+        )));
+        let metadata = AudioMetadata::new("Condensed audio");
+        let condenser = Arc::new(Mutex::new(Some(Condenser {
+            stripper: SilenceStripper::new(
+                sample_rate,
+                channels,
+                SILENCE_THRESHOLD,
+                cfg.min_silence_ms,
+            ),
+            stream: AudioStream::new(&encode_cfg, &metadata, sample_rate, channels)?,
+            kept: 0,
+        })));
+
+        let is_rec = Arc::clone(&is_recording);
+        let buf = Arc::clone(&capture_buffer);
+        thread::Builder::new()
+            .name("CondensedCapture".into())
+            .spawn(move || {
+                let _ = wasapi::initialize_mta();
+                let on_samples = |samples: &[f32]| buf.lock().unwrap().extend(samples);
+                if let Err(e) = AudioRecorder::capture_loop(
+                    is_rec,
+                    &AudioSource::Loopback,
+                    sample_rate as usize,
+                    channels,
+                    on_samples,
+                ) {
+                    error!("Condensed audio capture failed: {e}");
+                }
+            })?;
+
+        // 定期把采集缓冲搬进去静音处理器并编码，避免整段会话的 PCM 堆在内存里
+        let drain_task = {
+            let capture_buffer = Arc::clone(&capture_buffer);
+            let condenser = Arc::clone(&condenser);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = Self::drain(&capture_buffer, &condenser) {
+                        error!("Failed to encode condensed audio: {e}");
+                    }
+                }
+            })
+        };
+
+        info!("Condensed audio recording started");
+        Ok(Self {
+            cfg,
+            sample_rate,
+            channels,
+            is_recording,
+            capture_buffer,
+            condenser,
+            drain_task,
+        })
+    }
+
+    fn drain(
+        capture_buffer: &Mutex<SampleBuffer>,
+        condenser: &Mutex<Option<Condenser>>,
+    ) -> Res<()> {
+        let chunk = capture_buffer.lock().unwrap().take();
+        match condenser.lock().unwrap().as_mut() {
+            Some(condenser) if !chunk.is_empty() => condenser.push(&chunk),
+            _ => Ok(()),
+        }
+    }
+
+    /// 停止录音并导出文件，返回导出路径；整段会话无有效声音时返回 `None`
+    pub async fn finish(self) -> Res<Option<PathBuf>> {
+        *self.is_recording.lock().unwrap() = false;
+        self.drain_task.abort();
+        // 等待录音线程真正退出
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Self::drain(&self.capture_buffer, &self.condenser)?;
+
+        let Some(condenser) = self.condenser.lock().unwrap().take() else {
+            return Ok(None);
+        };
+        let (raw, kept) = condenser.finish()?;
+        if kept == 0 {
+            info!("No audio captured for condensed export");
+            return Ok(None);
+        }
+        debug!(
+            "Condensed audio: {:.1}s kept",
+            kept as f32 / (self.sample_rate as f32 * self.channels as f32)
+        );
+
+        let output_dir = config::data_path(&self.cfg.output_dir);
+        fs::create_dir_all(&output_dir)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let file_path = output_dir.join(format!("condensed_{timestamp}.{}", self.cfg.format));
+        fs::write(&file_path, raw)?;
+        info!("Condensed audio exported to: {}", file_path.display());
+        Ok(Some(file_path))
+    }
+}
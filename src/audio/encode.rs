@@ -1,200 +1,421 @@
 use super::Res;
-use crate::config::AudioFormat;
+use crate::config::{
+    AudioChannels, AudioFormat, AudioRecord, Mp3EncodeConfig, Mp3Mode, OpusApplication,
+    OpusEncodeConfig,
+};
 use log::{error, info};
-use mp3lame_encoder::{Builder, FlushNoGap, InterleavedPcm, MonoPcm};
+use mp3lame_encoder::{
+    Bitrate, Builder, FlushNoGap, Id3Tag, InterleavedPcm, MonoPcm, Quality, VbrMode,
+};
 use ogg::{PacketWriteEndInfo, writing::PacketWriter};
 use opus::{Application, Channels, Encoder};
 use std::io::Cursor;
 
+/// 写入音频文件的元数据标签，便于从 Anki 导出后仍能辨认来源
+pub struct AudioMetadata {
+    pub title: String,
+    pub date: String,
+}
+
+impl AudioMetadata {
+    pub const SOURCE: &'static str = "game2anki";
+
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
 pub fn encode(
-    format: AudioFormat,
+    cfg: &AudioRecord,
+    metadata: &AudioMetadata,
     samples: &[f32],
     sample_rate: u32,
     channels: u16,
 ) -> Res<Vec<u8>> {
-    match format {
-        AudioFormat::Opus => encode_to_ogg_opus(samples, sample_rate, channels),
-        AudioFormat::Mp3 => encode_to_mp3(samples, sample_rate, channels),
+    let mut stream = AudioStream::new(cfg, metadata, sample_rate, channels)?;
+    stream.push(samples)?;
+    stream.finish()
+}
+
+/// 将交错排列的多声道采样平均下混为单声道
+pub fn downmix_to_mono(samples: &[f32], channels: u16) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// 可逐段喂入采样的编码器
+trait StreamEncoder: Send {
+    fn push(&mut self, samples: &[f32]) -> Res<()>;
+    fn finish(self: Box<Self>) -> Res<Vec<u8>>;
+}
+
+/// 流式音频编码：录音时边采集边编码，停止时只需收尾
+pub struct AudioStream {
+    encoder: Box<dyn StreamEncoder>,
+    /// 需要下混为单声道时记录输入声道数
+    downmix_from: Option<u16>,
+}
+
+impl AudioStream {
+    pub fn new(
+        cfg: &AudioRecord,
+        metadata: &AudioMetadata,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Res<Self> {
+        let (downmix_from, channels) = match cfg.channels {
+            AudioChannels::Mono if channels > 1 => (Some(channels), 1),
+            _ => (None, channels),
+        };
+        let encoder: Box<dyn StreamEncoder> = match cfg.format {
+            AudioFormat::Opus => Box::new(OggOpusStream::new(
+                sample_rate,
+                channels,
+                &cfg.opus,
+                metadata,
+            )?),
+            AudioFormat::Mp3 => {
+                Box::new(Mp3Stream::new(sample_rate, channels, &cfg.mp3, metadata)?)
+            }
+        };
+        Ok(Self {
+            encoder,
+            downmix_from,
+        })
+    }
+
+    /// 追加交错排列的整帧采样
+    pub fn push(&mut self, samples: &[f32]) -> Res<()> {
+        match self.downmix_from {
+            Some(channels) => self.encoder.push(&downmix_to_mono(samples, channels)),
+            None => self.encoder.push(samples),
+        }
+    }
+
+    /// 刷新剩余数据并返回完整文件内容
+    pub fn finish(self) -> Res<Vec<u8>> {
+        self.encoder.finish()
     }
 }
 
-pub fn encode_to_ogg_opus(samples: &[f32], sample_rate: u32, channels: u16) -> Res<Vec<u8>> {
-    info!(
-        "Preparing to encode to Ogg Opus: sample_rate={}, channels={}, samples_len={}",
-        sample_rate,
-        channels,
-        samples.len()
-    );
-    let opus_channels = match channels {
-        1 => Channels::Mono,
-        2 => Channels::Stereo,
-        _ => {
-            error!("Unsupported channel count for Opus: {channels}");
-            return Err("Unsupported channel count".into());
+struct OggOpusStream {
+    encoder: Encoder,
+    writer: PacketWriter<'static, Cursor<Vec<u8>>>,
+    channels: usize,
+    pending: Vec<f32>,
+    abs_gp: u64,
+}
+
+impl OggOpusStream {
+    const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+
+    fn new(
+        sample_rate: u32,
+        channels: u16,
+        opus_cfg: &OpusEncodeConfig,
+        metadata: &AudioMetadata,
+    ) -> Res<Self> {
+        info!(
+            "Preparing to encode to Ogg Opus: sample_rate={sample_rate}, channels={channels}, {opus_cfg:?}"
+        );
+        let opus_channels = match channels {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            _ => {
+                error!("Unsupported channel count for Opus: {channels}");
+                return Err("Unsupported channel count".into());
+            }
+        };
+
+        let application = match opus_cfg.application {
+            OpusApplication::Voip => Application::Voip,
+            OpusApplication::Audio => Application::Audio,
+            OpusApplication::LowDelay => Application::LowDelay,
+        };
+        let encoder = Encoder::new(sample_rate, opus_channels, application);
+        let mut encoder = match encoder {
+            Ok(enc) => enc,
+            Err(e) => {
+                error!(
+                    "Failed to create Opus encoder: sample_rate={sample_rate}, channels={opus_channels:?}, error={e}"
+                );
+                return Err(e.into());
+            }
+        };
+        if let Err(e) = encoder.set_bitrate(opus::Bitrate::Bits(opus_cfg.bitrate as i32 * 1000)) {
+            error!("Failed to set Opus bitrate: {e}");
         }
-    };
-
-    let encoder = Encoder::new(sample_rate, opus_channels, Application::Audio);
-    let mut encoder = match encoder {
-        Ok(enc) => enc,
-        Err(e) => {
-            error!(
-                "Failed to create Opus encoder: sample_rate={sample_rate}, channels={opus_channels:?}, error={e}"
-            );
-            return Err(e.into());
+        if let Err(e) = encoder.set_vbr(opus_cfg.vbr) {
+            error!("Failed to set Opus VBR: {e}");
         }
-    };
-    if let Err(e) = encoder.set_bitrate(opus::Bitrate::Bits(128 * 1000)) {
-        error!("Failed to set Opus bitrate: {e}");
+        if let Err(e) = encoder.set_complexity(opus_cfg.complexity.min(10) as i32) {
+            error!("Failed to set Opus complexity: {e}");
+        }
+
+        // Ogg Opus header (ID + Comment)
+        let mut writer = PacketWriter::new(Cursor::new(Vec::new()));
+        let pre_skip = 312; // Opus spec recommends 312 for 48kHz
+        let id_header = {
+            let mut v = Vec::new();
+            v.extend_from_slice(b"OpusHead"); // Magic signature
+            v.push(1); // Version
+            v.push(channels as u8); // Channel count
+            v.extend_from_slice(&(pre_skip as u16).to_le_bytes()); // Pre-skip
+            v.extend_from_slice(&sample_rate.to_le_bytes()); // Original sample rate
+            v.extend_from_slice(&[0u8; 2]); // Output gain
+            v.push(0); // Channel mapping family
+            v
+        };
+        writer.write_packet(id_header, 1, PacketWriteEndInfo::EndPage, 0)?;
+        let comment_header = {
+            let vendor = AudioMetadata::SOURCE.as_bytes();
+            let comments = [
+                format!("TITLE={}", metadata.title),
+                format!("DATE={}", metadata.date),
+                format!("SOURCE={}", AudioMetadata::SOURCE),
+            ];
+            let mut v = Vec::new();
+            v.extend_from_slice(b"OpusTags");
+            v.extend_from_slice(&(vendor.len() as u32).to_le_bytes()); // Vendor string length
+            v.extend_from_slice(vendor); // Vendor string
+            v.extend_from_slice(&(comments.len() as u32).to_le_bytes()); // User comment list length
+            for comment in &comments {
+                v.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+                v.extend_from_slice(comment.as_bytes());
+            }
+            v
+        };
+        writer.write_packet(comment_header, 1, PacketWriteEndInfo::EndPage, 0)?;
+
+        Ok(Self {
+            encoder,
+            writer,
+            channels: channels as usize,
+            pending: Vec::new(),
+            abs_gp: 0,
+        })
     }
 
-    // Ogg Opus header (ID + Comment)
-    let mut ogg_buf = Vec::new();
-    let mut writer = PacketWriter::new(Cursor::new(&mut ogg_buf));
-    let pre_skip = 312; // Opus spec recommends 312 for 48kHz
-    let id_header = {
-        let mut v = Vec::new();
-        v.extend_from_slice(b"OpusHead"); // Magic signature
-        v.push(1); // Version
-        v.push(channels as u8); // Channel count
-        v.extend_from_slice(&(pre_skip as u16).to_le_bytes()); // Pre-skip
-        v.extend_from_slice(&sample_rate.to_le_bytes()); // Original sample rate
-        v.extend_from_slice(&[0u8; 2]); // Output gain
-        v.push(0); // Channel mapping family
-        v
-    };
-    writer.write_packet(id_header, 1, PacketWriteEndInfo::EndPage, 0)?;
-    let comment_header = {
-        let mut v = Vec::new();
-        v.extend_from_slice(b"OpusTags");
-        v.extend_from_slice(&[6, 0, 0, 0]); // Vendor string length (6)
-        v.extend_from_slice(b"rust"); // Vendor string (short)
-        v.extend_from_slice(&[0, 0, 0, 0]); // User comment list length (0)
-        v
-    };
-    writer.write_packet(comment_header, 1, PacketWriteEndInfo::EndPage, 0)?;
-
-    // PCM to Opus
-    let frame_size = 960; // 20ms at 48kHz
-    let mut abs_gp = 0u64;
-    for chunk in samples.chunks(frame_size * channels as usize) {
+    fn encode_frame(&mut self, frame: &[f32], end_info: PacketWriteEndInfo) -> Res<()> {
         let mut output = vec![0u8; 4000];
-        let mut padded = Vec::from(chunk);
-        if padded.len() < frame_size * channels as usize {
-            padded.resize(frame_size * channels as usize, 0.0);
-        }
-
-        match encoder.encode_float(&padded, &mut output) {
+        match self.encoder.encode_float(frame, &mut output) {
             Ok(encoded_size) => {
-                abs_gp += frame_size as u64;
-                writer.write_packet(
-                    output[..encoded_size].to_vec(),
-                    1,
-                    PacketWriteEndInfo::EndPage,
-                    abs_gp,
-                )?;
+                self.abs_gp += Self::FRAME_SIZE as u64;
+                output.truncate(encoded_size);
+                self.writer.write_packet(output, 1, end_info, self.abs_gp)?;
             }
             Err(e) => {
                 error!("Opus encoding error: {e}");
             }
         }
+        Ok(())
+    }
+}
+
+impl StreamEncoder for OggOpusStream {
+    fn push(&mut self, samples: &[f32]) -> Res<()> {
+        // PCM to Opus，不足一帧的部分留到下次
+        self.pending.extend_from_slice(samples);
+        let frame_len = Self::FRAME_SIZE * self.channels;
+        let full = self.pending.len() / frame_len * frame_len;
+        let pending = std::mem::take(&mut self.pending);
+        for frame in pending[..full].chunks(frame_len) {
+            self.encode_frame(frame, PacketWriteEndInfo::EndPage)?;
+        }
+        self.pending = pending[full..].to_vec();
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Res<Vec<u8>> {
+        let mut last = std::mem::take(&mut self.pending);
+        last.resize(Self::FRAME_SIZE * self.channels, 0.0);
+        self.encode_frame(&last, PacketWriteEndInfo::EndStream)?;
+        Ok(self.writer.into_inner().into_inner())
     }
-    Ok(ogg_buf)
 }
 
-pub fn encode_to_mp3(samples: &[f32], sample_rate: u32, channels: u16) -> Res<Vec<u8>> {
-    info!(
-        "Preparing to encode to MP3: sample_rate={}, channels={}, samples_len={}",
-        sample_rate,
-        channels,
-        samples.len()
-    );
-
-    // Create and configure encoder
-    let mut builder = Builder::new().ok_or_else(|| {
-        let msg = "Failed to create LAME builder";
-        error!("{msg}");
-        msg.to_string()
-    })?;
-    builder.set_num_channels(channels as u8).map_err(|e| {
-        error!("Failed to set channels: {e}");
-        e.to_string()
-    })?;
-    builder.set_sample_rate(sample_rate).map_err(|e| {
-        error!("Failed to set sample rate: {e}");
-        e.to_string()
-    })?;
-    builder
-        .set_brate(mp3lame_encoder::Bitrate::Kbps192)
-        .map_err(|e| {
-            error!("Failed to set bitrate: {e}");
+struct Mp3Stream {
+    encoder: mp3lame_encoder::Encoder,
+    channels: u16,
+    output: Vec<u8>,
+}
+
+impl Mp3Stream {
+    fn new(
+        sample_rate: u32,
+        channels: u16,
+        mp3_cfg: &Mp3EncodeConfig,
+        metadata: &AudioMetadata,
+    ) -> Res<Self> {
+        info!(
+            "Preparing to encode to MP3: sample_rate={sample_rate}, channels={channels}, {mp3_cfg:?}"
+        );
+        if channels != 1 && channels != 2 {
+            error!("Unsupported channel count for MP3: {channels}");
+            return Err("Unsupported channel count".into());
+        }
+
+        // Create and configure encoder
+        let mut builder = Builder::new().ok_or_else(|| {
+            let msg = "Failed to create LAME builder";
+            error!("{msg}");
+            msg.to_string()
+        })?;
+        builder.set_num_channels(channels as u8).map_err(|e| {
+            error!("Failed to set channels: {e}");
+            e.to_string()
+        })?;
+        builder.set_sample_rate(sample_rate).map_err(|e| {
+            error!("Failed to set sample rate: {e}");
             e.to_string()
         })?;
-    builder
-        .set_quality(mp3lame_encoder::Quality::Best)
-        .map_err(|e| {
+        match mp3_cfg.mode {
+            Mp3Mode::Cbr => {
+                builder
+                    .set_brate(Self::bitrate(mp3_cfg.bitrate))
+                    .map_err(|e| {
+                        error!("Failed to set bitrate: {e}");
+                        e.to_string()
+                    })?;
+            }
+            Mp3Mode::Vbr => {
+                builder.set_vbr_mode(VbrMode::Mtrh).map_err(|e| {
+                    error!("Failed to set VBR mode: {e}");
+                    e.to_string()
+                })?;
+                builder
+                    .set_vbr_quality(Self::quality(mp3_cfg.quality))
+                    .map_err(|e| {
+                        error!("Failed to set VBR quality: {e}");
+                        e.to_string()
+                    })?;
+                // 内存编码无法回填 Xing 头，不写入以免产生空帧
+                builder.set_to_write_vbr_tag(false).map_err(|e| {
+                    error!("Failed to disable VBR tag: {e}");
+                    e.to_string()
+                })?;
+            }
+        }
+        builder.set_quality(Quality::Best).map_err(|e| {
             error!("Failed to set quality: {e}");
             e.to_string()
         })?;
+        let comment = format!("Source: {}", AudioMetadata::SOURCE);
+        builder
+            .set_id3_tag(Id3Tag {
+                title: metadata.title.as_bytes(),
+                artist: &[],
+                album: &[],
+                album_art: &[],
+                year: metadata.date.get(..4).unwrap_or_default().as_bytes(),
+                comment: comment.as_bytes(),
+            })
+            .map_err(|e| {
+                error!("Failed to set ID3 tag: {e:?}");
+                format!("{e:?}")
+            })?;
+
+        let encoder = builder.build().map_err(|e| {
+            error!("Failed to build LAME encoder: {e}");
+            e.to_string()
+        })?;
 
-    let mut encoder = builder.build().map_err(|e| {
-        error!("Failed to build LAME encoder: {e}");
-        e.to_string()
-    })?;
+        Ok(Self {
+            encoder,
+            channels,
+            output: Vec::new(),
+        })
+    }
+}
 
-    let frame_size = 1152; // Typical MP3 frame size
+impl Mp3Stream {
+    // 取不超过配置值的最大 LAME 支持码率
+    fn bitrate(kbps: u32) -> Bitrate {
+        match kbps {
+            ..16 => Bitrate::Kbps8,
+            16..24 => Bitrate::Kbps16,
+            24..32 => Bitrate::Kbps24,
+            32..40 => Bitrate::Kbps32,
+            40..48 => Bitrate::Kbps40,
+            48..64 => Bitrate::Kbps48,
+            64..80 => Bitrate::Kbps64,
+            80..96 => Bitrate::Kbps80,
+            96..112 => Bitrate::Kbps96,
+            112..128 => Bitrate::Kbps112,
+            128..160 => Bitrate::Kbps128,
+            160..192 => Bitrate::Kbps160,
+            192..224 => Bitrate::Kbps192,
+            224..256 => Bitrate::Kbps224,
+            256..320 => Bitrate::Kbps256,
+            _ => Bitrate::Kbps320,
+        }
+    }
 
-    let mut mp3_out = Vec::new();
+    fn quality(value: u8) -> Quality {
+        match value {
+            0 => Quality::Best,
+            1 => Quality::SecondBest,
+            2 => Quality::NearBest,
+            3 => Quality::VeryNice,
+            4 => Quality::Nice,
+            5 => Quality::Good,
+            6 => Quality::Decent,
+            7 => Quality::Ok,
+            8 => Quality::SecondWorst,
+            _ => Quality::Worst,
+        }
+    }
+}
 
-    for chunk in samples.chunks(frame_size * channels as usize) {
-        if channels == 1 {
-            let input = MonoPcm(chunk);
-            let required = mp3lame_encoder::max_required_buffer_size(chunk.len());
-            mp3_out.reserve(required);
+impl StreamEncoder for Mp3Stream {
+    fn push(&mut self, samples: &[f32]) -> Res<()> {
+        let frame_size = 1152; // Typical MP3 frame size
 
-            let encoded_size = encoder
-                .encode(input, mp3_out.spare_capacity_mut())
-                .map_err(|e| {
-                    error!("MP3 encoding error: {e}");
-                    e.to_string()
-                })?;
+        for chunk in samples.chunks(frame_size * self.channels as usize) {
+            let frames = chunk.len() / self.channels as usize;
+            let required = mp3lame_encoder::max_required_buffer_size(frames);
+            self.output.reserve(required);
 
-            unsafe {
-                mp3_out.set_len(mp3_out.len() + encoded_size);
-            }
-        } else if channels == 2 {
-            let input = InterleavedPcm(chunk);
-            let required = mp3lame_encoder::max_required_buffer_size(chunk.len() / 2);
-            mp3_out.reserve(required);
-
-            let encoded_size = encoder
-                .encode(input, mp3_out.spare_capacity_mut())
-                .map_err(|e| {
-                    error!("MP3 encoding error: {e}");
-                    e.to_string()
-                })?;
+            let encoded = if self.channels == 1 {
+                self.encoder
+                    .encode(MonoPcm(chunk), self.output.spare_capacity_mut())
+            } else {
+                self.encoder
+                    .encode(InterleavedPcm(chunk), self.output.spare_capacity_mut())
+            };
+            let encoded_size = encoded.map_err(|e| {
+                error!("MP3 encoding error: {e}");
+                e.to_string()
+            })?;
 
             unsafe {
-                mp3_out.set_len(mp3_out.len() + encoded_size);
+                self.output.set_len(self.output.len() + encoded_size);
             }
-        } else {
-            error!("Unsupported channel count for MP3: {channels}");
-            return Err("Unsupported channel count".into());
         }
+        Ok(())
     }
 
-    // Final flush
-    let required = mp3lame_encoder::max_required_buffer_size(0);
-    mp3_out.reserve(required);
-    let flushed_size = encoder
-        .flush::<FlushNoGap>(mp3_out.spare_capacity_mut())
-        .map_err(|e| {
-            error!("MP3 flush error: {e}");
-            e.to_string()
-        })?;
-    unsafe {
-        mp3_out.set_len(mp3_out.len() + flushed_size);
-    }
+    fn finish(mut self: Box<Self>) -> Res<Vec<u8>> {
+        // Final flush
+        let required = mp3lame_encoder::max_required_buffer_size(0);
+        self.output.reserve(required);
+        let flushed_size = self
+            .encoder
+            .flush::<FlushNoGap>(self.output.spare_capacity_mut())
+            .map_err(|e| {
+                error!("MP3 flush error: {e}");
+                e.to_string()
+            })?;
+        unsafe {
+            self.output.set_len(self.output.len() + flushed_size);
+        }
 
-    Ok(mp3_out)
+        Ok(self.output)
+    }
 }
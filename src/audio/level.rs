@@ -0,0 +1,43 @@
+use crate::utils::border::LevelMeter;
+use std::time::{Duration, Instant};
+
+/// 音量条的刷新间隔
+const REPORT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 汇总采集到的采样，按固定间隔把峰值与 RMS 推送给边框上的音量条
+pub struct LevelTracker {
+    meter: LevelMeter,
+    peak: f32,
+    sum_squares: f32,
+    count: usize,
+    last_report: Instant,
+}
+
+impl LevelTracker {
+    pub fn new(meter: LevelMeter) -> Self {
+        Self {
+            meter,
+            peak: 0.0,
+            sum_squares: 0.0,
+            count: 0,
+            last_report: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        for &x in samples {
+            self.peak = self.peak.max(x.abs());
+            self.sum_squares += x * x;
+        }
+        self.count += samples.len();
+        if self.last_report.elapsed() < REPORT_INTERVAL || self.count == 0 {
+            return;
+        }
+        let rms = (self.sum_squares / self.count as f32).sqrt();
+        self.meter.set(self.peak, rms);
+        self.peak = 0.0;
+        self.sum_squares = 0.0;
+        self.count = 0;
+        self.last_report = Instant::now();
+    }
+}
@@ -1,41 +1,131 @@
 use crate::{
-    anki::AnkiClient,
-    config::AudioRecord,
-    utils::{border::BorderOverlay, file::generate_safe_filename},
+    anki::{AnkiClient, Capture, render_media_value},
+    config::{AudioRecord, AudioSource, Overlay, ScreenshotAttach},
+    jobs::JobQueue,
+    screenshot::AnkiScreenshot,
+    utils::{
+        border::{BorderOverlay, OverlayOptions},
+        file::generate_safe_filename,
+        window::get_foreground_window_name,
+    },
 };
-use log::{debug, error, info};
+use image::DynamicImage;
+use log::{debug, error, info, warn};
 use tokio;
+mod buffer;
+mod condensed;
 mod encode;
-use encode::encode;
+mod level;
+mod playback;
+mod resample;
+mod split;
+mod stretch;
+use buffer::SampleBuffer;
+pub use condensed::CondensedRecorder;
+use encode::{AudioMetadata, AudioStream, encode};
+use level::LevelTracker;
+use resample::StreamResampler;
 use std::{
     collections::VecDeque,
-    error, fs,
-    path::PathBuf,
-    sync::{Arc, Mutex},
+    error,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
     thread,
+    time::{Duration, Instant},
 };
+use tokio::sync::oneshot;
 use wasapi::{Direction, SampleType, StreamMode, WaveFormat, get_default_device, initialize_mta};
 type Res<T> = Result<T, Box<dyn error::Error>>;
 
+const SILENCE_THRESHOLD: f32 = 0.01;
+
+/// 最近一次写入 Anki 的录音，供“重录”使用
+struct SavedClip {
+    /// 写入的卡片，目标卡片在最前
+    note_ids: Vec<u64>,
+    /// 存入 Anki 的媒体文件名
+    files: Vec<String>,
+    /// 写入的字段及其内容
+    fields: Vec<(String, String)>,
+    saved_at: Instant,
+}
+
+/// 录音期间的截图，以及按停止录音时渲染的截图的其他字段
+struct Stills {
+    images: Vec<DynamicImage>,
+    capture: Capture,
+}
+
 #[derive(Clone)]
 pub struct AudioRecorder {
     is_recording: Arc<Mutex<bool>>,
-    audio_buffer: Arc<Mutex<Vec<f32>>>,
+    audio_buffer: Arc<Mutex<SampleBuffer>>,
     channels: u16,
     anki: Arc<AnkiClient>,
     cfg: AudioRecord,
     border: Arc<Mutex<Option<BorderOverlay>>>,
+    review: Arc<Mutex<Option<oneshot::Sender<bool>>>>,
+    stream: Arc<Mutex<Option<AudioStream>>>,
+    last_saved: Arc<Mutex<Option<SavedClip>>>,
+    target_note: Arc<Mutex<Option<Vec<u64>>>>,
+    jobs: JobQueue,
+    overlay: Overlay,
+    /// 本次运行已保存的录音文件数
+    saved_count: Arc<AtomicUsize>,
+    /// 录音期间定时截图，未启用时为 None
+    screenshot: Option<AnkiScreenshot>,
+    stills: Arc<Mutex<Vec<DynamicImage>>>,
+    /// 每次开始录音加一，旧的定时截图任务据此退出
+    still_session: Arc<AtomicUsize>,
 }
 
 impl AudioRecorder {
-    pub fn new(cfg: AudioRecord, anki: Arc<AnkiClient>) -> Self {
+    pub fn new(
+        cfg: AudioRecord,
+        overlay: Overlay,
+        anki: Arc<AnkiClient>,
+        jobs: JobQueue,
+        screenshot: Option<AnkiScreenshot>,
+    ) -> Self {
+        let channels = 2;
         Self {
             is_recording: Arc::new(Mutex::new(false)),
-            audio_buffer: Arc::new(Mutex::new(Vec::new())),
-            channels: 2,
+            audio_buffer: Arc::new(Mutex::new(SampleBuffer::with_memory_cap(
+                cfg.max_buffer_mb,
+                channels,
+            ))),
+            channels,
             anki,
             cfg,
             border: Arc::new(Mutex::new(None)),
+            review: Arc::new(Mutex::new(None)),
+            stream: Arc::new(Mutex::new(None)),
+            last_saved: Arc::new(Mutex::new(None)),
+            target_note: Arc::new(Mutex::new(None)),
+            jobs,
+            overlay,
+            saved_count: Arc::new(AtomicUsize::new(0)),
+            screenshot,
+            stills: Arc::new(Mutex::new(Vec::new())),
+            still_session: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    // 手动增益，限制倍数使峰值不超过满幅，避免削波
+    fn apply_gain(samples: &mut [f32], gain_db: f32) {
+        if gain_db == 0.0 {
+            return;
+        }
+        let peak = samples.iter().map(|&x| x.abs()).fold(0.0f32, f32::max);
+        let mut factor = 10f32.powf(gain_db / 20.0);
+        if peak > 0.0 && peak * factor > 1.0 {
+            factor = 1.0 / peak;
+            debug!("Gain of {gain_db}dB would clip, limited to factor {factor}");
+        }
+        for sample in samples.iter_mut() {
+            *sample *= factor;
         }
     }
 
@@ -55,21 +145,47 @@ impl AudioRecorder {
         }
     }
 
-    // 录音循环
-    fn capture_loop(
+    // 流式编码无法预知峰值，逐采样增益后截断到满幅
+    fn stream_gain(samples: &[f32], gain: f32) -> Vec<f32> {
+        samples
+            .iter()
+            .map(|&x| (x * gain).clamp(-1.0, 1.0))
+            .collect()
+    }
+
+    // 录音循环，每收到一批整帧采样就交给 on_samples
+    pub(crate) fn capture_loop(
         is_recording: Arc<Mutex<bool>>,
-        audio_buffer: Arc<Mutex<Vec<f32>>>,
+        source: &AudioSource,
         sample_rate: usize,
         channels: u16,
+        mut on_samples: impl FnMut(&[f32]),
     ) -> Res<()> {
-        let device = get_default_device(&Direction::Render)?;
+        // 在输出设备上以采集方向初始化即为环回录音
+        let device = match source {
+            AudioSource::Loopback => get_default_device(&Direction::Render)?,
+            AudioSource::Microphone => get_default_device(&Direction::Capture)?,
+        };
         let mut audio_client = device.get_iaudioclient()?;
 
+        // 以设备混音格式的采样率采集，再在进程内重采样到目标采样率
+        let device_rate = audio_client.get_mixformat()?.get_samplespersec();
+        let mut resampler = if device_rate as usize != sample_rate {
+            debug!("Resampling capture from {device_rate}Hz to {sample_rate}Hz");
+            Some(StreamResampler::new(
+                device_rate,
+                sample_rate as u32,
+                channels,
+            )?)
+        } else {
+            None
+        };
+
         let desired_format = WaveFormat::new(
             32,
             32,
             &SampleType::Float,
-            sample_rate,
+            device_rate as usize,
             channels.into(),
             None,
         );
@@ -105,17 +221,19 @@ impl AudioRecorder {
 
             render_client.read_from_device_to_deque(&mut sample_queue)?;
 
-            // 转换字节数据为f32样本
-            while sample_queue.len() >= 4 {
-                // 4 bytes per f32 sample
-                let mut bytes = [0u8; 4];
-                for byte in &mut bytes {
-                    *byte = sample_queue.pop_front().unwrap();
+            // 转换整帧字节数据为f32样本
+            let frame_bytes = sample_queue.len() / block_align as usize * block_align as usize;
+            if frame_bytes > 0 {
+                let samples: Vec<f32> = sample_queue
+                    .drain(..frame_bytes)
+                    .collect::<Vec<u8>>()
+                    .chunks_exact(4) // 4 bytes per f32 sample
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                match resampler.as_mut() {
+                    Some(resampler) => on_samples(&resampler.process(&samples)?),
+                    None => on_samples(&samples),
                 }
-                let sample = f32::from_le_bytes(bytes);
-
-                let mut buffer = audio_buffer.lock().unwrap();
-                buffer.push(sample);
             }
 
             if h_event.wait_for_event(100).is_err() {
@@ -125,6 +243,9 @@ impl AudioRecorder {
         }
 
         audio_client.stop_stream()?;
+        if let Some(resampler) = resampler.as_mut() {
+            on_samples(&resampler.flush()?);
+        }
         debug!("Audio capture stopped");
         Ok(())
     }
@@ -141,27 +262,156 @@ impl AudioRecorder {
         }
         // 清空缓冲
         self.audio_buffer.lock().unwrap().clear();
+        if self.cfg.streaming_encode {
+            let metadata = AudioMetadata::new(get_foreground_window_name());
+            let stream =
+                AudioStream::new(&self.cfg, &metadata, self.cfg.sample_rate, self.channels);
+            match stream {
+                Ok(stream) => *self.stream.lock().unwrap() = Some(stream),
+                Err(e) => {
+                    *self.is_recording.lock().unwrap() = false;
+                    return Err(e);
+                }
+            }
+        }
 
-        let new_border = BorderOverlay::new()?;
+        let new_border = BorderOverlay::new(OverlayOptions {
+            show_timer: self.overlay.show_timer,
+            clip_count: self
+                .overlay
+                .show_clip_count
+                .then(|| self.saved_count.load(Ordering::Relaxed)),
+            show_level_meter: self.overlay.show_level_meter,
+            flash_after: (self.overlay.flash_on_soft_limit && self.cfg.soft_limit_secs > 0)
+                .then(|| Duration::from_secs(self.cfg.soft_limit_secs as u64)),
+            countdown: None,
+            paused: false,
+        })?;
+        let mut levels = self
+            .overlay
+            .show_level_meter
+            .then(|| new_border.level_meter())
+            .flatten()
+            .map(LevelTracker::new);
         *self.border.lock().unwrap() = Some(new_border);
 
         // 启动录音线程
         let is_rec = Arc::clone(&self.is_recording);
         let audio_buf = Arc::clone(&self.audio_buffer);
+        let stream = Arc::clone(&self.stream);
+        let streaming = self.cfg.streaming_encode;
+        let gain = 10f32.powf(self.cfg.gain_db / 20.0);
+        let source = self.cfg.source.clone();
         let sr = self.cfg.sample_rate as usize;
         let ch = self.channels;
+        let soft_limit_secs = self.cfg.soft_limit_secs;
+        // 软上限按采样数计，达到后不再累加，每段录音只警告一次
+        let soft_limit = soft_limit_secs as usize * sr * ch as usize;
+        let mut captured = 0;
         thread::Builder::new()
             .name("AudioCapture".into())
             .spawn(move || {
-                if let Err(e) = Self::capture_loop(is_rec, audio_buf, sr, ch) {
+                let on_samples = |samples: &[f32]| {
+                    if soft_limit > 0 && captured < soft_limit {
+                        captured += samples.len();
+                        if captured >= soft_limit {
+                            warn!(
+                                "Recording has been running for over {soft_limit_secs}s, did you forget to stop it?"
+                            );
+                        }
+                    }
+                    if let Some(levels) = levels.as_mut() {
+                        levels.push(samples);
+                    }
+                    if !streaming {
+                        audio_buf.lock().unwrap().extend(samples);
+                    } else if let Some(stream) = stream.lock().unwrap().as_mut()
+                        && let Err(e) = stream.push(&Self::stream_gain(samples, gain))
+                    {
+                        error!("Streaming encode failed: {e}");
+                    }
+                };
+                if let Err(e) = Self::capture_loop(is_rec, &source, sr, ch, on_samples) {
                     error!("Audio capture loop failed: {e}");
                 }
             })?;
+        self.start_interval_screenshots();
         Ok(())
     }
 
+    // 录音期间每隔一段时间截图，按配置只保留需要附加的几张
+    fn start_interval_screenshots(&self) {
+        self.stills.lock().unwrap().clear();
+        let Some(tool) = self.screenshot.clone() else {
+            return;
+        };
+        let session = self.still_session.fetch_add(1, Ordering::Relaxed) + 1;
+        let current = Arc::clone(&self.still_session);
+        let is_rec = Arc::clone(&self.is_recording);
+        let stills = Arc::clone(&self.stills);
+        let attach = self.cfg.interval_screenshots.attach.clone();
+        let interval =
+            Duration::from_secs(self.cfg.interval_screenshots.interval_secs.max(1) as u64);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let active =
+                    || *is_rec.lock().unwrap() && current.load(Ordering::Relaxed) == session;
+                if !active() {
+                    break;
+                }
+                let tool = tool.clone();
+                let image = match tokio::task::spawn_blocking(move || tool.capture_still()).await {
+                    Ok(Ok(image)) => image,
+                    Ok(Err(e)) => {
+                        warn!("Interval screenshot failed: {e}");
+                        continue;
+                    }
+                    Err(e) => {
+                        error!("Interval screenshot task panicked: {e}");
+                        break;
+                    }
+                };
+                // 截图期间录音可能已经结束或重新开始
+                if !active() {
+                    break;
+                }
+                let mut stills = stills.lock().unwrap();
+                match attach {
+                    ScreenshotAttach::First => {
+                        stills.push(image);
+                        break;
+                    }
+                    ScreenshotAttach::Last => *stills = vec![image],
+                    ScreenshotAttach::All => stills.push(image),
+                }
+                debug!("Interval screenshot captured ({} kept)", stills.len());
+            }
+        });
+    }
+
+    // 取走本次录音期间的截图
+    fn take_stills(&self) -> Stills {
+        let images = std::mem::take(&mut *self.stills.lock().unwrap());
+        let capture = match &self.screenshot {
+            Some(tool) if !images.is_empty() => tool.still_capture(),
+            _ => Capture::default(),
+        };
+        Stills { images, capture }
+    }
+
     // 停止录音并保存
     pub async fn stop_recording_and_save(&self) -> Res<()> {
+        self.stop_and_save(self.cfg.preview).await
+    }
+
+    /// 退出时停止录音并直接保存，不等待试听
+    pub async fn save_on_exit(&self) -> Res<()> {
+        self.stop_and_save(false).await
+    }
+
+    async fn stop_and_save(&self, preview: bool) -> Res<()> {
         *self.is_recording.lock().unwrap() = false;
 
         if let Some(border_to_stop) = self.border.lock().unwrap().take() {
@@ -171,55 +421,411 @@ impl AudioRecorder {
         // 等待录音线程真正退出
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
+        // 流式编码时只需收尾，跳过归一化、裁剪、预览、切分与慢速副本
+        let stream = self.stream.lock().unwrap().take();
+        if let Some(stream) = stream {
+            let raw = stream.finish()?;
+            let file_name =
+                generate_safe_filename(&self.cfg.field_name, &self.cfg.format.to_string());
+            let target = self.target_note.lock().unwrap().take();
+            let stills = self.take_stills();
+            let capture = self.anki.capture(&self.cfg.extra_fields);
+            let recorder = self.clone();
+            self.jobs.submit("save audio", async move {
+                match recorder
+                    .save_to_anki(&[(file_name.clone(), raw)], &[], stills, target, &capture)
+                    .await
+                {
+                    Ok(()) => info!("Recording saved as: {file_name}"),
+                    Err(e) => error!("Failed to save recording: {e}"),
+                }
+            });
+            return Ok(());
+        }
+
         // 获取并处理音频数据
-        let mut data = {
-            let buf = self.audio_buffer.lock().unwrap();
-            buf.clone()
-        };
+        let stills = self.take_stills();
+        let capture = self.anki.capture(&self.cfg.extra_fields);
+        let mut data = self.audio_buffer.lock().unwrap().take();
         if data.is_empty() {
             return Err("No audio data recorded".into());
         }
+        Self::apply_gain(&mut data, self.cfg.gain_db);
         Self::normalize_audio(&mut data);
-        let trimmed = Self::trim_silence(&data, 0.01);
+        let trimmed = Self::trim_silence(&data, SILENCE_THRESHOLD);
         if trimmed.is_empty() {
             return Err("Audio is silent after trimming".into());
         }
+        if preview && !self.review(trimmed).await? {
+            info!("Recording discarded after preview");
+            return Ok(());
+        }
 
-        // 编码、保存并更新 Anki
-        let raw = encode(
-            self.cfg.format.clone(),
-            trimmed,
-            self.cfg.sample_rate,
-            self.channels,
-        )?;
-        let file_name = generate_safe_filename(&self.cfg.field_name, &self.cfg.format.to_string());
-        self.save_to_anki(raw, &file_name).await?;
-        info!("Recording saved as: {file_name}");
+        // 编码与上传交给后台队列，期间可以开始下一段录音
+        let samples = trimmed.to_vec();
+        let metadata = AudioMetadata::new(capture.game.clone());
+        let target = self.target_note.lock().unwrap().take();
+        let recorder = self.clone();
+        self.jobs.submit("encode audio", async move {
+            if let Err(e) = recorder
+                .encode_and_save(&samples, &metadata, stills, target, &capture)
+                .await
+            {
+                error!("Failed to save recording: {e}");
+            }
+        });
         Ok(())
     }
 
-    // 保存到Anki
-    async fn save_to_anki(&self, _data: Vec<u8>, filename: &str) -> Res<()> {
-        // 获取媒体目录并保存文件
-        let media_dir = self.anki.get_media_dir().await?;
-        let file_path = PathBuf::from(&media_dir).join(filename);
-        fs::write(&file_path, _data)?;
-        info!("Audio file saved to: {}", file_path.display());
-
-        // 更新最新的卡片
-        let note_id = self.anki.get_latest_note_id().await?;
-        self.anki
-            .update_note_field(
-                note_id,
-                &self.cfg.field_name,
-                &format!("[sound:{filename}]"),
-            )
+    // 切分、编码并写入 Anki
+    async fn encode_and_save(
+        &self,
+        trimmed: &[f32],
+        metadata: &AudioMetadata,
+        stills: Stills,
+        target: Option<Vec<u64>>,
+        capture: &Capture,
+    ) -> Res<()> {
+        // 按静音切分为多句
+        let mut segments = Vec::new();
+        if self.cfg.split_on_silence {
+            segments = split::split_on_silence(
+                trimmed,
+                self.cfg.sample_rate,
+                self.channels,
+                SILENCE_THRESHOLD,
+                self.cfg.min_silence_ms,
+            );
+            debug!("Recording split into {} clip(s)", segments.len());
+        }
+        if segments.is_empty() {
+            segments.push(trimmed);
+        }
+
+        // 编码、保存并更新 Anki
+        let clips = self.encode_segments(&segments, &self.cfg.field_name, metadata)?;
+        let slowed = if self.cfg.slowed.enabled {
+            let stretched: Vec<Vec<f32>> = segments
+                .iter()
+                .map(|segment| {
+                    stretch::time_stretch(
+                        segment,
+                        self.cfg.sample_rate,
+                        self.channels,
+                        self.cfg.slowed.speed,
+                    )
+                })
+                .collect();
+            let stretched: Vec<&[f32]> = stretched.iter().map(Vec::as_slice).collect();
+            self.encode_segments(&stretched, &self.cfg.slowed.field_name, metadata)?
+        } else {
+            Vec::new()
+        };
+        self.save_to_anki(&clips, &slowed, stills, target, capture)
             .await?;
+        for (file_name, _) in clips.iter().chain(&slowed) {
+            info!("Recording saved as: {file_name}");
+        }
+        Ok(())
+    }
+
+    // 编码各片段，多段时文件名带序号
+    fn encode_segments(
+        &self,
+        segments: &[&[f32]],
+        field_name: &str,
+        metadata: &AudioMetadata,
+    ) -> Res<Vec<(String, Vec<u8>)>> {
+        let ext = self.cfg.format.to_string();
+        let mut clips = Vec::with_capacity(segments.len());
+        for (i, segment) in segments.iter().enumerate() {
+            let raw = encode(
+                &self.cfg,
+                metadata,
+                segment,
+                self.cfg.sample_rate,
+                self.channels,
+            )?;
+            let file_name = if segments.len() > 1 {
+                generate_safe_filename(&format!("{field_name}_{}", i + 1), &ext)
+            } else {
+                generate_safe_filename(field_name, &ext)
+            };
+            clips.push((file_name, raw));
+        }
+        Ok(clips)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        *self.is_recording.lock().unwrap()
+    }
+
+    /// 停止录音并写入指定的卡片
+    pub async fn stop_and_save_to(&self, note_ids: Vec<u64>) -> Res<()> {
+        *self.target_note.lock().unwrap() = Some(note_ids);
+        let result = self.stop_recording_and_save().await;
+        // 没有保存时清掉目标，以免下一段录音写到这张卡片
+        self.target_note.lock().unwrap().take();
+        result
+    }
+
+    /// 中止正在进行的录音，丢弃数据且不写入 Anki；未在录音时返回 false
+    pub async fn cancel_recording(&self) -> bool {
+        {
+            let mut rec = self.is_recording.lock().unwrap();
+            if !*rec {
+                return false;
+            }
+            *rec = false;
+        }
+        if let Some(border_to_stop) = self.border.lock().unwrap().take() {
+            border_to_stop.stop();
+        }
+
+        // 等待录音线程真正退出后再清空，避免残留数据
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        self.stream.lock().unwrap().take();
+        self.audio_buffer.lock().unwrap().clear();
+        self.target_note.lock().unwrap().take();
+        self.take_stills();
+        info!("Recording cancelled");
+        true
+    }
+
+    // 回放录音并等待确认/丢弃热键，返回是否保留；超时没有操作时保留
+    async fn review(&self, samples: &[f32]) -> Res<bool> {
+        let (tx, rx) = oneshot::channel();
+        *self.review.lock().unwrap() = Some(tx);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let playback = {
+            let samples = samples.to_vec();
+            let sample_rate = self.cfg.sample_rate;
+            let channels = self.channels;
+            let stop = Arc::clone(&stop);
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = playback::play(&samples, sample_rate, channels, stop) {
+                    error!("Preview playback failed: {e}");
+                }
+            })
+        };
+        info!("Previewing recording, press confirm to save or discard to drop it");
+
+        let keep = if self.cfg.preview_timeout_ms == 0 {
+            rx.await.unwrap_or(false)
+        } else {
+            let timeout = Duration::from_millis(self.cfg.preview_timeout_ms);
+            match tokio::time::timeout(timeout, rx).await {
+                Ok(keep) => keep.unwrap_or(false),
+                Err(_) => {
+                    self.review.lock().unwrap().take();
+                    info!("Preview timed out, saving recording");
+                    true
+                }
+            }
+        };
+        // 在开始下一段录音前停止回放，避免被环回录进去
+        stop.store(true, Ordering::Relaxed);
+        let _ = playback.await;
+        Ok(keep)
+    }
+
+    /// 响应确认/丢弃热键，没有等待中的预览时返回 false
+    pub fn resolve_review(&self, keep: bool) -> bool {
+        match self.review.lock().unwrap().take() {
+            Some(tx) => tx.send(keep).is_ok(),
+            None => false,
+        }
+    }
+
+    // 保存到Anki，暂存模式下放入暂存区；Anki 没有运行时放入离线队列，定时截图不会入队
+    async fn save_to_anki(
+        &self,
+        clips: &[(String, Vec<u8>)],
+        slowed: &[(String, Vec<u8>)],
+        stills: Stills,
+        target: Option<Vec<u64>>,
+        capture: &Capture,
+    ) -> Res<()> {
+        let target = target.as_deref();
+        let media: Vec<_> = clips
+            .iter()
+            .chain(slowed)
+            .map(|(filename, data)| (filename.as_str(), data.as_slice()))
+            .collect();
+        let sound_tags = |clips: &[(String, Vec<u8>)]| -> String {
+            clips
+                .iter()
+                .map(|(filename, _)| {
+                    render_media_value(&self.cfg.value_template, filename, &capture.game)
+                })
+                .collect()
+        };
+        let mut fields = vec![(self.cfg.field_name.clone(), sound_tags(clips))];
+        if !slowed.is_empty() {
+            fields.push((self.cfg.slowed.field_name.clone(), sound_tags(slowed)));
+        }
+        if self.anki.is_staging() {
+            self.anki
+                .stage(target, &media, &fields, &self.cfg.field_mode, capture)?;
+            if let Some(tool) = &self.screenshot
+                && !stills.images.is_empty()
+                && let Err(e) = tool.stage_stills(target, stills.images, &stills.capture)
+            {
+                error!("Failed to stage interval screenshots: {e}");
+            }
+            return Ok(());
+        }
+        let Err(e) = self
+            .write_to_anki(clips, slowed, stills, target, capture)
+            .await
+        else {
+            return Ok(());
+        };
+        if self.anki.queue_if_unreachable(
+            &*e,
+            target,
+            &media,
+            &fields,
+            &self.cfg.field_mode,
+            capture,
+        ) {
+            return Err("AnkiConnect is unreachable, recording queued for retry".into());
+        }
+        if self
+            .anki
+            .save_fallback(&*e, target, &media, &fields, capture)
+        {
+            return Err(format!("{e}, recording saved to fallback directory").into());
+        }
+        Err(e)
+    }
+
+    async fn write_to_anki(
+        &self,
+        clips: &[(String, Vec<u8>)],
+        slowed: &[(String, Vec<u8>)],
+        stills: Stills,
+        target: Option<&[u64]>,
+        capture: &Capture,
+    ) -> Res<()> {
+        // 重录时写回原来的卡片
+        let note_ids = match target {
+            Some(ids) => ids.to_vec(),
+            None => self.anki.resolve_note_ids().await?,
+        };
+        let note_id = note_ids[0];
+
+        // 保存文件到 Anki 的媒体库，多段录音写入多个 [sound:] 标签
+        let mut groups = vec![(&self.cfg.field_name, clips)];
+        if !slowed.is_empty() {
+            groups.push((&self.cfg.slowed.field_name, slowed));
+        }
+        let mut files = Vec::with_capacity(clips.len() + slowed.len());
+        let mut fields = Vec::with_capacity(groups.len());
+        for (field, group) in groups {
+            let mut sound_tags = String::new();
+            for (filename, data) in group {
+                if self.anki.is_duplicate_media(note_id, field, data).await {
+                    continue;
+                }
+                let filename = self.anki.store_media(filename, data).await?;
+                info!("Audio file saved: {filename}");
+                sound_tags.push_str(&render_media_value(
+                    &self.cfg.value_template,
+                    &filename,
+                    &capture.game,
+                ));
+                files.push(filename);
+            }
+            if !sound_tags.is_empty() {
+                fields.push((field.clone(), sound_tags));
+            }
+        }
+        // 录音字段与其他字段一次写入
+        if !fields.is_empty() {
+            self.anki
+                .write_note_fields(&note_ids, &fields, &self.cfg.field_mode, capture)
+                .await?;
+        }
+        // 截图失败不影响录音，重录时一并移除
+        if let Some(tool) = &self.screenshot
+            && !stills.images.is_empty()
+        {
+            match tool
+                .save_stills(&note_ids, stills.images, &stills.capture)
+                .await
+            {
+                Ok((still_files, field)) => {
+                    files.extend(still_files);
+                    fields.push(field);
+                }
+                Err(e) => error!("Failed to save interval screenshots: {e}"),
+            }
+        }
+        self.saved_count.fetch_add(clips.len(), Ordering::Relaxed);
+        *self.last_saved.lock().unwrap() = Some(SavedClip {
+            note_ids,
+            files,
+            fields,
+            saved_at: Instant::now(),
+        });
 
         info!("Audio saved to Anki note: {note_id}");
         Ok(())
     }
 
+    /// 上一段可重录的录音的保存时间
+    pub fn last_saved_at(&self) -> Option<Instant> {
+        self.last_saved.lock().unwrap().as_ref().map(|c| c.saved_at)
+    }
+
+    /// 丢弃上一段已保存的录音（删除文件并清空字段），并立即开始针对同一卡片的新录音
+    pub async fn redo_last_recording(&self) -> Res<()> {
+        if *self.is_recording.lock().unwrap() {
+            return Err("Recording in progress, stop it before redoing".into());
+        }
+        let last = self.last_saved.lock().unwrap().take();
+        let Some(last) = last else {
+            return Err("No saved recording to redo".into());
+        };
+
+        for file in &last.files {
+            if let Err(e) = self.anki.delete_media(file).await {
+                error!("Failed to delete {file}: {e}");
+            }
+        }
+        // 只移除上次写入的内容，追加模式下保留字段原有部分；一起写入的其他卡片同样移除
+        let note_id = last.note_ids[0];
+        for &target in &last.note_ids {
+            if let Err(e) = self.strip_fields(target, &last.fields).await {
+                if target == note_id {
+                    return Err(e);
+                }
+                warn!("Failed to remove last recording from note {target}: {e}");
+            }
+        }
+        info!("Discarded last recording on note {note_id}, recording again");
+
+        *self.target_note.lock().unwrap() = Some(last.note_ids);
+        if let Err(e) = self.start_recording() {
+            *self.target_note.lock().unwrap() = None;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // 从卡片的字段中移除写入过的内容
+    async fn strip_fields(&self, note_id: u64, fields: &[(String, String)]) -> Res<()> {
+        for (field, value) in fields {
+            let current = self.anki.get_note_field(note_id, field).await?;
+            self.anki
+                .update_note_field(note_id, field, &current.replace(value, ""))
+                .await?;
+        }
+        Ok(())
+    }
+
     fn trim_silence(samples: &[f32], threshold: f32) -> &[f32] {
         let start = samples
             .iter()
@@ -237,6 +843,16 @@ impl AudioRecorder {
 }
 
 // 热键处理函数 - 将被外部调用
+/// 按住录音模式：按下时开始录音，松开时停止并保存
+pub fn on_hotkey_held(recorder: &AudioRecorder, pressed: bool) -> Res<()> {
+    let is_currently_recording = *recorder.is_recording.lock().unwrap();
+    // 按下时已在录音（例如上次的松开事件丢失）或松开时未在录音，都不切换状态
+    if pressed == is_currently_recording {
+        return Ok(());
+    }
+    on_hotkey_clicked(recorder)
+}
+
 pub fn on_hotkey_clicked(recorder: &AudioRecorder) -> Res<()> {
     let is_currently_recording = {
         let recording = recorder.is_recording.lock().unwrap();
@@ -0,0 +1,61 @@
+use super::Res;
+use log::debug;
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use wasapi::{Direction, SampleType, StreamMode, WaveFormat, get_default_device, initialize_mta};
+
+/// 通过默认输出设备回放录音，阻塞直到播放完毕或 `stop` 被置位
+pub fn play(samples: &[f32], sample_rate: u32, channels: u16, stop: Arc<AtomicBool>) -> Res<()> {
+    let _ = initialize_mta();
+    let device = get_default_device(&Direction::Render)?;
+    let mut audio_client = device.get_iaudioclient()?;
+
+    let format = WaveFormat::new(
+        32,
+        32,
+        &SampleType::Float,
+        sample_rate as usize,
+        channels.into(),
+        None,
+    );
+    let block_align = format.get_blockalign() as usize;
+
+    let (def_time, _min_time) = audio_client.get_device_period()?;
+    let mode = StreamMode::EventsShared {
+        autoconvert: true,
+        buffer_duration_hns: def_time,
+    };
+    audio_client.initialize_client(&format, &Direction::Render, &mode)?;
+    let h_event = audio_client.set_get_eventhandle()?;
+    let render_client = audio_client.get_audiorenderclient()?;
+
+    let mut queue: VecDeque<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+
+    audio_client.start_stream()?;
+    debug!("Preview playback started");
+
+    while queue.len() >= block_align && !stop.load(Ordering::Relaxed) {
+        let available = audio_client.get_available_space_in_frames()? as usize;
+        let frames = available.min(queue.len() / block_align);
+        render_client.write_to_device_from_deque(frames, &mut queue, None)?;
+        if h_event.wait_for_event(1000).is_err() {
+            break;
+        }
+    }
+
+    // 等待设备缓冲中剩余的数据播完
+    while !stop.load(Ordering::Relaxed) && audio_client.get_current_padding()? > 0 {
+        if h_event.wait_for_event(1000).is_err() {
+            break;
+        }
+    }
+
+    audio_client.stop_stream()?;
+    debug!("Preview playback stopped");
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use super::Res;
+use rubato::{FftFixedInOut, Resampler};
+
+/// 交错采样的流式重采样，按块送入 rubato 并去掉其固有延迟
+pub struct StreamResampler {
+    inner: FftFixedInOut<f32>,
+    channels: usize,
+    pending: Vec<f32>,
+    delay_frames: usize,
+}
+
+impl StreamResampler {
+    const CHUNK_FRAMES: usize = 1024;
+
+    pub fn new(from_rate: u32, to_rate: u32, channels: u16) -> Res<Self> {
+        let channels = channels.max(1) as usize;
+        let inner = FftFixedInOut::new(
+            from_rate as usize,
+            to_rate as usize,
+            Self::CHUNK_FRAMES,
+            channels,
+        )?;
+        let delay_frames = inner.output_delay();
+        Ok(Self {
+            inner,
+            channels,
+            pending: Vec::new(),
+            delay_frames,
+        })
+    }
+
+    /// 送入整帧交错采样，返回目前可输出的重采样结果
+    pub fn process(&mut self, samples: &[f32]) -> Res<Vec<f32>> {
+        self.pending.extend_from_slice(samples);
+        let mut output = Vec::new();
+        loop {
+            let needed = self.inner.input_frames_next() * self.channels;
+            if self.pending.len() < needed {
+                break;
+            }
+            let chunk: Vec<f32> = self.pending.drain(..needed).collect();
+            let out = self.inner.process(&self.deinterleave(&chunk), None)?;
+            self.interleave_into(&out, &mut output);
+        }
+        Ok(output)
+    }
+
+    /// 处理剩余的不足一块的采样
+    pub fn flush(&mut self) -> Res<Vec<f32>> {
+        let mut output = Vec::new();
+        let chunk = std::mem::take(&mut self.pending);
+        let out = self
+            .inner
+            .process_partial(Some(&self.deinterleave(&chunk)), None)?;
+        self.interleave_into(&out, &mut output);
+        Ok(output)
+    }
+
+    fn deinterleave(&self, samples: &[f32]) -> Vec<Vec<f32>> {
+        (0..self.channels)
+            .map(|ch| {
+                samples
+                    .iter()
+                    .skip(ch)
+                    .step_by(self.channels)
+                    .copied()
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn interleave_into(&mut self, channels: &[Vec<f32>], output: &mut Vec<f32>) {
+        let frames = channels.first().map_or(0, Vec::len);
+        let skip = self.delay_frames.min(frames);
+        self.delay_frames -= skip;
+        for i in skip..frames {
+            output.extend(channels.iter().map(|ch| ch[i]));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+/// 分析窗口长度（毫秒）
+const WINDOW_MS: u32 = 10;
+/// 短于该长度的片段视为噪声丢弃（毫秒）
+const MIN_CLIP_MS: u32 = 200;
+
+/// 按静音间隔将交错采样切分为多个片段
+///
+/// # 参数
+/// - samples: 交错排列的采样
+/// - sample_rate / channels: 采样格式
+/// - threshold: 静音判定阈值（绝对幅值）
+/// - min_gap_ms: 被视为分句的最短静音时长
+///
+/// 返回的片段均以帧为边界，且首尾不含静音窗口。
+pub fn split_on_silence(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    threshold: f32,
+    min_gap_ms: u32,
+) -> Vec<&[f32]> {
+    let channels = channels.max(1) as usize;
+    let window = (sample_rate * WINDOW_MS / 1000) as usize * channels;
+    if window == 0 {
+        return vec![samples];
+    }
+    let min_gap_windows = (min_gap_ms / WINDOW_MS).max(1) as usize;
+    let min_clip = (sample_rate * MIN_CLIP_MS / 1000) as usize * channels;
+
+    let mut clips = Vec::new();
+    let mut clip_start: Option<usize> = None;
+    let mut last_loud_end = 0;
+    let mut silent_windows = 0;
+
+    for (i, chunk) in samples.chunks(window).enumerate() {
+        let start = i * window;
+        let loud = chunk.iter().any(|x| x.abs() > threshold);
+        if loud {
+            if clip_start.is_none() {
+                clip_start = Some(start);
+            }
+            last_loud_end = start + chunk.len();
+            silent_windows = 0;
+        } else if let Some(begin) = clip_start {
+            silent_windows += 1;
+            if silent_windows >= min_gap_windows {
+                if last_loud_end - begin >= min_clip {
+                    clips.push(&samples[begin..last_loud_end]);
+                }
+                clip_start = None;
+            }
+        }
+    }
+    if let Some(begin) = clip_start
+        && last_loud_end - begin >= min_clip
+    {
+        clips.push(&samples[begin..last_loud_end]);
+    }
+    clips
+}
+
+/// 流式去除长静音：短于 `max_gap_ms` 的停顿保留，更长的静音整段丢弃
+pub struct SilenceStripper {
+    window: usize,
+    max_gap: usize,
+    threshold: f32,
+    carry: Vec<f32>,
+    pending_silence: Vec<f32>,
+    output: Vec<f32>,
+}
+
+impl SilenceStripper {
+    pub fn new(sample_rate: u32, channels: u16, threshold: f32, max_gap_ms: u32) -> Self {
+        let channels = channels.max(1) as usize;
+        let frames_per_ms = (sample_rate / 1000).max(1) as usize;
+        Self {
+            window: (WINDOW_MS as usize * frames_per_ms * channels).max(channels),
+            max_gap: max_gap_ms as usize * frames_per_ms * channels,
+            threshold,
+            carry: Vec::new(),
+            pending_silence: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// 追加一段交错采样
+    pub fn push(&mut self, samples: &[f32]) {
+        self.carry.extend_from_slice(samples);
+        let full = self.carry.len() / self.window * self.window;
+        let carry = std::mem::take(&mut self.carry);
+        for chunk in carry[..full].chunks(self.window) {
+            self.push_window(chunk);
+        }
+        self.carry = carry[full..].to_vec();
+    }
+
+    fn push_window(&mut self, chunk: &[f32]) {
+        if chunk.iter().any(|x| x.abs() > self.threshold) {
+            // 停顿足够短才保留，否则视为分隔并丢弃
+            if self.pending_silence.len() <= self.max_gap {
+                self.output.append(&mut self.pending_silence);
+            }
+            self.pending_silence.clear();
+            self.output.extend_from_slice(chunk);
+        } else if self.pending_silence.len() <= self.max_gap {
+            self.pending_silence.extend_from_slice(chunk);
+        }
+    }
+
+    /// 取出目前已确定保留的采样，调用方应及时交给编码器
+    pub fn take_output(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// 取出剩余保留的采样，末尾的静音不计入
+    pub fn finish(mut self) -> Vec<f32> {
+        let carry = std::mem::take(&mut self.carry);
+        self.push_window(&carry);
+        self.output
+    }
+}
@@ -0,0 +1,99 @@
+/// 帧长（毫秒）
+const FRAME_MS: u32 = 40;
+/// 帧位置的搜索范围（毫秒）
+const TOLERANCE_MS: u32 = 10;
+/// 计算相似度时的采样间隔，牺牲少量精度换取速度
+const CORRELATION_STRIDE: usize = 4;
+
+/// WSOLA 变速不变调
+///
+/// # 参数
+/// - samples: 交错排列的采样
+/// - sample_rate / channels: 采样格式
+/// - speed: 播放速度倍率，小于 1 为放慢
+///
+/// 每一帧在标称位置附近寻找与上一帧自然延续最相似的位置再叠加，
+/// 以避免相位错乱带来的“回声”感。
+pub fn time_stretch(samples: &[f32], sample_rate: u32, channels: u16, speed: f32) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let frame_len = (sample_rate * FRAME_MS / 1000) as usize;
+    let hop_out = frame_len / 2;
+    if speed <= 0.0 || (speed - 1.0).abs() < f32::EPSILON || hop_out == 0 || frames < frame_len {
+        return samples.to_vec();
+    }
+    let hop_in = hop_out as f32 * speed;
+    let tolerance = (sample_rate * TOLERANCE_MS / 1000) as usize;
+    let window: Vec<f32> = (0..frame_len)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / frame_len as f32).cos())
+        .collect();
+    let mono: Vec<f32> = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    let out_frames = (frames as f32 / speed) as usize + frame_len;
+    let mut output = vec![0.0f32; out_frames * channels];
+    let mut weight = vec![0.0f32; out_frames];
+    let mut written = 0;
+    let mut prev_pos = 0;
+
+    for k in 0.. {
+        let out_pos = k * hop_out;
+        if out_pos + frame_len > out_frames {
+            break;
+        }
+        let pos = if k == 0 {
+            0
+        } else {
+            let natural = prev_pos + hop_out;
+            let nominal = (k as f32 * hop_in) as usize;
+            if natural + frame_len > frames || nominal + frame_len > frames {
+                break;
+            }
+            let lo = nominal.saturating_sub(tolerance);
+            let hi = (nominal + tolerance).min(frames - frame_len);
+            best_match(&mono, natural, lo, hi, frame_len)
+        };
+
+        for (i, &w) in window.iter().enumerate() {
+            let src = (pos + i) * channels;
+            let dst = (out_pos + i) * channels;
+            for ch in 0..channels {
+                output[dst + ch] += samples[src + ch] * w;
+            }
+            weight[out_pos + i] += w;
+        }
+        written = out_pos + frame_len;
+        prev_pos = pos;
+    }
+
+    output.truncate(written * channels);
+    for (frame, &w) in output.chunks_exact_mut(channels).zip(&weight) {
+        if w > 1e-3 {
+            frame.iter_mut().for_each(|x| *x /= w);
+        }
+    }
+    output
+}
+
+/// 在 [lo, hi] 内寻找与 `reference` 起始帧互相关最大的位置
+fn best_match(mono: &[f32], reference: usize, lo: usize, hi: usize, len: usize) -> usize {
+    let target = &mono[reference..reference + len];
+    let mut best = lo;
+    let mut best_score = f32::MIN;
+    for pos in (lo..=hi).step_by(2) {
+        let candidate = &mono[pos..pos + len];
+        let score: f32 = target
+            .iter()
+            .zip(candidate)
+            .step_by(CORRELATION_STRIDE)
+            .map(|(a, b)| a * b)
+            .sum();
+        if score > best_score {
+            best_score = score;
+            best = pos;
+        }
+    }
+    best
+}
@@ -0,0 +1,280 @@
+use crate::anki::AnkiClient;
+use crate::config::{self, AudioFormat, AvifEncoder, Config, ScreenshotFormat};
+use crate::hotkey_manager::KeyCombo;
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::process::Command;
+
+/// Opus 编码器支持的采样率
+const OPUS_SAMPLE_RATES: &[u32] = &[8000, 12000, 16000, 24000, 48000];
+/// LAME 支持的采样率
+const MP3_SAMPLE_RATES: &[u32] = &[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000];
+
+/// 检查结果，逐条打印，最后有问题时返回错误
+#[derive(Default)]
+struct Report {
+    warnings: usize,
+    problems: usize,
+}
+
+impl Report {
+    fn ok(&mut self, msg: impl AsRef<str>) {
+        println!("[ OK ] {}", msg.as_ref());
+    }
+
+    /// 能运行但可能不符合预期
+    fn warn(&mut self, msg: impl AsRef<str>) {
+        self.warnings += 1;
+        println!("[WARN] {}", msg.as_ref());
+    }
+
+    fn problem(&mut self, msg: impl AsRef<str>) {
+        self.problems += 1;
+        println!("[FAIL] {}", msg.as_ref());
+    }
+
+    fn finish(self) -> Result<()> {
+        println!();
+        println!("{} 个问题，{} 个警告", self.problems, self.warnings);
+        if self.problems > 0 {
+            return Err(anyhow!("配置检查未通过"));
+        }
+        Ok(())
+    }
+}
+
+/// 检查配置文件：能否解析、热键、编码参数、外部编码器，以及字段是否存在于 Anki 的笔记类型中
+///
+/// 不会改动配置文件，旧版本的配置也不会像启动时那样升级后写回。
+pub async fn check_config() -> Result<()> {
+    let path = config::config_file_path()?;
+    println!("配置文件: {}", path.display());
+    let mut report = Report::default();
+    let cfg = match config::reload_user_config() {
+        Ok(cfg) => {
+            report.ok("配置文件解析成功，所有热键的按键名称有效");
+            cfg
+        }
+        Err(e) => {
+            report.problem(format!("{e:#}"));
+            return report.finish();
+        }
+    };
+    check_hotkeys(&cfg, &mut report);
+    check_screenshot(&cfg, &mut report);
+    check_audio(&cfg, &mut report);
+    check_video(&cfg, &mut report);
+    check_encoders(&cfg, &mut report);
+    check_anki(&cfg, &mut report).await;
+    report.finish()
+}
+
+fn check_hotkeys(cfg: &Config, report: &mut Report) {
+    let hot_key = &cfg.hot_key;
+    for (option, names) in [
+        ("swallow", &hot_key.swallow),
+        ("exact", &hot_key.exact),
+        ("leaderHotkeys", &hot_key.leader_hotkeys),
+    ] {
+        for name in names {
+            if hot_key.keys_by_name(name).is_none() {
+                report.problem(format!(
+                    "hotKey.{option} 中的 {name} 不是热键名称，可用: {}",
+                    config::HotKey::NAMES.join(", ")
+                ));
+            }
+        }
+    }
+
+    // 双击与单击共用同一组按键是正常用法，不参与比较
+    let mut bound: HashMap<KeyCombo, Vec<String>> = HashMap::new();
+    for name in config::HotKey::NAMES
+        .iter()
+        .filter(|&&name| name != "comboDoubleTap")
+    {
+        match hot_key.keys_by_name(name) {
+            Some(keys) if !keys.is_empty() => bound
+                .entry(KeyCombo::new(keys))
+                .or_default()
+                .push(format!("hotKey.{name}")),
+            _ => {}
+        }
+    }
+    for (i, record) in cfg.audio_record.iter().enumerate() {
+        bound
+            .entry(KeyCombo::new(&record.hot_key))
+            .or_default()
+            .push(format!("audioRecord[{i}].hotKey"));
+    }
+    let mut conflicts = 0;
+    for names in bound.values().filter(|names| names.len() > 1) {
+        conflicts += 1;
+        report.warn(format!("{} 使用了相同的按键", names.join(", ")));
+    }
+    if conflicts == 0 {
+        report.ok(format!("{} 组热键互不冲突", bound.len()));
+    }
+}
+
+fn check_screenshot(cfg: &Config, report: &mut Report) {
+    let shot = &cfg.screen_shot;
+    if shot.field_name.is_empty() {
+        report.problem("screenShot.fieldName 为空");
+    }
+    if shot.quality > 100 {
+        report.problem(format!(
+            "screenShot.quality 应为 0-100，当前为 {}",
+            shot.quality
+        ));
+    }
+    if matches!(shot.format, ScreenshotFormat::Avif) && !(1..=10).contains(&shot.speed) {
+        report.problem(format!("screenShot.speed 应为 1-10，当前为 {}", shot.speed));
+    }
+    if matches!(shot.format, ScreenshotFormat::Jxl) {
+        if !(0.0..=25.0).contains(&shot.jxl.distance) {
+            report.problem(format!(
+                "screenShot.jxl.distance 应为 0-25，当前为 {}",
+                shot.jxl.distance
+            ));
+        }
+        if !(1..=10).contains(&shot.jxl.effort) {
+            report.problem(format!(
+                "screenShot.jxl.effort 应为 1-10，当前为 {}",
+                shot.jxl.effort
+            ));
+        }
+    }
+    report.ok(format!("截图: {:?}，质量 {}", shot.format, shot.quality));
+}
+
+fn check_audio(cfg: &Config, report: &mut Report) {
+    for (i, record) in cfg.audio_record.iter().enumerate() {
+        let prefix = format!("audioRecord[{i}]");
+        let mut valid = true;
+        if record.field_name.is_empty() {
+            report.problem(format!("{prefix}.fieldName 为空"));
+            valid = false;
+        }
+        let rates = match record.format {
+            AudioFormat::Opus => OPUS_SAMPLE_RATES,
+            AudioFormat::Mp3 => MP3_SAMPLE_RATES,
+        };
+        if !rates.contains(&record.sample_rate) {
+            report.problem(format!(
+                "{prefix}.sampleRate 为 {}，{:?} 支持: {}",
+                record.sample_rate,
+                record.format,
+                rates
+                    .iter()
+                    .map(|rate| rate.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            valid = false;
+        }
+        match record.format {
+            AudioFormat::Opus => {
+                if !(6..=510).contains(&record.opus.bitrate) {
+                    report.problem(format!(
+                        "{prefix}.opus.bitrate 应为 6-510 kbps，当前为 {}",
+                        record.opus.bitrate
+                    ));
+                    valid = false;
+                }
+                if record.opus.complexity > 10 {
+                    report.warn(format!("{prefix}.opus.complexity 超过 10，按 10 处理",));
+                }
+            }
+            AudioFormat::Mp3 => {
+                if record.mp3.quality > 9 {
+                    report.problem(format!(
+                        "{prefix}.mp3.quality 应为 0-9，当前为 {}",
+                        record.mp3.quality
+                    ));
+                    valid = false;
+                }
+                if !(8..=320).contains(&record.mp3.bitrate) {
+                    report.problem(format!(
+                        "{prefix}.mp3.bitrate 应为 8-320 kbps，当前为 {}",
+                        record.mp3.bitrate
+                    ));
+                    valid = false;
+                }
+            }
+        }
+        if valid {
+            report.ok(format!(
+                "{prefix}: {:?}，{} Hz，写入字段 {}",
+                record.format, record.sample_rate, record.field_name
+            ));
+        }
+    }
+}
+
+fn check_video(cfg: &Config, report: &mut Report) {
+    if cfg.hot_key.video.is_empty() {
+        return;
+    }
+    let video = &cfg.video;
+    if video.field_name.is_empty() {
+        report.problem("video.fieldName 为空");
+    }
+    for (option, value) in [
+        ("durationSecs", video.duration_secs),
+        ("frameRate", video.frame_rate),
+        ("bitrate", video.bitrate),
+    ] {
+        if value == 0 {
+            report.problem(format!("video.{option} 不能为 0"));
+        }
+    }
+    report.ok(format!(
+        "视频: {} 秒，{} fps，{} bit/s",
+        video.duration_secs, video.frame_rate, video.bitrate
+    ));
+}
+
+/// 用到的外部编码器能否运行
+fn check_encoders(cfg: &Config, report: &mut Report) {
+    let shot = &cfg.screen_shot;
+    match shot.format {
+        ScreenshotFormat::Avif if matches!(shot.avif.encoder, AvifEncoder::Ffmpeg) => {
+            check_program(&shot.avif.ffmpeg_path, "-version", report);
+        }
+        ScreenshotFormat::Jxl => check_program(&shot.jxl.encoder_path, "--version", report),
+        _ => {}
+    }
+}
+
+fn check_program(program: &str, version_arg: &str, report: &mut Report) {
+    let mut command = Command::new(program);
+    command.arg(version_arg);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    match command.output() {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout);
+            let version = version.lines().next().unwrap_or_default();
+            report.ok(format!("{program} 可用: {version}"));
+        }
+        Ok(output) => report.problem(format!("{program} 运行失败: {}", output.status)),
+        Err(e) => report.problem(format!("无法运行 {program}: {e}")),
+    }
+}
+
+/// 配置的字段是否都存在于目标笔记类型中
+async fn check_anki(cfg: &Config, report: &mut Report) {
+    let anki = match AnkiClient::new(&cfg.anki) {
+        Ok(anki) => anki,
+        Err(e) => return report.problem(format!("{e:#}")),
+    };
+    let fields = crate::configured_fields(cfg);
+    match anki.health_check(&fields).await {
+        Ok(()) => report.ok(format!("字段 {} 都存在于笔记类型中", fields.join(", "))),
+        Err(e) => report.problem(format!("{e:#}")),
+    }
+}
@@ -0,0 +1,70 @@
+use crate::anki::{AnkiClient, escape_search};
+use crate::config::Config;
+use anyhow::{Result, bail};
+use log::{info, warn};
+use std::collections::BTreeSet;
+
+/// 删除媒体目录中由本程序生成、但已没有卡片引用的文件；`delete` 为 false 时只列出不删除
+///
+/// 只考虑以配置的截图、录音、视频字段名开头的文件，这是本程序生成的文件名格式。
+/// 每个文件都在整个牌组集合中搜索，引用被移到其他字段或其他笔记类型时同样保留。
+pub async fn clean_media(anki: &AnkiClient, cfg: &Config, delete: bool) -> Result<()> {
+    let prefixes = media_fields(cfg);
+    let mut candidates = BTreeSet::new();
+    for prefix in &prefixes {
+        candidates.extend(anki.media_file_names(&format!("{prefix}_*")).await?);
+    }
+    if candidates.is_empty() {
+        info!("No media files generated by game2anki found");
+        return Ok(());
+    }
+
+    // 搜索不到任何卡片时多半是连错了配置文件或搜索出错，不能据此认定文件没有引用
+    let total = anki.find_notes("deck:*").await?.len();
+    if total == 0 {
+        bail!(
+            "No notes found in the collection, refusing to treat {} media files as unreferenced",
+            candidates.len()
+        );
+    }
+
+    let mut orphaned = Vec::new();
+    for name in &candidates {
+        let query = format!("\"*{}*\"", escape_search(name));
+        if anki.find_notes(&query).await?.is_empty() {
+            orphaned.push(name);
+        }
+    }
+    info!(
+        "{} of {} media files are not referenced by any of {} notes",
+        orphaned.len(),
+        candidates.len(),
+        total
+    );
+    for name in orphaned {
+        if !delete {
+            info!("Would delete: {name}");
+        } else if let Err(e) = anki.delete_media(name).await {
+            warn!("Failed to delete {name}: {e}");
+        } else {
+            info!("Deleted: {name}");
+        }
+    }
+    if !delete {
+        info!("Run again with --yes to delete these files");
+    }
+    Ok(())
+}
+
+/// 写入媒体的字段，也是生成的文件名的前缀
+fn media_fields(cfg: &Config) -> BTreeSet<String> {
+    let mut fields = BTreeSet::from([
+        cfg.screen_shot.field_name.clone(),
+        cfg.video.field_name.clone(),
+    ]);
+    for record in &cfg.audio_record {
+        fields.insert(record.field_name.clone());
+        fields.insert(record.slowed.field_name.clone());
+    }
+    fields
+}
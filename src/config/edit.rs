@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use toml::{Table, Value};
+use toml_edit::{DocumentMut, Item, TableLike};
+
+/// 把改动后的配置写回原来的 TOML 文本，保留注释与格式
+///
+/// 没有改动的项原样保留，改动的值就地替换并保留所在行的注释，删除的项移除，新增的表追加到文件末尾。
+pub fn write_toml(original: &str, table: &Table) -> Result<String> {
+    let mut document: DocumentMut = original.parse()?;
+    sync_table(document.as_table_mut(), table)?;
+    Ok(document.to_string())
+}
+
+/// 按点分隔的路径修改值，数组中的条目用序号表示，如 `audioRecord.0.format`；缺少的表自动补上
+pub fn set_value(target: &mut Value, path: &[&str], value: Value) -> Result<()> {
+    let Some((key, rest)) = path.split_first() else {
+        *target = value;
+        return Ok(());
+    };
+    let child = match (target, key.parse::<usize>()) {
+        (Value::Array(items), Ok(index)) => items.get_mut(index),
+        // 序号只用于数组，不在表中补出名为 "0" 的项
+        (Value::Table(table), Ok(_)) => table.get_mut(*key),
+        (Value::Table(table), Err(_)) => Some(
+            table
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Table(Table::new())),
+        ),
+        _ => None,
+    }
+    .with_context(|| format!("No such config entry: {key}"))?;
+    set_value(child, rest, value)
+}
+
+fn sync_table(document: &mut dyn TableLike, table: &Table) -> Result<()> {
+    let removed: Vec<String> = document
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !table.contains_key(key))
+        .collect();
+    for key in removed {
+        document.remove(&key);
+    }
+    for (key, value) in table {
+        let Some(item) = document.get_mut(key) else {
+            document.insert(key, to_item(key, value)?);
+            continue;
+        };
+        match value {
+            Value::Table(table) if item.is_table_like() => {
+                sync_table(item.as_table_like_mut().context("Not a table")?, table)?;
+            }
+            // 数组中的表逐个同步，数量变化时整个替换
+            Value::Array(values)
+                if values.iter().all(Value::is_table)
+                    && item
+                        .as_array_of_tables()
+                        .is_some_and(|tables| tables.len() == values.len()) =>
+            {
+                let tables = item
+                    .as_array_of_tables_mut()
+                    .context("Not an array of tables")?;
+                for (document, value) in tables.iter_mut().zip(values) {
+                    if let Value::Table(table) = value {
+                        sync_table(document, table)?;
+                    }
+                }
+            }
+            value => {
+                let mut new = to_item(key, value)?;
+                if let (Some(old), Some(new)) = (item.as_value(), new.as_value_mut()) {
+                    // 写法不同但值相同时，例如单引号字符串，保留原来的写法
+                    if same_value(old, value) {
+                        continue;
+                    }
+                    *new.decor_mut() = old.decor().clone();
+                }
+                *item = new;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn same_value(old: &toml_edit::Value, new: &Value) -> bool {
+    old.clone()
+        .decorated("", "")
+        .to_string()
+        .parse::<Value>()
+        .is_ok_and(|old| &old == new)
+}
+
+/// 把一个值转换为文档中的项：表写成 `[section]`，表的数组写成 `[[section]]`
+fn to_item(key: &str, value: &Value) -> Result<Item> {
+    let table = Table::from_iter([(key.to_string(), value.clone())]);
+    let mut document: DocumentMut = toml::to_string_pretty(&table)?.parse()?;
+    let mut item = document
+        .remove(key)
+        .context("Failed to convert config value")?;
+    move_to_end(&mut item);
+    Ok(item)
+}
+
+/// 新增的表放在文件末尾，而不是按解析时的位置插在原有的表之间
+fn move_to_end(item: &mut Item) {
+    let tables: Vec<&mut toml_edit::Table> = match item {
+        Item::Table(table) => vec![table],
+        Item::ArrayOfTables(tables) => tables.iter_mut().collect(),
+        _ => return,
+    };
+    for table in tables {
+        table.set_position(isize::MAX);
+        table.decor_mut().set_prefix("\n");
+        for (_, child) in table.iter_mut() {
+            move_to_end(child);
+        }
+    }
+}
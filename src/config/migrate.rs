@@ -0,0 +1,134 @@
+use super::CONFIG_VERSION;
+use anyhow::Result;
+use toml::{Table, Value};
+
+/// 依次执行的升级步骤，第 i 项把版本 i 的配置升级到版本 i + 1
+///
+/// 重命名或改变字段类型时在这里追加一步，并增大 `CONFIG_VERSION`；
+/// 只新增字段时不需要，缺少的字段在解析时取默认值。
+const MIGRATIONS: &[fn(&mut Table)] = &[audio_record_list];
+
+/// 版本 0 → 1：`audioRecord` 由单个表改为表的数组，录音热键由 `hotKey.audioRecord`
+/// 移到每一项的 `hotKey`
+fn audio_record_list(table: &mut Table) {
+    let hot_key = table
+        .get_mut("hotKey")
+        .and_then(Value::as_table_mut)
+        .and_then(|hot_key| hot_key.remove("audioRecord"));
+    let mut records = match table.remove("audioRecord") {
+        Some(Value::Table(record)) => vec![Value::Table(record)],
+        Some(Value::Array(records)) => records,
+        Some(other) => {
+            table.insert("audioRecord".to_string(), other);
+            return;
+        }
+        // 只设置了热键时，其余的录音设置取默认值
+        None if hot_key.is_some() => vec![Value::Table(Table::new())],
+        None => return,
+    };
+    if let Some(hot_key) = hot_key
+        && let Some(Value::Table(first)) = records.first_mut()
+    {
+        first.entry("hotKey").or_insert(hot_key);
+    }
+    table.insert("audioRecord".to_string(), Value::Array(records));
+}
+
+/// 把旧版本的配置升级到当前版本，返回做过的改动，没有改动时为空
+///
+/// 用户已有的设置都会保留，只按升级步骤改写旧的字段。比当前程序更新的配置不做改动，
+/// 以免被旧版本改写。
+pub fn migrate(table: &mut Table) -> Result<Vec<String>> {
+    let version = table
+        .get("version")
+        .and_then(Value::as_integer)
+        .unwrap_or(0)
+        .max(0) as usize;
+    if version > CONFIG_VERSION as usize {
+        log::warn!(
+            "Config version {version} is newer than supported version {CONFIG_VERSION}, \
+             settings added by newer versions are ignored"
+        );
+        return Ok(Vec::new());
+    }
+    let mut changes = Vec::new();
+    for (from, step) in MIGRATIONS.iter().enumerate().skip(version) {
+        step(table);
+        changes.push(format!("version {from} -> {}", from + 1));
+    }
+    table.insert("version".to_string(), Value::Integer(CONFIG_VERSION.into()));
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    /// 第一个版本写出的默认配置，原样保留
+    const VERSION_0_DEFAULT: &str = r#"logLevel = "info"
+
+[hotKey]
+screenShot = ["CapsLock"]
+audioRecord = ["Tab"]
+
+[screenShot]
+format = "avif"
+fieldName = "Picture"
+quality = 60
+speed = 6
+excludeTitleBar = true
+
+[audioRecord]
+format = "opus"
+fieldName = "SentenceAudio"
+sampleRate = 48000
+
+[anki]
+ankiConnectUrl = "http://127.0.0.1:8765"
+"#;
+
+    #[test]
+    fn upgrades_version_0_default_config() {
+        let mut table: Table = toml::from_str(VERSION_0_DEFAULT).unwrap();
+        let changes = migrate(&mut table).unwrap();
+        assert_eq!(changes, ["version 0 -> 1"]);
+        assert_eq!(table["version"].as_integer(), Some(CONFIG_VERSION.into()));
+        assert!(
+            !table["hotKey"]
+                .as_table()
+                .unwrap()
+                .contains_key("audioRecord")
+        );
+        let records = table["audioRecord"].as_array().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["hotKey"], Value::from(vec!["Tab"]));
+        assert_eq!(records[0]["fieldName"].as_str(), Some("SentenceAudio"));
+
+        let config: Config = Value::Table(table).try_into().unwrap();
+        assert_eq!(config.audio_record.len(), 1);
+        assert_eq!(config.audio_record[0].hot_key, [rdev::Key::Tab]);
+        assert_eq!(config.audio_record[0].sample_rate, 48000);
+    }
+
+    #[test]
+    fn moves_audio_hotkey_without_audio_section() {
+        let mut table: Table = toml::from_str("[hotKey]\naudioRecord = \"F9\"").unwrap();
+        migrate(&mut table).unwrap();
+        assert_eq!(table["audioRecord"][0]["hotKey"].as_str(), Some("F9"));
+    }
+
+    #[test]
+    fn current_version_is_unchanged() {
+        let mut table: Table = toml::from_str(&format!("version = {CONFIG_VERSION}")).unwrap();
+        assert!(migrate(&mut table).unwrap().is_empty());
+    }
+
+    #[test]
+    fn newer_version_is_left_alone() {
+        let newer = CONFIG_VERSION + 1;
+        let mut table: Table = toml::from_str(&format!("version = {newer}")).unwrap();
+        assert!(migrate(&mut table).unwrap().is_empty());
+        assert_eq!(table["version"].as_integer(), Some(newer.into()));
+    }
+}
@@ -1,62 +1,575 @@
 use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, mpsc};
+use std::time::Duration;
+mod edit;
+mod migrate;
+mod template;
 mod types;
 
 pub use types::*;
 
-pub fn load_user_config() -> Result<Config> {
+/// 命令行中覆盖配置文件的参数，读取与重新加载配置时都会应用
+#[derive(Debug, Default)]
+pub struct Overrides {
+    /// 代替默认位置的配置文件
+    pub config_file: Option<PathBuf>,
+    pub log_level: Option<LogLevel>,
+    pub anki_connect_url: Option<String>,
+}
+
+static OVERRIDES: OnceLock<Overrides> = OnceLock::new();
+
+/// 在读取配置之前调用，只有第一次调用生效
+pub fn set_overrides(overrides: Overrides) {
+    if OVERRIDES.set(overrides).is_err() {
+        log::warn!("Config overrides already set, ignoring");
+    }
+}
+
+fn apply_overrides(mut config: Config) -> Config {
+    if let Some(overrides) = OVERRIDES.get() {
+        if let Some(level) = &overrides.log_level {
+            config.log_level = level.clone();
+        }
+        if let Some(url) = &overrides.anki_connect_url {
+            config.anki.anki_connect_url = url.clone();
+        }
+    }
+    config
+}
+
+/// 配置目录下依次查找的配置文件，都不存在时创建第一个
+const CONFIG_FILE_NAMES: &[&str] = &["config.toml", "config.json", "config.yaml", "config.yml"];
+
+/// 使用中的配置文件，未通过命令行指定时位于配置目录下
+pub fn config_file_path() -> Result<PathBuf> {
+    if let Some(path) = OVERRIDES.get().and_then(|o| o.config_file.clone()) {
+        return Ok(path);
+    }
     let config_dir = get_config_directory()?;
-    let config_file_path = config_dir.join("config.toml");
+    let path = CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| config_dir.join(name))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| config_dir.join(CONFIG_FILE_NAMES[0]));
+    Ok(path)
+}
+
+/// 配置文件的格式，按扩展名判断，其余扩展名都按 TOML 解析
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                Self::Yaml
+            }
+            _ => Self::Toml,
+        }
+    }
+
+    /// 解析为 TOML 表，之后的升级、合并都在 TOML 表上进行
+    fn parse(self, content: &str) -> Result<toml::Table> {
+        match self {
+            Self::Toml => Ok(toml::from_str(content)?),
+            Self::Json => Ok(serde_json::from_str(content)?),
+            Self::Yaml => Ok(::config::Config::builder()
+                .add_source(::config::File::from_str(
+                    content,
+                    ::config::FileFormat::Yaml,
+                ))
+                .build()?
+                .try_deserialize()?),
+        }
+    }
+
+    /// 把改动后的配置写回，TOML 保留 `original` 中的注释；YAML 没有可用的序列化，返回 None
+    fn serialize(self, original: &str, table: &toml::Table) -> Option<Result<String>> {
+        match self {
+            Self::Toml => Some(edit::write_toml(original, table)),
+            Self::Json => Some(serde_json::to_string_pretty(table).map_err(Into::into)),
+            Self::Yaml => None,
+        }
+    }
+}
 
-    // 确保配置目录存在
-    fs::create_dir_all(&config_dir)
-        .with_context(|| format!("Failed to create config directory: {config_dir:?}"))?;
+/// 读取配置文件，不存在时创建默认配置
+///
+/// 旧版本的配置升级后写回文件，缺少的字段取默认值；语法或取值有误时返回错误，不改动文件。
+pub fn load_user_config() -> Result<Config> {
+    load_config_file(&config_file_path()?).map(apply_overrides)
+}
+
+fn load_config_file(config_file_path: &Path) -> Result<Config> {
+    let format = ConfigFormat::of(config_file_path);
+    // 确保配置文件所在目录存在
+    if let Some(config_dir) = config_file_path.parent() {
+        fs::create_dir_all(config_dir)
+            .with_context(|| format!("Failed to create config directory: {config_dir:?}"))?;
+    }
 
     if !config_file_path.exists() {
-        create_default_config(&config_file_path)?;
+        create_default_config(config_file_path, format)?;
     }
 
-    // 读取并解析配置文件
+    // 读取并解析配置文件，旧版本的配置先升级
+    let config_content = fs::read_to_string(config_file_path)
+        .with_context(|| format!("Failed to read config file: {config_file_path:?}"))?;
+    let parsed = format.parse(&config_content).and_then(|mut table| {
+        let changes = migrate::migrate(&mut table)?;
+        toml::Value::Table(table.clone()).try_into::<Config>()?;
+        Ok((table, changes))
+    });
+
+    // 配置有误时报告错误并保留原文件，由用户修改，而不是备份后重建
+    let (mut table, changes) = parsed.with_context(|| {
+        format!(
+            "Invalid config file {config_file_path:?}; fix it, or delete it to recreate the default config"
+        )
+    })?;
+    if !changes.is_empty() {
+        write_upgraded(config_file_path, format, &config_content, &table, &changes)?;
+    }
+    // 文件本身能解析时才合并片段并叠加环境变量
+    merge_includes(&mut table, config_file_path, 0, &mut Vec::new())?;
+    with_env_overrides(&toml::to_string(&table)?)
+}
+
+/// 升级后写回配置文件，保留用户的设置，原文件备份为 .bak
+fn write_upgraded(
+    config_file_path: &Path,
+    format: ConfigFormat,
+    original: &str,
+    table: &toml::Table,
+    changes: &[String],
+) -> Result<()> {
+    for change in changes {
+        log::info!("Config upgraded: {change}");
+    }
+    let Some(upgraded) = format.serialize(original, table) else {
+        log::info!("Upgraded config is not written back for {format:?} files");
+        return Ok(());
+    };
+    let upgraded = upgraded.context("Failed to serialize upgraded config")?;
+    let bak_path = config_file_path.with_extension("bak");
+    fs::copy(config_file_path, &bak_path)
+        .with_context(|| format!("Failed to backup old config to {bak_path:?}"))?;
+    fs::write(config_file_path, upgraded)
+        .with_context(|| format!("Failed to write upgraded config: {config_file_path:?}"))?;
+    log::info!("Old config has been backed up to {bak_path:?}");
+    Ok(())
+}
+
+/// 读取配置文件但不改动它，用于运行中重新加载与检查配置；解析失败时返回错误
+pub fn reload_user_config() -> Result<Config> {
+    let config_file_path = config_file_path()?;
     let config_content = fs::read_to_string(&config_file_path)
         .with_context(|| format!("Failed to read config file: {config_file_path:?}"))?;
+    let mut table = ConfigFormat::of(&config_file_path)
+        .parse(&config_content)
+        .with_context(|| format!("Failed to parse config file: {config_file_path:?}"))?;
+    // 运行中只在内存中升级，写回留到下次启动
+    migrate::migrate(&mut table)?;
+    merge_includes(&mut table, &config_file_path, 0, &mut Vec::new())?;
+    let config_content = toml::to_string(&table)?;
+    toml::from_str::<Config>(&config_content)
+        .with_context(|| format!("Failed to parse config file: {config_file_path:?}"))?;
+    with_env_overrides(&config_content).map(apply_overrides)
+}
 
-    let config: Result<Config, toml::de::Error> = toml::from_str(&config_content);
-    match config {
-        Ok(cfg) => Ok(cfg),
-        Err(e) => {
-            // 解析失败，自动备份原配置并重建
-            let bak_path = config_file_path.with_extension("bak");
-            fs::rename(&config_file_path, &bak_path)
-                .with_context(|| format!("Failed to backup old config to {bak_path:?}"))?;
-            create_default_config(&config_file_path)?;
-            let config_content = fs::read_to_string(&config_file_path)
-                .with_context(|| format!("Failed to read new config file: {config_file_path:?}"))?;
-            let config: Config = toml::from_str(&config_content)
-                .with_context(|| "Failed to parse new config file")?;
-            log::warn!(
-                "Config parse error: {e}. Old config has been backed up to {bak_path:?}, new config created."
-            );
-            Ok(config)
-        }
-    }
-}
-
-fn get_config_directory() -> Result<PathBuf> {
-    if let Some(config_dir) = dirs::config_dir() {
-        Ok(config_dir.join("Game2Anki"))
-    } else {
-        anyhow::bail!("Could not determine config directory")
+/// 修改配置文件中的一项并保存，用于托盘菜单；保存后由配置文件的监视重新加载
+///
+/// `key` 为点分隔的路径，数组中的条目用序号表示，如 `audioRecord.0.format`。
+/// 写入最后设置了这一项的文件，这一项来自 include 的片段时修改片段；都没有设置时写入主配置文件。
+/// TOML 文件保留原有的注释与格式；修改后的配置无法解析时不保存。
+pub fn update_user_config(key: &str, value: toml::Value) -> Result<()> {
+    let config_file_path = config_file_path()?;
+    let path: Vec<&str> = key.split('.').collect();
+    let target = config_files(&config_file_path)
+        .into_iter()
+        .rev()
+        .find(|file| {
+            fs::read_to_string(file)
+                .ok()
+                .and_then(|content| ConfigFormat::of(file).parse(&content).ok())
+                .is_some_and(|table| sets_key(&table, &path))
+        })
+        .unwrap_or_else(|| config_file_path.clone());
+    let format = ConfigFormat::of(&target);
+    let content = fs::read_to_string(&target)
+        .with_context(|| format!("Failed to read config file: {target:?}"))?;
+    let mut table = format.parse(&content)?;
+    // 主配置文件还是旧版本时一并升级，否则按新的结构修改会写出错误的项
+    if target == config_file_path {
+        migrate::migrate(&mut table)?;
     }
+    let mut root = toml::Value::Table(table);
+    edit::set_value(&mut root, &path, value.clone())?;
+    let table = root.as_table().context("Config root is not a table")?;
+    let updated = format
+        .serialize(&content, table)
+        .with_context(|| format!("{format:?} config files cannot be written back"))??;
+    // 与读取时一样先在内存中升级并合并片段，旧版本的配置也能修改；
+    // 写入的是最后设置这一项的文件，合并后的值就是新值
+    let main_content = fs::read_to_string(&config_file_path)
+        .with_context(|| format!("Failed to read config file: {config_file_path:?}"))?;
+    let mut merged = ConfigFormat::of(&config_file_path).parse(&main_content)?;
+    migrate::migrate(&mut merged)?;
+    merge_includes(&mut merged, &config_file_path, 0, &mut Vec::new())?;
+    let mut merged = toml::Value::Table(merged);
+    edit::set_value(&mut merged, &path, value.clone())?;
+    merged
+        .try_into::<Config>()
+        .with_context(|| format!("Invalid value for {key}: {value}"))?;
+    fs::write(&target, updated)
+        .with_context(|| format!("Failed to write config file: {target:?}"))?;
+    log::info!("Config updated: {key} = {value} in {}", target.display());
+    Ok(())
 }
 
-fn create_default_config(config_path: &PathBuf) -> Result<()> {
-    let default_cfg = Config::default();
-    // 序列化为 TOML
-    let default_content = toml::to_string_pretty(&default_cfg)
-        .map_err(|e| anyhow::anyhow!("Failed to serialize default config: {}", e))?;
+/// 文件中是否设置了这一项；数组在合并时整个替换，设置了数组即设置了其中的每一项
+fn sets_key(table: &toml::Table, path: &[&str]) -> bool {
+    let Some((key, rest)) = path.split_first() else {
+        return false;
+    };
+    match table.get(*key) {
+        None => false,
+        Some(toml::Value::Table(table)) if !rest.is_empty() => sets_key(table, rest),
+        Some(_) => true,
+    }
+}
+
+/// 片段中还可以再 include 其他片段，超过这个层数时视为循环引用
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// 依次合并 `include` 中列出的配置片段，片段中的设置覆盖所在文件中的，后面的片段覆盖前面的
+///
+/// 相对路径以所在文件的目录为准；表逐项合并，数组（如 `audioRecord`）整个替换。
+/// 读取的片段按合并的顺序加入 `files`。
+fn merge_includes(
+    table: &mut toml::Table,
+    file_path: &Path,
+    depth: usize,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let includes: Vec<String> = match table.get("include") {
+        Some(value) => value
+            .clone()
+            .try_into()
+            .with_context(|| format!("include must be a list of paths: {file_path:?}"))?,
+        None => return Ok(()),
+    };
+    if depth >= MAX_INCLUDE_DEPTH {
+        anyhow::bail!("Config includes are nested too deeply: {file_path:?}");
+    }
+    let base_dir = file_path.parent().unwrap_or(Path::new(""));
+    for include in includes {
+        let path = base_dir.join(&include);
+        files.push(path.clone());
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read included config: {path:?}"))?;
+        let mut fragment = ConfigFormat::of(&path)
+            .parse(&content)
+            .with_context(|| format!("Failed to parse included config: {path:?}"))?;
+        merge_includes(&mut fragment, &path, depth + 1, files)?;
+        // 版本与引用关系只看主配置文件
+        fragment.remove("version");
+        fragment.remove("include");
+        merge_tables(table, fragment);
+    }
+    Ok(())
+}
+
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_tables(base, overlay)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// 环境变量的前缀，如 `GAME2ANKI_ANKI__ANKI_CONNECT_URL` 覆盖 `anki.ankiConnectUrl`
+const ENV_PREFIX: &str = "GAME2ANKI";
+
+/// 在配置文件之上叠加 `GAME2ANKI_*` 环境变量
+///
+/// 层级之间用双下划线分隔，每一级的名称写成下划线形式，会转换为配置文件中的驼峰命名。
+fn with_env_overrides(config_content: &str) -> Result<Config> {
+    ::config::Config::builder()
+        .add_source(::config::File::from_str(
+            config_content,
+            ::config::FileFormat::Toml,
+        ))
+        .add_source(
+            ::config::Environment::with_prefix(ENV_PREFIX)
+                .prefix_separator("_")
+                .separator("__")
+                .convert_case(::config::Case::Camel)
+                .try_parsing(true),
+        )
+        .build()
+        .and_then(|config| config.try_deserialize())
+        .context("Failed to apply environment variable overrides")
+}
+
+/// 保存时编辑器可能连续触发多次事件，最后一次事件后等待这么久再通知
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// 监视配置文件及其 include 的片段，内容变化时调用 `on_change`
+///
+/// 每次变化后重新解析 include，新增的片段随之加入监视。
+pub fn watch_user_config(on_change: impl Fn() + Send + 'static) -> Result<()> {
+    let config_file_path = config_file_path()?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mut watched_dirs = HashSet::new();
+    let mut files = config_files(&config_file_path);
+    let config_dir = parent_dir(&config_file_path).to_path_buf();
+    watcher
+        .watch(&config_dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch config directory: {config_dir:?}"))?;
+    watched_dirs.insert(config_dir);
+    watch_dirs(&mut watcher, &files, &mut watched_dirs);
+    std::thread::spawn(move || {
+        let is_config_change =
+            |event: &notify::Result<notify::Event>, files: &[PathBuf]| match event {
+                Ok(event) => {
+                    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                        && event.paths.iter().any(|path| {
+                            files
+                                .iter()
+                                .any(|file| file.file_name() == path.file_name())
+                        })
+                }
+                Err(e) => {
+                    log::warn!("Config watcher error: {e}");
+                    false
+                }
+            };
+        while let Ok(event) = rx.recv() {
+            if !is_config_change(&event, &files) {
+                continue;
+            }
+            while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+            files = config_files(&config_file_path);
+            watch_dirs(&mut watcher, &files, &mut watched_dirs);
+            on_change();
+        }
+    });
+    Ok(())
+}
+
+/// 监视片段所在的目录，编辑器用替换文件的方式保存时也能收到
+fn watch_dirs(
+    watcher: &mut RecommendedWatcher,
+    files: &[PathBuf],
+    watched_dirs: &mut HashSet<PathBuf>,
+) {
+    for file in files {
+        let dir = parent_dir(file);
+        if watched_dirs.contains(dir) {
+            continue;
+        }
+        match watcher.watch(dir, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                watched_dirs.insert(dir.to_path_buf());
+            }
+            Err(e) => log::warn!("Failed to watch included config directory {dir:?}: {e}"),
+        }
+    }
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    path.parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+/// 主配置文件及其 include 的所有片段，按合并的顺序；文件有误时只列出能解析出的部分
+fn config_files(config_file_path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![config_file_path.to_path_buf()];
+    if let Ok(content) = fs::read_to_string(config_file_path)
+        && let Ok(mut table) = ConfigFormat::of(config_file_path).parse(&content)
+    {
+        // 读取失败的片段也已列出，修正后同样会触发重新加载
+        let _ = merge_includes(&mut table, config_file_path, 0, &mut files);
+    }
+    files
+}
+
+/// 两份配置中取值不同的项，如 `audioRecord`、`screenShot.quality`
+pub fn changed_keys(old: &Config, new: &Config) -> Result<Vec<String>> {
+    let old = toml::Value::try_from(old)?;
+    let new = toml::Value::try_from(new)?;
+    let mut changed = Vec::new();
+    diff_values("", &old, &new, &mut changed);
+    Ok(changed)
+}
+
+fn diff_values(path: &str, old: &toml::Value, new: &toml::Value, changed: &mut Vec<String>) {
+    match (old, new) {
+        (toml::Value::Table(old), toml::Value::Table(new)) => {
+            let mut keys: Vec<_> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => diff_values(&path, old, new, changed),
+                    _ => changed.push(path),
+                }
+            }
+        }
+        // 数组整体比较，如多个录音配置
+        _ if old != new => changed.push(path.to_string()),
+        _ => {}
+    }
+}
+
+/// 程序所在目录下有这个文件（或配置文件）时使用便携模式
+const PORTABLE_FLAG: &str = "portable.flag";
+
+/// 便携模式下程序所在的目录，配置、离线队列等都保存在这里而不是用户的配置目录
+///
+/// 适合从 U 盘或游戏目录直接运行的情况。
+pub fn portable_directory() -> Option<&'static Path> {
+    static PORTABLE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    PORTABLE_DIR
+        .get_or_init(|| {
+            let exe = std::env::current_exe().ok()?;
+            let dir = exe.parent()?;
+            std::iter::once(&PORTABLE_FLAG)
+                .chain(CONFIG_FILE_NAMES)
+                .any(|name| dir.join(name).is_file())
+                .then(|| dir.to_path_buf())
+        })
+        .as_deref()
+}
+
+/// 配置中的目录：便携模式下相对路径以程序所在目录为准，例如 `archiveDir = "archive"`
+pub fn data_path(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    match portable_directory() {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path,
+    }
+}
+
+/// 配置目录：Windows 上为 `%APPDATA%\Game2Anki`，Linux 上遵循 XDG（`~/.config/game2anki`），
+/// macOS 上为 `~/Library/Application Support/Game2Anki`
+pub fn get_config_directory() -> Result<PathBuf> {
+    if let Some(dir) = portable_directory() {
+        return Ok(dir.to_path_buf());
+    }
+    // Windows 上沿用以前的位置，不放到 ProjectDirs 的 config 子目录下
+    let config_dir = if cfg!(windows) {
+        directories::BaseDirs::new().map(|dirs| dirs.config_dir().join("Game2Anki"))
+    } else {
+        directories::ProjectDirs::from("", "", "Game2Anki")
+            .map(|dirs| dirs.config_dir().to_path_buf())
+    };
+    config_dir.ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))
+}
+
+fn create_default_config(config_path: &Path, format: ConfigFormat) -> Result<()> {
+    // TOML 带注释，说明每个配置项的用途；JSON 也是合法的 YAML
+    let default_content = match format {
+        ConfigFormat::Toml => template::default_config_template(),
+        ConfigFormat::Json | ConfigFormat::Yaml => {
+            serde_json::to_string_pretty(&Config::default()).map_err(Into::into)
+        }
+    }
+    .map_err(|e| anyhow::anyhow!("Failed to serialize default config: {}", e))?;
     fs::write(config_path, default_content)
         .with_context(|| format!("Failed to write default config to {config_path:?}"))?;
     log::info!("Created default config file at: {config_path:?}");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_tables_overrides_nested_keys() {
+        let mut base: toml::Table = toml::from_str(
+            r#"
+            logLevel = "info"
+            [hotKey]
+            screenShot = "F9"
+            video = "F10"
+            [[audioRecord]]
+            hotKey = "F11"
+            "#,
+        )
+        .unwrap();
+        let overlay: toml::Table = toml::from_str(
+            r#"
+            [hotKey]
+            video = "F12"
+            [[audioRecord]]
+            hotKey = "F8"
+            [[audioRecord]]
+            hotKey = "F7"
+            "#,
+        )
+        .unwrap();
+        merge_tables(&mut base, overlay);
+        // 表按键合并，其余的值与数组整体替换
+        assert_eq!(base["logLevel"].as_str(), Some("info"));
+        assert_eq!(base["hotKey"]["screenShot"].as_str(), Some("F9"));
+        assert_eq!(base["hotKey"]["video"].as_str(), Some("F12"));
+        let records = base["audioRecord"].as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["hotKey"].as_str(), Some("F8"));
+    }
+
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        let old: toml::Value = toml::from_str(old).unwrap();
+        let new: toml::Value = toml::from_str(new).unwrap();
+        let mut changed = Vec::new();
+        diff_values("", &old, &new, &mut changed);
+        changed
+    }
+
+    #[test]
+    fn diff_values_reports_changed_paths() {
+        assert!(diff("[hotKey]\nvideo = \"F9\"", "[hotKey]\nvideo = \"F9\"").is_empty());
+        assert_eq!(
+            diff("[hotKey]\nvideo = \"F9\"", "[hotKey]\nvideo = \"F10\""),
+            ["hotKey.video"]
+        );
+        // 新增与删除的键同样算作改动
+        assert_eq!(diff("a = 1", "b = 1"), ["a", "b"]);
+    }
+
+    #[test]
+    fn diff_values_compares_arrays_whole() {
+        assert_eq!(
+            diff(
+                "[[audioRecord]]\nhotKey = \"F9\"",
+                "[[audioRecord]]\nhotKey = \"F10\""
+            ),
+            ["audioRecord"]
+        );
+    }
+}
@@ -0,0 +1,656 @@
+use super::Config;
+use anyhow::{Result, anyhow};
+use std::fmt::Write;
+use toml::{Table, Value};
+
+/// 文件开头的说明
+const HEADER: &str = "\
+Game2Anki 配置文件
+
+热键的写法：单个键写成字符串，组合键写成数组或用 + 连接，例如 \"F9\"、[\"Alt\", \"KeyS\"]、\"Ctrl+Shift+S\"；
+留空 [] 表示不使用该热键。按键名称不区分大小写：
+  字母 KeyA-KeyZ 或 A-Z，数字 Num0-Num9 或 0-9，小键盘 Kp0-Kp9、KpPlus、KpMinus、KpMultiply、KpDivide、KpReturn
+  功能键 F1-F24，修饰键 Ctrl、Shift、Alt、Win（左右两侧都能触发），也可写 ControlLeft、ShiftRight、AltGr 等
+  其他 Space、Tab、Return/Enter、Escape/Esc、Backspace、CapsLock、Insert、Delete、Home、End、PageUp、PageDown、
+  UpArrow、DownArrow、LeftArrow、RightArrow、PrintScreen、ScrollLock、Pause、NumLock，以及 ` - = [ ] ; ' \\ , . /
+  宏键盘上没有名称的键可以用扫描码 \"Scan(0x64)\" 或虚拟键码 \"Unknown(124)\"";
+
+/// 各配置项的说明，按路径查找，数组中的表不带下标；找不到时按 `*.` 加最后一级名称查找
+const COMMENTS: &[(&str, &str)] = &[
+    ("version", "配置格式的版本，由程序维护，请勿修改"),
+    (
+        "include",
+        "合并进来的配置片段，例如每个游戏单独的设置 [\"profiles/persona5.toml\"]；\n片段中的设置覆盖本文件中的，后面的片段覆盖前面的，相对路径以本文件所在目录为准",
+    ),
+    ("logLevel", "日志级别：trace、debug、info、warn、error"),
+    (
+        "tray",
+        "在通知区域显示托盘图标，从菜单中修改截图、录音与视频的常用设置和热键，修改会写回本文件",
+    ),
+    // 热键
+    ("hotKey", "热键"),
+    ("hotKey.screenShot", "截图并写入最新卡片"),
+    ("hotKey.confirm", "截图或录音预览时保存"),
+    ("hotKey.discard", "截图或录音预览时放弃"),
+    (
+        "hotKey.redoAudio",
+        "重录：丢弃最近一次保存的录音，重新开始录音并写回同一张卡片",
+    ),
+    ("hotKey.cancelAudio", "中止所有正在进行的录音，不保存"),
+    (
+        "hotKey.retakeScreenshot",
+        "重拍上一张截图，写回同一张卡片；截图预览时按下则重新截图",
+    ),
+    ("hotKey.video", "录制一小段视频"),
+    ("hotKey.combo", "组合动作，见 [combo]"),
+    (
+        "hotKey.comboDoubleTap",
+        "双击时执行组合动作的键，可以与 screenShot 相同：单击截图，双击截图并录音",
+    ),
+    (
+        "hotKey.doubleTapMs",
+        "双击的两次按下之间的最长间隔（毫秒）；同一个键的单击要等这段时间过后才触发",
+    ),
+    ("hotKey.undo", "撤销最近一次写入卡片的内容"),
+    ("hotKey.commit", "暂存模式下把暂存的内容写入最新卡片"),
+    (
+        "hotKey.lockNote",
+        "锁定当前最新的卡片，之后的内容都写入它；再按一次解除锁定",
+    ),
+    (
+        "hotKey.quit",
+        "退出程序：保存正在进行的录音、等待后台任务完成后退出",
+    ),
+    (
+        "hotKey.reloadConfig",
+        "重新读取配置文件并重新注册热键，anki 部分的修改仍需重启；保存配置文件时也会自动重新读取",
+    ),
+    (
+        "hotKey.pause",
+        "暂停或恢复所有热键，暂停时屏幕显示灰色边框；例如在游戏中打字聊天时",
+    ),
+    (
+        "hotKey.swallow",
+        "按下时不传给游戏的热键，填写上面的热键名，例如 [\"screenShot\", \"combo\"]",
+    ),
+    (
+        "hotKey.leader",
+        "前导键：leaderHotkeys 中的热键需要先按前导键，再在 leaderTimeoutMs 内按下",
+    ),
+    ("hotKey.leaderHotkeys", "需要前导键的热键，填写上面的热键名"),
+    (
+        "hotKey.leaderTimeoutMs",
+        "按下前导键后等待下一个键的时间（毫秒）",
+    ),
+    (
+        "hotKey.exact",
+        "精确匹配的热键，填写上面的热键名：按住热键以外的修饰键时不触发",
+    ),
+    (
+        "hotKey.onlyInWindows",
+        "只在前台窗口的标题或进程名包含其中之一时响应热键，不区分大小写，例如 [\"game.exe\"]；为空时在任何窗口都响应",
+    ),
+    // 截图
+    ("screenShot", "截图"),
+    ("screenShot.format", "图片格式：avif、webp、png、jxl"),
+    (
+        "screenShot.quality",
+        "编码质量 0-100，越高越清晰、体积越大；PNG 无效",
+    ),
+    ("screenShot.speed", "AVIF 编码速度 1-10，越高越快、体积越大"),
+    ("screenShot.excludeTitleBar", "截取窗口时去掉标题栏"),
+    (
+        "screenShot.delayMs",
+        "按下热键后延迟多久再截图（毫秒），留出切回游戏窗口的时间",
+    ),
+    ("screenShot.showCountdown", "延迟截图时在屏幕上显示倒计时"),
+    (
+        "screenShot.preview",
+        "截图后先显示缩略图，按确认热键保存、丢弃热键放弃，按重拍热键（hotKey.retakeScreenshot）重新截图",
+    ),
+    (
+        "screenShot.previewTimeoutMs",
+        "预览超过这段时间（毫秒）没有操作时自动保存，0 表示一直等待",
+    ),
+    (
+        "screenShot.includeCursor",
+        "截图中包含鼠标指针，适合指针位置本身就是上下文的点击类游戏",
+    ),
+    (
+        "screenShot.selectRegion",
+        "截图后先拖动框选区域，只保存选中的部分",
+    ),
+    (
+        "screenShot.target",
+        "截图对象：window 为前台窗口，monitor 为整个显示器，适合无边框全屏的游戏",
+    ),
+    (
+        "screenShot.monitor",
+        "target 为 monitor 时使用：留空为主显示器，数字为从 1 开始的序号，或显示器名称",
+    ),
+    (
+        "screenShot.targetSizeKb",
+        "目标文件大小（KB），大于 0 时自动搜索质量使文件不超过该大小；PNG 无效",
+    ),
+    (
+        "screenShot.maxWidth",
+        "编码前等比缩小到不超过该宽度，0 为不限制",
+    ),
+    (
+        "screenShot.maxHeight",
+        "编码前等比缩小到不超过该高度，0 为不限制",
+    ),
+    (
+        "screenShot.cropBlackBars",
+        "自动裁掉画面四周的黑边，在固定裁剪之前进行",
+    ),
+    (
+        "screenShot.warmSession",
+        "在游戏窗口上保持一个常驻的采集会话，截图时直接使用最近一帧，省去每次建立会话的延迟；启动时即建立，切换窗口后自动重建",
+    ),
+    (
+        "screenShot.filter",
+        "编码前的滤镜，只关心画面文字时可进一步减小文件",
+    ),
+    ("screenShot.filter.grayscale", "转为灰度图"),
+    (
+        "screenShot.filter.posterizeLevels",
+        "每个通道保留的色阶数，0 为不处理",
+    ),
+    ("screenShot.avif", "AVIF 编码器"),
+    (
+        "screenShot.avif.encoder",
+        "ravif 为进程内编码，无需外部程序；ffmpeg 调用 ffmpeg 的 libaom-av1",
+    ),
+    (
+        "screenShot.avif.ffmpegPath",
+        "encoder 为 ffmpeg 时使用，在 PATH 中时可只写文件名",
+    ),
+    (
+        "screenShot.jxl",
+        "JPEG XL 通过 libjxl 自带的 cjxl 命令行编码",
+    ),
+    (
+        "screenShot.jxl.encoderPath",
+        "cjxl 可执行文件路径，在 PATH 中时可只写文件名",
+    ),
+    (
+        "screenShot.jxl.distance",
+        "与原图的视觉距离 0-25，0 为无损，1 为视觉无损，越大体积越小",
+    ),
+    ("screenShot.jxl.effort", "编码力度 1-10，越高越慢、体积越小"),
+    (
+        "screenShot.crop",
+        "截图后固定裁剪的区域，例如去掉 HUD、小地图",
+    ),
+    (
+        "screenShot.burst",
+        "连拍一小段时间再选取一帧，避开文字淡入淡出的中间帧",
+    ),
+    (
+        "screenShot.burst.durationMs",
+        "收到第一帧后继续接收的时长（毫秒）",
+    ),
+    (
+        "screenShot.burst.pick",
+        "选取哪一帧：last 为最后一帧，sharpest 为清晰度最高的一帧",
+    ),
+    (
+        "screenShot.animation",
+        "录制一小段动图代替静态截图，输出为动画 WebP",
+    ),
+    ("screenShot.animation.frames", "最多采集的帧数"),
+    (
+        "screenShot.animation.durationMs",
+        "录制时长（毫秒），帧在其中均匀取样",
+    ),
+    (
+        "screenShot.thumbnail",
+        "正面字段放缩略图，另一个字段放完整尺寸的图（仍受 maxWidth、maxHeight 限制）",
+    ),
+    ("screenShot.thumbnail.maxWidth", "缩略图的最大宽度"),
+    ("screenShot.thumbnail.maxHeight", "缩略图的最大高度"),
+    (
+        "screenShot.thumbnail.fullFieldName",
+        "写入完整尺寸图片的字段",
+    ),
+    ("screenShot.ocr", "识别字幕区域的文字并写入句子字段"),
+    (
+        "screenShot.ocr.region",
+        "字幕所在区域，相对完整截图（不受 crop、selectRegion 影响），其中 enabled 无效",
+    ),
+    (
+        "screenShot.ocr.language",
+        "识别语言的 BCP-47 标签，例如 ja、zh-Hans；留空则使用系统的用户语言",
+    ),
+    ("screenShot.hdr", "HDR 显示器上的截图色调映射"),
+    (
+        "screenShot.hdr.mode",
+        "auto 以 16 位浮点采集，画面超出 SDR 范围时才做色调映射；off 直接以 8 位采集",
+    ),
+    (
+        "screenShot.hdr.toneMap",
+        "色调映射方式：reinhard 按亮度压缩高光，aces 对比度更高，clip 直接截断超出的高光",
+    ),
+    (
+        "screenShot.hdr.sdrWhiteNits",
+        "SDR 白色对应的亮度（尼特），与 Windows 设置中的“SDR 内容亮度”一致时效果最好",
+    ),
+    // 录音
+    (
+        "audioRecord",
+        "录音，可以写多个 [[audioRecord]]，分别使用不同的热键和设置",
+    ),
+    ("audioRecord.hotKey", "开始或停止录音"),
+    (
+        "audioRecord.mode",
+        "toggle 按一次开始录音，再按一次停止；hold 按住时录音，松开后停止并保存",
+    ),
+    ("audioRecord.swallow", "热键按下时不传给游戏"),
+    ("audioRecord.exact", "按住热键以外的修饰键时不触发"),
+    (
+        "audioRecord.source",
+        "录音来源：loopback 为默认输出设备的声音，即游戏声音；microphone 为麦克风",
+    ),
+    ("audioRecord.format", "音频格式：opus、mp3"),
+    (
+        "audioRecord.sampleRate",
+        "采样率，opus 支持 8000、12000、16000、24000、48000",
+    ),
+    (
+        "audioRecord.channels",
+        "声道：mono 下混为单声道，文件体积减半；stereo",
+    ),
+    (
+        "audioRecord.preview",
+        "录音结束后先试听，按确认热键保存、丢弃热键放弃；退出程序时不试听，直接保存",
+    ),
+    (
+        "audioRecord.previewTimeoutMs",
+        "试听超过这段时间（毫秒）没有操作时自动保存，0 表示一直等待",
+    ),
+    (
+        "audioRecord.splitOnSilence",
+        "按静音把录音切分为多段，分别保存",
+    ),
+    (
+        "audioRecord.minSilenceMs",
+        "切分时视为分句的最短静音时长（毫秒）",
+    ),
+    ("audioRecord.maxBufferMb", "录音缓冲占用的最大内存（MB）"),
+    (
+        "audioRecord.streamingEncode",
+        "边录边编码，长时间录音时占用更少内存",
+    ),
+    ("audioRecord.gainDb", "音量增益（dB），0 为不调整"),
+    (
+        "audioRecord.softLimitSecs",
+        "录音超过该时长（秒）时发出警告但不停止，0 为不警告",
+    ),
+    ("audioRecord.opus", "Opus 编码参数，format 为 opus 时使用"),
+    ("audioRecord.opus.bitrate", "码率（kbps），6-510"),
+    ("audioRecord.opus.vbr", "使用可变码率"),
+    (
+        "audioRecord.opus.complexity",
+        "编码复杂度 0-10，越高越慢、质量越好",
+    ),
+    (
+        "audioRecord.opus.application",
+        "voip 针对人声优化，适合纯对白；audio 适合音乐等一般音频；lowdelay 为低延迟",
+    ),
+    ("audioRecord.mp3", "MP3 编码参数，format 为 mp3 时使用"),
+    ("audioRecord.mp3.mode", "cbr 为固定码率，vbr 为可变码率"),
+    ("audioRecord.mp3.bitrate", "CBR 码率（kbps）"),
+    (
+        "audioRecord.mp3.quality",
+        "VBR 质量 0-9，数值越小质量越高（同 lame -V / ffmpeg -q:a）",
+    ),
+    (
+        "audioRecord.slowed",
+        "额外生成一份放慢（不变调）的录音，写入单独的字段用于听力练习",
+    ),
+    ("audioRecord.slowed.speed", "播放速度倍率"),
+    (
+        "audioRecord.intervalScreenshots",
+        "录音期间每隔一段时间截图，保存录音时一并写入同一张卡片；使用 [screenShot] 的格式、裁剪与字段设置",
+    ),
+    (
+        "audioRecord.intervalScreenshots.intervalSecs",
+        "截图间隔（秒），录音开始时立即截取第一张",
+    ),
+    (
+        "audioRecord.intervalScreenshots.attach",
+        "写入卡片的截图：first 为第一张，last 为最后一张，all 为全部",
+    ),
+    // 浓缩音频
+    (
+        "condensedAudio",
+        "运行期间持续录制游戏声音，退出时去掉静音部分导出为一个文件，用于复习听力",
+    ),
+    ("condensedAudio.format", "音频格式：opus、mp3"),
+    (
+        "condensedAudio.outputDir",
+        "导出文件所在的目录，便携模式下相对路径以程序所在目录为准",
+    ),
+    (
+        "condensedAudio.minSilenceMs",
+        "超过该时长（毫秒）的静音会被去掉",
+    ),
+    // 视频
+    (
+        "video",
+        "录制焦点窗口的一小段视频（含游戏声音），保存为 mp4",
+    ),
+    ("video.durationSecs", "录制时长（秒）"),
+    ("video.frameRate", "帧率"),
+    ("video.bitrate", "视频码率（bit/s）"),
+    ("video.recordAudio", "同时录制默认输出设备的声音"),
+    // 组合动作
+    (
+        "combo",
+        "组合热键：一次按键结束当前录音并截图，两者写入同一张卡片",
+    ),
+    ("combo.stopAudio", "结束并保存正在进行的录音"),
+    ("combo.screenshot", "截图"),
+    // 录音边框
+    ("overlay", "录音时边框上显示的状态信息"),
+    ("overlay.showTimer", "显示已录制时长"),
+    ("overlay.showClipCount", "显示本次运行已保存的录音文件数"),
+    (
+        "overlay.showLevelMeter",
+        "显示实时音量条，便于发现录错了设备",
+    ),
+    (
+        "overlay.flashOnSoftLimit",
+        "录音超过 softLimitSecs 后边框闪烁黄色",
+    ),
+    // Anki
+    ("anki", "AnkiConnect 与写入卡片的方式，修改后需要重启"),
+    (
+        "anki.ankiConnectUrl",
+        "AnkiConnect 的地址，跨机器访问时可以使用 https 地址",
+    ),
+    (
+        "anki.acceptInvalidCerts",
+        "AnkiConnect 通过 https 访问且使用自签名证书时可以开启，会跳过证书校验",
+    ),
+    (
+        "anki.caCert",
+        "额外信任的 CA 证书（PEM 文件路径），用于校验自签名证书，留空则不添加",
+    ),
+    (
+        "anki.clientCert",
+        "客户端证书（PKCS#12 文件路径），服务端要求客户端认证时填写，留空则不发送",
+    ),
+    ("anki.clientCertPassword", "客户端证书的密码"),
+    (
+        "anki.requestTimeoutSecs",
+        "单个请求的超时时间（秒），Anki 卡住时不会一直等待",
+    ),
+    (
+        "anki.retries",
+        "连接失败后的重试次数；超时只重试查询类的请求，避免新建卡片之类的请求重复执行",
+    ),
+    (
+        "anki.retryBackoffMs",
+        "第一次重试前等待的毫秒数，之后每次加倍",
+    ),
+    (
+        "anki.profile",
+        "截图、录音写入的 Anki 用户配置文件，当前不是它时先切换过去，留空则不检查",
+    ),
+    (
+        "anki.apiKey",
+        "AnkiConnect 设置了 apiKey 时填写，留空则不发送",
+    ),
+    (
+        "anki.archiveDir",
+        "写入媒体目录的文件同时保存一份到此目录下按日期分的子目录，留空则不保存；便携模式下相对路径以程序所在目录为准",
+    ),
+    (
+        "anki.fallbackDir",
+        "写入 Anki 失败且没有进入离线队列时，把截图、录音保存到此目录，留空则不保存；便携模式下相对路径以程序所在目录为准",
+    ),
+    (
+        "anki.mediaTransfer",
+        "媒体文件的存入方式：local 直接写入本机的媒体目录；upload 通过 storeMediaFile 上传，支持远程与 Android 上的 AnkiConnect",
+    ),
+    (
+        "anki.verifyMedia",
+        "存入媒体后确认 Anki 能看到该文件，再写入字段",
+    ),
+    (
+        "anki.noteQuery",
+        "查找目标卡片的 findNotes 查询，取结果中最新的卡片，例如 \"added:1 deck:Mining\" 或 \"tag:yomitan\"",
+    ),
+    (
+        "anki.targetTag",
+        "不为空时改为查找带有此标签的最新卡片（例如 Yomitan 添加的标签），忽略 noteQuery",
+    ),
+    (
+        "anki.maxNoteAgeMinutes",
+        "目标卡片须在这么多分钟内添加，避免写入昨天的卡片，0 表示不限制",
+    ),
+    (
+        "anki.noteCount",
+        "同时写入最新的几张卡片，Yomitan 有时会用同一个句子建好几张卡；新建卡片模式下无效",
+    ),
+    (
+        "anki.newNote",
+        "每次按热键新建一张卡片，而不是写入最新添加的卡片，不依赖 Yomitan 先建卡",
+    ),
+    ("anki.newNote.deckName", "新卡片所在的牌组"),
+    ("anki.newNote.modelName", "新卡片的笔记类型"),
+    (
+        "anki.newNote.fields",
+        "新卡片的字段初始内容，{source} 替换为游戏窗口标题，{time} 替换为当前时间",
+    ),
+    ("anki.newNote.tags", "新卡片的标签"),
+    (
+        "anki.duplicateMedia",
+        "卡片字段里已有相同的媒体时：ignore 不检查，warn 记录警告后照常写入，skip 跳过这份媒体",
+    ),
+    (
+        "anki.tags",
+        "写入字段后给卡片加上的标签，{game} 替换为游戏窗口标题，例如 [\"game2anki\", \"{game}\"]",
+    ),
+    (
+        "anki.browseAfterUpdate",
+        "写入字段后在 Anki 的浏览器中打开这张卡片，方便立即检查或修改句子",
+    ),
+    (
+        "anki.sourceField",
+        "每次写入卡片时一并填写来源的字段，方便追溯卡片出处，留空则不填写",
+    ),
+    (
+        "anki.sourceTemplate",
+        "来源字段的模板，{game} 替换为游戏窗口标题，{date} 替换为当前日期，{time} 替换为当前时间",
+    ),
+    (
+        "anki.protectNonEmptyFields",
+        "覆盖写入前检查字段，已有内容（例如 Yomitan 已添加的音频）时跳过这个字段；只检查句子、图片与音频字段，extraFields 总是写入，追加模式不受影响",
+    ),
+    (
+        "anki.syncAfterUpdates",
+        "每成功写入这么多次卡片后触发一次 Anki 同步，退出时也会同步，0 表示不同步",
+    ),
+    (
+        "anki.staging",
+        "暂存模式：截图、录音先保存在本地，不访问 Anki，按下提交热键时再全部写入那时的最新卡片",
+    ),
+    (
+        "anki.offlineQueue",
+        "AnkiConnect 无法连接时把写入保存到磁盘，启动时及之后定期重试；因其他原因多次失败的写入移到队列目录下的 failed 中",
+    ),
+    // 多处共用的配置项
+    ("*.enabled", "是否启用"),
+    ("*.fieldName", "写入的字段名，须与笔记类型中的字段一致"),
+    (
+        "*.fieldMode",
+        "写入方式：overwrite 覆盖字段内容，append 追加到字段现有内容之后",
+    ),
+    (
+        "*.valueTemplate",
+        "写入字段的内容模板，{file} 替换为媒体文件名，{game} 替换为游戏窗口标题，也可以写成 {{file}}、{{game}}",
+    ),
+    (
+        "*.extraFields",
+        "同时写入的其他字段，{source} 替换为游戏窗口标题，{time} 替换为采集时的时间，总是覆盖写入，例如 { Source = \"{source}\" }",
+    ),
+    (
+        "*.unit",
+        "x、y、width、height 的单位：pixel 为像素，percent 为相对截图尺寸的百分比",
+    ),
+    ("*.x", "区域左边的位置"),
+    ("*.y", "区域上边的位置"),
+    ("*.width", "区域的宽度"),
+    ("*.height", "区域的高度"),
+];
+
+/// 带说明的默认配置，每个配置项前用注释说明用途与可选值
+pub fn default_config_template() -> Result<String> {
+    let Value::Table(table) = Value::try_from(Config::default())? else {
+        return Err(anyhow!("Default config is not a table"));
+    };
+    let mut out = String::new();
+    for line in HEADER.lines() {
+        writeln!(out, "{}", format!("# {line}").trim_end())?;
+    }
+    writeln!(out)?;
+    write_table(&mut out, "", &table)?;
+    Ok(out)
+}
+
+/// 先写出普通的值，再写出子表与表数组，与 TOML 的要求一致
+fn write_table(out: &mut String, path: &str, table: &Table) -> Result<()> {
+    for (key, value) in table.iter().filter(|(_, value)| !is_section(value)) {
+        let path = join(path, key);
+        write_comment(out, &path)?;
+        writeln!(out, "{} = {value}", format_key(key))?;
+    }
+    for (key, value) in table.iter().filter(|(_, value)| is_section(value)) {
+        let path = join(path, key);
+        match value {
+            Value::Table(table) => {
+                writeln!(out)?;
+                write_comment(out, &path)?;
+                writeln!(out, "[{path}]")?;
+                write_table(out, &path, table)?;
+            }
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    writeln!(out)?;
+                    if i == 0 {
+                        write_comment(out, &path)?;
+                    }
+                    writeln!(out, "[[{path}]]")?;
+                    if let Value::Table(table) = item {
+                        write_table(out, &path, table)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// 非空的表写成 `[section]`，元素都是表的数组写成 `[[section]]`，其余写成行内的值
+fn is_section(value: &Value) -> bool {
+    match value {
+        Value::Table(table) => !table.is_empty(),
+        Value::Array(items) => !items.is_empty() && items.iter().all(Value::is_table),
+        _ => false,
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// 只由字母、数字、下划线与连字符组成的键不需要引号
+fn format_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+fn write_comment(out: &mut String, path: &str) -> Result<()> {
+    if let Some(comment) = comment(path) {
+        for line in comment.lines() {
+            writeln!(out, "# {line}")?;
+        }
+    }
+    Ok(())
+}
+
+fn comment(path: &str) -> Option<&'static str> {
+    let last = path.rsplit('.').next().unwrap_or(path);
+    let wildcard = format!("*.{last}");
+    COMMENTS
+        .iter()
+        .find(|(key, _)| *key == path)
+        .or_else(|| COMMENTS.iter().find(|(key, _)| *key == wildcard))
+        .map(|(_, comment)| *comment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 键由用户填写的表，例如字段名，只需要说明表本身
+    const FREE_FORM: &[&str] = &["anki.newNote.fields", "extraFields"];
+
+    fn missing_comments(path: &str, table: &Table, missing: &mut Vec<String>) {
+        for (key, value) in table {
+            let path = join(path, key);
+            if comment(&path).is_none() {
+                missing.push(path.clone());
+            }
+            if FREE_FORM.iter().any(|free| path == *free || key == free) {
+                continue;
+            }
+            match value {
+                Value::Table(table) => missing_comments(&path, table, missing),
+                Value::Array(items) => {
+                    for item in items {
+                        if let Value::Table(table) = item {
+                            missing_comments(&path, table, missing);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn every_default_key_has_a_comment() {
+        let Value::Table(table) = Value::try_from(Config::default()).unwrap() else {
+            panic!("Default config is not a table");
+        };
+        let mut missing = Vec::new();
+        missing_comments("", &table, &mut missing);
+        assert!(missing.is_empty(), "Missing comments: {missing:?}");
+    }
+
+    #[test]
+    fn template_parses_as_default_config() {
+        let template = default_config_template().unwrap();
+        let parsed: Config = toml::from_str(&template).unwrap();
+        assert_eq!(
+            Value::try_from(parsed).unwrap(),
+            Value::try_from(Config::default()).unwrap()
+        );
+    }
+}
@@ -1,9 +1,22 @@
-use crate::utils::keyboard::keys_from_str_de;
+use crate::utils::keyboard::{keys_from_str_de, keys_to_str_ser};
 use rdev::Key;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
-#[derive(Debug, Deserialize, Serialize, Default)]
+/// 当前的配置格式版本，旧版本的配置在读取时自动升级
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
+    /// 配置格式的版本，由程序维护
+    #[serde(rename = "version")]
+    pub version: u32,
+
+    /// 合并进来的配置片段，例如每个游戏单独的设置 `["profiles/persona5.toml"]`
+    #[serde(rename = "include")]
+    pub include: Vec<String>,
+
     #[serde(rename = "hotKey")]
     pub hot_key: HotKey,
 
@@ -11,34 +24,286 @@ pub struct Config {
     pub screen_shot: Screenshot,
 
     #[serde(rename = "audioRecord")]
-    pub audio_record: AudioRecord,
+    pub audio_record: Vec<AudioRecord>,
+
+    #[serde(rename = "condensedAudio")]
+    pub condensed_audio: CondensedAudio,
+
+    #[serde(rename = "video")]
+    pub video: Video,
+
+    #[serde(rename = "combo")]
+    pub combo: ComboAction,
+
+    #[serde(rename = "overlay")]
+    pub overlay: Overlay,
 
     #[serde(rename = "anki")]
     pub anki: Anki,
 
     #[serde(rename = "logLevel")]
     pub log_level: LogLevel,
+
+    /// 在通知区域显示托盘图标，从菜单中修改常用设置
+    #[serde(rename = "tray")]
+    pub tray: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            include: Vec::new(),
+            hot_key: HotKey::default(),
+            screen_shot: Screenshot::default(),
+            audio_record: vec![AudioRecord::default()],
+            condensed_audio: CondensedAudio::default(),
+            video: Video::default(),
+            combo: ComboAction::default(),
+            overlay: Overlay::default(),
+            anki: Anki::default(),
+            log_level: LogLevel::default(),
+            tray: true,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct HotKey {
-    #[serde(rename = "screenShot", deserialize_with = "keys_from_str_de")]
+    #[serde(
+        rename = "screenShot",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
     pub screen_shot: Vec<Key>,
 
-    #[serde(rename = "audioRecord", deserialize_with = "keys_from_str_de")]
-    pub audio_record: Vec<Key>,
+    #[serde(
+        rename = "confirm",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub confirm: Vec<Key>,
+
+    #[serde(
+        rename = "discard",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub discard: Vec<Key>,
+
+    #[serde(
+        rename = "redoAudio",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub redo_audio: Vec<Key>,
+
+    #[serde(
+        rename = "cancelAudio",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub cancel_audio: Vec<Key>,
+
+    #[serde(
+        rename = "retakeScreenshot",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub retake_screenshot: Vec<Key>,
+
+    #[serde(
+        rename = "video",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub video: Vec<Key>,
+
+    #[serde(
+        rename = "combo",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub combo: Vec<Key>,
+
+    /// 双击时执行组合动作的键，可以与 screenShot 相同：单击截图，双击截图并录音
+    #[serde(
+        rename = "comboDoubleTap",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub combo_double_tap: Vec<Key>,
+
+    /// 双击的两次按下之间的最长间隔（毫秒）；同一个键的单击要等这段时间过后才触发
+    #[serde(rename = "doubleTapMs")]
+    pub double_tap_ms: u64,
+
+    /// 撤销最近一次写入卡片的内容
+    #[serde(
+        rename = "undo",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub undo: Vec<Key>,
+
+    /// 暂存模式下把暂存的内容写入最新卡片
+    #[serde(
+        rename = "commit",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub commit: Vec<Key>,
+
+    /// 锁定当前最新的卡片，之后的内容都写入它；再按一次解除锁定
+    #[serde(
+        rename = "lockNote",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub lock_note: Vec<Key>,
+
+    /// 退出程序：保存正在进行的录音、等待后台任务完成后退出
+    #[serde(
+        rename = "quit",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub quit: Vec<Key>,
+
+    /// 重新读取配置文件并重新注册热键，anki 部分的修改仍需重启
+    #[serde(
+        rename = "reloadConfig",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub reload_config: Vec<Key>,
+
+    /// 暂停或恢复所有热键，暂停时屏幕显示灰色边框；例如在游戏中打字聊天时
+    #[serde(
+        rename = "pause",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub pause: Vec<Key>,
+
+    /// 按下时不传给游戏的热键，填写上面的热键名，例如 `["screenShot", "combo"]`
+    ///
+    /// 避免 CapsLock 切换大小写、Tab 打开游戏菜单。
+    #[serde(rename = "swallow")]
+    pub swallow: Vec<String>,
+
+    /// 前导键：leaderHotkeys 中的热键需要先按前导键，再在 leaderTimeoutMs 内按下，
+    /// 例如 `leader = "F9"`、`screenShot = "KeyS"` 时先按 F9 再按 S 截图，避免与游戏按键冲突
+    #[serde(
+        rename = "leader",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub leader: Vec<Key>,
+
+    /// 需要前导键的热键，填写上面的热键名
+    #[serde(rename = "leaderHotkeys")]
+    pub leader_hotkeys: Vec<String>,
+
+    #[serde(rename = "leaderTimeoutMs")]
+    pub leader_timeout_ms: u64,
+
+    /// 精确匹配的热键，填写上面的热键名：按住热键以外的修饰键时不触发，
+    /// 例如 Ctrl+S 在按下 Ctrl+Shift+S 时不触发
+    #[serde(rename = "exact")]
+    pub exact: Vec<String>,
+
+    /// 只在前台窗口的标题或进程名包含其中之一时响应热键，不区分大小写，例如 `["game.exe"]`；
+    /// 为空时在任何窗口都响应
+    #[serde(rename = "onlyInWindows")]
+    pub only_in_windows: Vec<String>,
 }
 
 impl Default for HotKey {
     fn default() -> Self {
         Self {
             screen_shot: vec![Key::CapsLock],
-            audio_record: vec![Key::Tab],
+            confirm: vec![Key::Return],
+            // Esc 在游戏中常用于打开菜单，预览时按下会同时传给游戏
+            discard: vec![Key::Delete],
+            redo_audio: vec![],
+            cancel_audio: vec![],
+            retake_screenshot: vec![],
+            video: vec![],
+            combo: vec![],
+            combo_double_tap: vec![],
+            double_tap_ms: 300,
+            undo: vec![],
+            commit: vec![],
+            lock_note: vec![],
+            quit: vec![],
+            reload_config: vec![],
+            pause: vec![],
+            swallow: vec![],
+            leader: vec![],
+            leader_hotkeys: vec![],
+            leader_timeout_ms: 1000,
+            exact: vec![],
+            only_in_windows: vec![],
         }
     }
 }
 
+impl HotKey {
+    /// 可以按名称引用的热键
+    pub const NAMES: &[&str] = &[
+        "screenShot",
+        "confirm",
+        "discard",
+        "redoAudio",
+        "cancelAudio",
+        "retakeScreenshot",
+        "video",
+        "combo",
+        "comboDoubleTap",
+        "undo",
+        "commit",
+        "lockNote",
+        "quit",
+        "reloadConfig",
+        "pause",
+        "leader",
+    ];
+
+    /// 按配置中的名称取热键
+    pub fn keys_by_name(&self, name: &str) -> Option<&[Key]> {
+        let keys = match name {
+            "screenShot" => &self.screen_shot,
+            "confirm" => &self.confirm,
+            "discard" => &self.discard,
+            "redoAudio" => &self.redo_audio,
+            "cancelAudio" => &self.cancel_audio,
+            "retakeScreenshot" => &self.retake_screenshot,
+            "video" => &self.video,
+            "combo" => &self.combo,
+            "comboDoubleTap" => &self.combo_double_tap,
+            "undo" => &self.undo,
+            "commit" => &self.commit,
+            "lockNote" => &self.lock_note,
+            "quit" => &self.quit,
+            "reloadConfig" => &self.reload_config,
+            "pause" => &self.pause,
+            "leader" => &self.leader,
+            _ => return None,
+        };
+        Some(keys)
+    }
+}
+
+/// 截图、录音、视频同时写入的其他字段，总是覆盖写入
+///
+/// `{source}`、`{game}` 替换为游戏窗口标题，`{time}`、`{date}` 替换为采集时的时间与日期，
+/// 写入排队或暂存时也按采集时填写。例如 `{ Source = "{source}", CaptureTime = "{time}" }`
+pub type ExtraFields = BTreeMap<String, String>;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Screenshot {
     #[serde(rename = "format")]
     pub format: ScreenshotFormat,
@@ -46,6 +311,17 @@ pub struct Screenshot {
     #[serde(rename = "fieldName")]
     pub field_name: String,
 
+    #[serde(rename = "fieldMode")]
+    pub field_mode: FieldMode,
+
+    /// 写入字段的内容模板，占位符见 [`render_media_value`](crate::anki::render_media_value)
+    #[serde(rename = "valueTemplate")]
+    pub value_template: String,
+
+    /// 同时写入的其他字段，见 [`ExtraFields`]
+    #[serde(rename = "extraFields")]
+    pub extra_fields: ExtraFields,
+
     #[serde(rename = "quality")]
     pub quality: u8,
 
@@ -54,6 +330,92 @@ pub struct Screenshot {
 
     #[serde(rename = "excludeTitleBar")]
     pub exclude_title_bar: bool,
+
+    /// 按下热键后延迟多久再截图（毫秒），留出切回游戏窗口的时间
+    #[serde(rename = "delayMs")]
+    pub delay_ms: u32,
+
+    /// 延迟截图时在屏幕上显示倒计时
+    #[serde(rename = "showCountdown")]
+    pub show_countdown: bool,
+
+    /// 截图后先显示缩略图，按确认热键保存、丢弃热键放弃，按重拍热键重新截图
+    #[serde(rename = "preview")]
+    pub preview: bool,
+
+    /// 预览超过这段时间（毫秒）没有操作时自动保存，0 表示一直等待
+    #[serde(rename = "previewTimeoutMs")]
+    pub preview_timeout_ms: u64,
+
+    /// 截图中包含鼠标指针，适合指针位置本身就是上下文的点击类游戏
+    #[serde(rename = "includeCursor")]
+    pub include_cursor: bool,
+
+    /// 截图后先拖动框选区域，只保存选中的部分
+    #[serde(rename = "selectRegion")]
+    pub select_region: bool,
+
+    #[serde(rename = "target")]
+    pub target: CaptureTarget,
+
+    /// target 为 monitor 时使用：留空为主显示器，数字为从 1 开始的序号，或显示器名称
+    #[serde(rename = "monitor")]
+    pub monitor: String,
+
+    /// 编码前的滤镜，只关心画面文字时可进一步减小文件
+    #[serde(rename = "filter")]
+    pub filter: ImageFilter,
+
+    /// 目标文件大小（KB），大于 0 时自动搜索质量使文件不超过该大小；PNG 无效
+    #[serde(rename = "targetSizeKb")]
+    pub target_size_kb: u32,
+
+    #[serde(rename = "avif")]
+    pub avif: AvifEncodeConfig,
+
+    #[serde(rename = "jxl")]
+    pub jxl: JxlEncodeConfig,
+
+    /// 编码前等比缩小到不超过该宽度，0 为不限制
+    #[serde(rename = "maxWidth")]
+    pub max_width: u32,
+
+    /// 编码前等比缩小到不超过该高度，0 为不限制
+    #[serde(rename = "maxHeight")]
+    pub max_height: u32,
+
+    /// 自动裁掉画面四周的黑边，在固定裁剪之前进行
+    #[serde(rename = "cropBlackBars")]
+    pub crop_black_bars: bool,
+
+    /// 截图后固定裁剪的区域，例如去掉 HUD、小地图
+    #[serde(rename = "crop")]
+    pub crop: CropRegion,
+
+    /// 连拍一小段时间再选取一帧，避开文字淡入淡出的中间帧
+    #[serde(rename = "burst")]
+    pub burst: BurstCapture,
+
+    /// 录制一小段动图代替静态截图，输出为动画 WebP
+    #[serde(rename = "animation")]
+    pub animation: AnimatedCapture,
+
+    /// 正面字段放缩略图，另一个字段放完整尺寸的图
+    #[serde(rename = "thumbnail")]
+    pub thumbnail: ThumbnailCopy,
+
+    /// 在游戏窗口上保持一个常驻的采集会话，截图时直接使用最近一帧，
+    /// 省去每次建立会话的延迟；启动时即在后台建立，窗口或显示器变化时自动重建
+    #[serde(rename = "warmSession")]
+    pub warm_session: bool,
+
+    /// 识别字幕区域的文字并写入句子字段
+    #[serde(rename = "ocr")]
+    pub ocr: SubtitleOcr,
+
+    /// HDR 显示器上的截图色调映射
+    #[serde(rename = "hdr")]
+    pub hdr: HdrCapture,
 }
 
 impl Default for Screenshot {
@@ -61,49 +423,920 @@ impl Default for Screenshot {
         Self {
             format: ScreenshotFormat::Avif,
             field_name: "Picture".to_string(),
+            field_mode: FieldMode::Overwrite,
+            value_template: "<img src=\"{file}\">".to_string(),
+            extra_fields: BTreeMap::new(),
             quality: 60,
             speed: 6,
             exclude_title_bar: true,
+            delay_ms: 0,
+            show_countdown: true,
+            preview: false,
+            preview_timeout_ms: 5000,
+            include_cursor: false,
+            select_region: false,
+            target: CaptureTarget::Window,
+            monitor: String::new(),
+            filter: ImageFilter::default(),
+            target_size_kb: 0,
+            avif: AvifEncodeConfig::default(),
+            jxl: JxlEncodeConfig::default(),
+            max_width: 0,
+            max_height: 0,
+            crop_black_bars: false,
+            crop: CropRegion::default(),
+            burst: BurstCapture::default(),
+            animation: AnimatedCapture::default(),
+            thumbnail: ThumbnailCopy::default(),
+            warm_session: false,
+            ocr: SubtitleOcr::default(),
+            hdr: HdrCapture::default(),
         }
     }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AnimatedCapture {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    /// 最多采集的帧数
+    #[serde(rename = "frames")]
+    pub frames: u32,
+
+    /// 录制时长（毫秒），帧在其中均匀取样
+    #[serde(rename = "durationMs")]
+    pub duration_ms: u32,
+}
+
+impl Default for AnimatedCapture {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frames: 20,
+            duration_ms: 2000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BurstCapture {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    /// 收到第一帧后继续接收的时长（毫秒）
+    #[serde(rename = "durationMs")]
+    pub duration_ms: u32,
+
+    #[serde(rename = "pick")]
+    pub pick: BurstPick,
+}
+
+impl Default for BurstCapture {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration_ms: 300,
+            pick: BurstPick::Last,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum BurstPick {
+    #[serde(rename = "last")]
+    Last, // 最后一帧
+    #[serde(rename = "sharpest")]
+    Sharpest, // 清晰度最高的一帧
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CropRegion {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    /// x、y、width、height 的单位
+    #[serde(rename = "unit")]
+    pub unit: CropUnit,
+
+    #[serde(rename = "x")]
+    pub x: f32,
+
+    #[serde(rename = "y")]
+    pub y: f32,
+
+    #[serde(rename = "width")]
+    pub width: f32,
+
+    #[serde(rename = "height")]
+    pub height: f32,
+}
+
+impl Default for CropRegion {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            unit: CropUnit::Percent,
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ThumbnailCopy {
+    /// 启用后 fieldName 写入缩略图，完整尺寸的图（仍受 maxWidth、maxHeight 限制）写入 fullFieldName
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    #[serde(rename = "maxWidth")]
+    pub max_width: u32,
+
+    #[serde(rename = "maxHeight")]
+    pub max_height: u32,
+
+    #[serde(rename = "fullFieldName")]
+    pub full_field_name: String,
+}
+
+impl Default for ThumbnailCopy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_width: 640,
+            max_height: 360,
+            full_field_name: "PictureFull".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SubtitleOcr {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    /// 字幕所在区域，相对完整截图（不受 crop、selectRegion 影响），其中 enabled 无效
+    #[serde(rename = "region")]
+    pub region: CropRegion,
+
+    #[serde(rename = "fieldName")]
+    pub field_name: String,
+
+    #[serde(rename = "fieldMode")]
+    pub field_mode: FieldMode,
+
+    /// 识别语言的 BCP-47 标签，例如 ja、zh-Hans；留空则使用系统的用户语言
+    #[serde(rename = "language")]
+    pub language: String,
+}
+
+impl Default for SubtitleOcr {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            region: CropRegion {
+                enabled: true,
+                unit: CropUnit::Percent,
+                x: 0.0,
+                y: 70.0,
+                width: 100.0,
+                height: 30.0,
+            },
+            field_name: "Sentence".to_string(),
+            field_mode: FieldMode::Overwrite,
+            language: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum CropUnit {
+    #[serde(rename = "pixel")]
+    Pixel,
+    #[serde(rename = "percent")]
+    Percent, // 相对截图尺寸的百分比
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ImageFilter {
+    #[serde(rename = "grayscale")]
+    pub grayscale: bool,
+
+    /// 每个通道保留的色阶数，0 为不处理
+    #[serde(rename = "posterizeLevels")]
+    pub posterize_levels: u8,
+}
+
+/// AVIF 默认用 ravif 在进程内编码，也可以改用 ffmpeg 的 libaom
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AvifEncodeConfig {
+    #[serde(rename = "encoder")]
+    pub encoder: AvifEncoder,
+
+    /// encoder 为 ffmpeg 时使用，在 PATH 中时可只写文件名
+    #[serde(rename = "ffmpegPath")]
+    pub ffmpeg_path: String,
+}
+
+impl Default for AvifEncodeConfig {
+    fn default() -> Self {
+        Self {
+            encoder: AvifEncoder::Ravif,
+            ffmpeg_path: "ffmpeg".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum AvifEncoder {
+    #[serde(rename = "ravif")]
+    Ravif, // 进程内 rav1e 编码，无需外部程序
+    #[serde(rename = "ffmpeg")]
+    Ffmpeg, // 调用 ffmpeg 的 libaom-av1
+}
+
+/// JPEG XL 通过 libjxl 自带的 cjxl 命令行编码
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JxlEncodeConfig {
+    /// cjxl 可执行文件路径，在 PATH 中时可只写文件名
+    #[serde(rename = "encoderPath")]
+    pub encoder_path: String,
+
+    /// 与原图的视觉距离，0 为无损，1 为视觉无损，越大体积越小
+    #[serde(rename = "distance")]
+    pub distance: f32,
+
+    /// 编码力度 1-10，越高越慢、体积越小
+    #[serde(rename = "effort")]
+    pub effort: u8,
+}
+
+impl Default for JxlEncodeConfig {
+    fn default() -> Self {
+        Self {
+            encoder_path: "cjxl".to_string(),
+            distance: 1.0,
+            effort: 7,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HdrCapture {
+    #[serde(rename = "mode")]
+    pub mode: HdrMode,
+
+    #[serde(rename = "toneMap")]
+    pub tone_map: ToneMapOperator,
+
+    /// SDR 白色对应的亮度（尼特），与 Windows 设置中的“SDR 内容亮度”一致时效果最好
+    #[serde(rename = "sdrWhiteNits")]
+    pub sdr_white_nits: f32,
+}
+
+impl Default for HdrCapture {
+    fn default() -> Self {
+        Self {
+            mode: HdrMode::Auto,
+            tone_map: ToneMapOperator::Reinhard,
+            sdr_white_nits: 200.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum HdrMode {
+    #[serde(rename = "auto")]
+    Auto, // 以 16 位浮点采集，画面超出 SDR 范围时才做色调映射
+    #[serde(rename = "off")]
+    Off, // 直接以 8 位采集
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ToneMapOperator {
+    #[serde(rename = "reinhard")]
+    Reinhard, // 按亮度压缩高光，色相不变
+    #[serde(rename = "aces")]
+    Aces, // 电影感曲线，对比度更高
+    #[serde(rename = "clip")]
+    Clip, // 直接截断超出的高光
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum CaptureTarget {
+    #[serde(rename = "window")]
+    Window, // 前台窗口
+    #[serde(rename = "monitor")]
+    Monitor, // 整个显示器，适合无边框全屏的游戏
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct AudioRecord {
+    #[serde(
+        rename = "hotKey",
+        deserialize_with = "keys_from_str_de",
+        serialize_with = "keys_to_str_ser"
+    )]
+    pub hot_key: Vec<Key>,
+
+    #[serde(rename = "mode")]
+    pub mode: HotKeyMode,
+
+    /// 热键按下时不传给游戏
+    #[serde(rename = "swallow")]
+    pub swallow: bool,
+
+    /// 按住热键以外的修饰键时不触发
+    #[serde(rename = "exact")]
+    pub exact: bool,
+
+    #[serde(rename = "source")]
+    pub source: AudioSource,
+
     #[serde(rename = "format")]
     pub format: AudioFormat,
 
     #[serde(rename = "fieldName")]
     pub field_name: String,
 
+    #[serde(rename = "fieldMode")]
+    pub field_mode: FieldMode,
+
+    /// 写入字段的内容模板，占位符见 [`render_media_value`](crate::anki::render_media_value)
+    #[serde(rename = "valueTemplate")]
+    pub value_template: String,
+
+    /// 同时写入的其他字段，见 [`ExtraFields`]
+    #[serde(rename = "extraFields")]
+    pub extra_fields: ExtraFields,
+
     #[serde(rename = "sampleRate")]
     pub sample_rate: u32,
+
+    #[serde(rename = "channels")]
+    pub channels: AudioChannels,
+
+    #[serde(rename = "preview")]
+    pub preview: bool,
+
+    /// 试听超过这段时间（毫秒）没有操作时自动保存，0 表示一直等待
+    #[serde(rename = "previewTimeoutMs")]
+    pub preview_timeout_ms: u64,
+
+    #[serde(rename = "splitOnSilence")]
+    pub split_on_silence: bool,
+
+    #[serde(rename = "minSilenceMs")]
+    pub min_silence_ms: u32,
+
+    #[serde(rename = "maxBufferMb")]
+    pub max_buffer_mb: usize,
+
+    #[serde(rename = "streamingEncode")]
+    pub streaming_encode: bool,
+
+    #[serde(rename = "gainDb")]
+    pub gain_db: f32,
+
+    #[serde(rename = "opus")]
+    pub opus: OpusEncodeConfig,
+
+    #[serde(rename = "mp3")]
+    pub mp3: Mp3EncodeConfig,
+
+    #[serde(rename = "slowed")]
+    pub slowed: SlowedCopy,
+
+    /// 录音超过该时长（秒）时发出警告但不停止，0 为不警告
+    #[serde(rename = "softLimitSecs")]
+    pub soft_limit_secs: u32,
+
+    #[serde(rename = "intervalScreenshots")]
+    pub interval_screenshots: IntervalScreenshots,
 }
 
 impl Default for AudioRecord {
     fn default() -> Self {
         Self {
+            hot_key: vec![Key::Tab],
+            mode: HotKeyMode::Toggle,
+            swallow: false,
+            exact: false,
+            source: AudioSource::Loopback,
             format: AudioFormat::Opus,
             field_name: "SentenceAudio".to_string(),
+            field_mode: FieldMode::Overwrite,
+            value_template: "[sound:{file}]".to_string(),
+            extra_fields: BTreeMap::new(),
             sample_rate: 48000,
+            channels: AudioChannels::Stereo,
+            preview: false,
+            preview_timeout_ms: 5000,
+            split_on_silence: false,
+            min_silence_ms: 700,
+            max_buffer_mb: 256,
+            streaming_encode: false,
+            gain_db: 0.0,
+            opus: OpusEncodeConfig::default(),
+            mp3: Mp3EncodeConfig::default(),
+            slowed: SlowedCopy::default(),
+            soft_limit_secs: 60,
+            interval_screenshots: IntervalScreenshots::default(),
+        }
+    }
+}
+
+/// 额外生成一份放慢（不变调）的录音，写入单独的字段用于听力练习
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SlowedCopy {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    /// 播放速度倍率
+    #[serde(rename = "speed")]
+    pub speed: f32,
+
+    #[serde(rename = "fieldName")]
+    pub field_name: String,
+}
+
+impl Default for SlowedCopy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            speed: 0.8,
+            field_name: "SentenceAudioSlow".to_string(),
+        }
+    }
+}
+
+/// 录音期间每隔一段时间截图，保存录音时一并写入同一张卡片
+///
+/// 截图使用 `[screenShot]` 的格式、裁剪与字段设置，不经延迟、框选和预览。
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IntervalScreenshots {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    /// 截图间隔（秒），录音开始时立即截取第一张
+    #[serde(rename = "intervalSecs")]
+    pub interval_secs: u32,
+
+    #[serde(rename = "attach")]
+    pub attach: ScreenshotAttach,
+}
+
+impl Default for IntervalScreenshots {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 5,
+            attach: ScreenshotAttach::First,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ScreenshotAttach {
+    #[serde(rename = "first")]
+    First, // 第一张
+    #[serde(rename = "last")]
+    Last, // 最后一张
+    #[serde(rename = "all")]
+    All, // 全部
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OpusEncodeConfig {
+    /// 码率（kbps）
+    #[serde(rename = "bitrate")]
+    pub bitrate: u32,
+
+    #[serde(rename = "vbr")]
+    pub vbr: bool,
+
+    /// 编码复杂度 0-10，越高越慢、质量越好
+    #[serde(rename = "complexity")]
+    pub complexity: u8,
+
+    #[serde(rename = "application")]
+    pub application: OpusApplication,
+}
+
+impl Default for OpusEncodeConfig {
+    fn default() -> Self {
+        Self {
+            bitrate: 128,
+            vbr: true,
+            complexity: 10,
+            application: OpusApplication::Audio,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Mp3EncodeConfig {
+    #[serde(rename = "mode")]
+    pub mode: Mp3Mode,
+
+    /// CBR 码率（kbps）
+    #[serde(rename = "bitrate")]
+    pub bitrate: u32,
+
+    /// VBR 质量 0-9，数值越小质量越高（同 lame -V / ffmpeg -q:a）
+    #[serde(rename = "quality")]
+    pub quality: u8,
+}
+
+impl Default for Mp3EncodeConfig {
+    fn default() -> Self {
+        Self {
+            mode: Mp3Mode::Cbr,
+            bitrate: 192,
+            quality: 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Mp3Mode {
+    #[serde(rename = "cbr")]
+    Cbr,
+    #[serde(rename = "vbr")]
+    Vbr,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum OpusApplication {
+    #[serde(rename = "voip")]
+    Voip, // 针对人声优化，适合纯对白
+    #[serde(rename = "audio")]
+    Audio,
+    #[serde(rename = "lowdelay")]
+    LowDelay,
+}
+
+/// 录音时边框上显示的状态信息
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Overlay {
+    /// 显示已录制时长
+    #[serde(rename = "showTimer")]
+    pub show_timer: bool,
+
+    /// 显示本次运行已保存的录音文件数
+    #[serde(rename = "showClipCount")]
+    pub show_clip_count: bool,
+
+    /// 显示实时音量条，便于发现录错了设备
+    #[serde(rename = "showLevelMeter")]
+    pub show_level_meter: bool,
+
+    /// 录音超过 softLimitSecs 后边框闪烁黄色
+    #[serde(rename = "flashOnSoftLimit")]
+    pub flash_on_soft_limit: bool,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self {
+            show_timer: true,
+            show_clip_count: false,
+            show_level_meter: true,
+            flash_on_soft_limit: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CondensedAudio {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    #[serde(rename = "format")]
+    pub format: AudioFormat,
+
+    #[serde(rename = "outputDir")]
+    pub output_dir: String,
+
+    #[serde(rename = "minSilenceMs")]
+    pub min_silence_ms: u32,
+}
+
+impl Default for CondensedAudio {
+    fn default() -> Self {
+        // 便携模式下默认放在程序所在目录
+        let output_dir = match super::portable_directory() {
+            Some(_) => "condensed".to_string(),
+            None => directories::UserDirs::new()
+                .map(|dirs| dirs.audio_dir().unwrap_or(dirs.home_dir()).to_path_buf())
+                .unwrap_or_default()
+                .join("Game2Anki")
+                .to_string_lossy()
+                .into_owned(),
+        };
+        Self {
+            enabled: false,
+            format: AudioFormat::Opus,
+            output_dir,
+            min_silence_ms: 1000,
+        }
+    }
+}
+
+/// 录制焦点窗口的一小段视频（含游戏声音），保存为 mp4
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Video {
+    #[serde(rename = "fieldName")]
+    pub field_name: String,
+
+    #[serde(rename = "fieldMode")]
+    pub field_mode: FieldMode,
+
+    /// 写入字段的内容模板，占位符见 [`render_media_value`](crate::anki::render_media_value)
+    #[serde(rename = "valueTemplate")]
+    pub value_template: String,
+
+    /// 同时写入的其他字段，见 [`ExtraFields`]
+    #[serde(rename = "extraFields")]
+    pub extra_fields: ExtraFields,
+
+    /// 录制时长（秒）
+    #[serde(rename = "durationSecs")]
+    pub duration_secs: u32,
+
+    #[serde(rename = "frameRate")]
+    pub frame_rate: u32,
+
+    /// 视频码率（bit/s）
+    #[serde(rename = "bitrate")]
+    pub bitrate: u32,
+
+    /// 同时录制默认输出设备的声音
+    #[serde(rename = "recordAudio")]
+    pub record_audio: bool,
+}
+
+impl Default for Video {
+    fn default() -> Self {
+        Self {
+            field_name: "SentenceVideo".to_string(),
+            field_mode: FieldMode::Overwrite,
+            value_template: "[sound:{file}]".to_string(),
+            extra_fields: BTreeMap::new(),
+            duration_secs: 5,
+            frame_rate: 30,
+            bitrate: 4_000_000,
+            record_audio: true,
+        }
+    }
+}
+
+/// 组合热键：一次按键结束当前录音并截图，两者写入同一张卡片
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ComboAction {
+    /// 结束并保存正在进行的录音
+    #[serde(rename = "stopAudio")]
+    pub stop_audio: bool,
+
+    #[serde(rename = "screenshot")]
+    pub screenshot: bool,
+}
+
+impl Default for ComboAction {
+    fn default() -> Self {
+        Self {
+            stop_audio: true,
+            screenshot: true,
         }
     }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Anki {
+    /// 跨机器访问时可以使用 https 地址
     #[serde(rename = "ankiConnectUrl")]
     pub anki_connect_url: String,
+
+    /// AnkiConnect 通过 https 访问且使用自签名证书时可以开启，会跳过证书校验
+    #[serde(rename = "acceptInvalidCerts")]
+    pub accept_invalid_certs: bool,
+
+    /// 额外信任的 CA 证书（PEM 文件路径），用于校验自签名证书，留空则不添加
+    #[serde(rename = "caCert")]
+    pub ca_cert: String,
+
+    /// 客户端证书（PKCS#12 文件路径），服务端要求客户端认证时填写，留空则不发送
+    #[serde(rename = "clientCert")]
+    pub client_cert: String,
+
+    #[serde(rename = "clientCertPassword")]
+    pub client_cert_password: String,
+
+    /// 单个请求的超时时间（秒），Anki 卡住时不会一直等待
+    #[serde(rename = "requestTimeoutSecs")]
+    pub request_timeout_secs: u64,
+
+    /// 连接失败后的重试次数；超时只重试查询类的请求，避免新建卡片之类的请求重复执行
+    #[serde(rename = "retries")]
+    pub retries: u32,
+
+    /// 第一次重试前等待的毫秒数，之后每次加倍
+    #[serde(rename = "retryBackoffMs")]
+    pub retry_backoff_ms: u64,
+
+    /// 截图、录音写入的 Anki 用户配置文件，当前不是它时先切换过去，留空则不检查
+    #[serde(rename = "profile")]
+    pub profile: String,
+
+    /// AnkiConnect 设置了 apiKey 时填写，留空则不发送
+    #[serde(rename = "apiKey")]
+    pub api_key: String,
+
+    /// 写入媒体目录的文件同时保存一份到此目录下按日期分的子目录，留空则不保存
+    #[serde(rename = "archiveDir")]
+    pub archive_dir: String,
+
+    /// 写入 Anki 失败且没有进入离线队列时，把截图、录音保存到此目录，留空则不保存
+    #[serde(rename = "fallbackDir")]
+    pub fallback_dir: String,
+
+    /// 媒体文件的存入方式，Anki 不在本机时使用 upload
+    #[serde(rename = "mediaTransfer")]
+    pub media_transfer: MediaTransfer,
+
+    /// 存入媒体后通过 getMediaFilesNames 确认 Anki 能看到该文件，再写入字段
+    #[serde(rename = "verifyMedia")]
+    pub verify_media: bool,
+
+    /// 查找目标卡片的 findNotes 查询，取结果中最新的卡片，例如 `added:1 deck:Mining` 或 `tag:yomitan`
+    #[serde(rename = "noteQuery")]
+    pub note_query: String,
+
+    /// 不为空时改为查找带有此标签的最新卡片（例如 Yomitan 添加的标签），忽略 noteQuery，
+    /// 避免当天在其他牌组添加的卡片被写入
+    #[serde(rename = "targetTag")]
+    pub target_tag: String,
+
+    /// 目标卡片须在这么多分钟内添加，避免写入昨天的卡片，0 表示不限制
+    #[serde(rename = "maxNoteAgeMinutes")]
+    pub max_note_age_minutes: u64,
+
+    /// 同时写入最新的几张卡片，Yomitan 有时会用同一个句子建好几张卡；新建卡片模式下无效
+    #[serde(rename = "noteCount")]
+    pub note_count: usize,
+
+    #[serde(rename = "newNote")]
+    pub new_note: NewNote,
+
+    /// 卡片字段里已有本次运行写入的媒体，或内容完全相同的媒体已经存入过时的处理方式
+    #[serde(rename = "duplicateMedia")]
+    pub duplicate_media: DuplicateAction,
+
+    /// 写入字段后给卡片加上的标签，`{game}` 替换为游戏窗口标题，例如 `["game2anki", "{game}"]`
+    #[serde(rename = "tags")]
+    pub tags: Vec<String>,
+
+    /// 写入字段后在 Anki 的浏览器中打开这张卡片，方便立即检查或修改句子
+    #[serde(rename = "browseAfterUpdate")]
+    pub browse_after_update: bool,
+
+    /// 每次写入卡片时一并填写来源的字段，方便追溯卡片出处，留空则不填写
+    #[serde(rename = "sourceField")]
+    pub source_field: String,
+
+    /// 来源字段的模板，`{game}` 替换为游戏窗口标题，`{date}` 替换为当前日期，`{time}` 替换为当前时间
+    #[serde(rename = "sourceTemplate")]
+    pub source_template: String,
+
+    /// 覆盖写入前检查字段，已有内容（例如 Yomitan 已添加的音频）时跳过这个字段；
+    /// 只检查句子、图片与音频字段，extraFields 总是写入，追加模式不受影响
+    #[serde(rename = "protectNonEmptyFields")]
+    pub protect_non_empty_fields: bool,
+
+    /// 每成功写入这么多次卡片后触发一次 Anki 同步，退出时也会同步，0 表示不同步
+    #[serde(rename = "syncAfterUpdates")]
+    pub sync_after_updates: u32,
+
+    /// 暂存模式：截图、录音先保存在本地，不访问 Anki，按下提交热键时再全部写入那时的最新卡片
+    ///
+    /// 适合 Yomitan 建卡晚于截图、录音的情况。
+    #[serde(rename = "staging")]
+    pub staging: bool,
+
+    /// AnkiConnect 无法连接时把写入保存到磁盘，启动时及之后定期重试；因其他原因多次失败的写入移到队列目录下的 failed 中
+    #[serde(rename = "offlineQueue")]
+    pub offline_queue: bool,
 }
 
 impl Default for Anki {
     fn default() -> Self {
         Self {
             anki_connect_url: "http://127.0.0.1:8765".to_string(),
+            accept_invalid_certs: false,
+            ca_cert: String::new(),
+            client_cert: String::new(),
+            client_cert_password: String::new(),
+            request_timeout_secs: 30,
+            retries: 2,
+            retry_backoff_ms: 500,
+            profile: String::new(),
+            api_key: String::new(),
+            archive_dir: String::new(),
+            fallback_dir: String::new(),
+            media_transfer: MediaTransfer::Local,
+            verify_media: true,
+            note_query: "added:1".to_string(),
+            target_tag: String::new(),
+            max_note_age_minutes: 0,
+            note_count: 1,
+            new_note: NewNote::default(),
+            duplicate_media: DuplicateAction::Warn,
+            tags: vec![],
+            browse_after_update: false,
+            source_field: String::new(),
+            source_template: "{game} — {date}".to_string(),
+            protect_non_empty_fields: false,
+            sync_after_updates: 0,
+            staging: false,
+            offline_queue: true,
         }
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum DuplicateAction {
+    #[serde(rename = "ignore")]
+    Ignore, // 不检查
+    #[serde(rename = "warn")]
+    Warn, // 记录警告后照常写入
+    #[serde(rename = "skip")]
+    Skip, // 跳过这份媒体
+}
+
+/// 每次按热键新建一张卡片，而不是写入最新添加的卡片，不依赖 Yomitan 先建卡
+///
+/// 截图、录音等仍写入各自配置的字段，这些字段需要在笔记类型中存在。
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NewNote {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    #[serde(rename = "deckName")]
+    pub deck_name: String,
+
+    #[serde(rename = "modelName")]
+    pub model_name: String,
+
+    /// 新卡片的字段初始内容，`{source}` 替换为游戏窗口标题，`{time}` 替换为当前时间
+    #[serde(rename = "fields")]
+    pub fields: BTreeMap<String, String>,
+
+    #[serde(rename = "tags")]
+    pub tags: Vec<String>,
+}
+
+impl Default for NewNote {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            deck_name: "Default".to_string(),
+            model_name: "Basic".to_string(),
+            fields: BTreeMap::from([("Front".to_string(), "{source}".to_string())]),
+            tags: vec!["game2anki".to_string()],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum MediaTransfer {
+    #[serde(rename = "local")]
+    Local, // 直接写入本机的媒体目录
+    #[serde(rename = "upload")]
+    Upload, // 通过 storeMediaFile 上传，支持远程与 Android 上的 AnkiConnect
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum AudioFormat {
     #[serde(rename = "opus")]
@@ -112,6 +1345,38 @@ pub enum AudioFormat {
     Mp3,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum FieldMode {
+    #[serde(rename = "overwrite")]
+    Overwrite,
+    #[serde(rename = "append")]
+    Append, // 追加到字段现有内容之后
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum HotKeyMode {
+    #[serde(rename = "toggle")]
+    Toggle, // 按一次开始录音，再按一次停止
+    #[serde(rename = "hold")]
+    Hold, // 按住时录音，松开后停止并保存
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum AudioSource {
+    #[serde(rename = "loopback")]
+    Loopback, // 默认输出设备的环回，即游戏声音
+    #[serde(rename = "microphone")]
+    Microphone,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum AudioChannels {
+    #[serde(rename = "mono")]
+    Mono, // 下混为单声道，文件体积减半
+    #[serde(rename = "stereo")]
+    Stereo,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum ScreenshotFormat {
     #[serde(rename = "avif")]
@@ -120,6 +1385,8 @@ pub enum ScreenshotFormat {
     Webp,
     #[serde(rename = "png")]
     Png,
+    #[serde(rename = "jxl")]
+    Jxl,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -143,11 +1410,12 @@ impl fmt::Display for ScreenshotFormat {
             ScreenshotFormat::Avif => write!(f, "avif"),
             ScreenshotFormat::Webp => write!(f, "webp"),
             ScreenshotFormat::Png => write!(f, "png"),
+            ScreenshotFormat::Jxl => write!(f, "jxl"),
         }
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, clap::ValueEnum)]
 pub enum LogLevel {
     #[serde(rename = "trace")]
     Trace,
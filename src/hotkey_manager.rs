@@ -1,23 +1,170 @@
+use crate::utils::window::foreground_window_matches;
 use once_cell::sync::Lazy;
-use rdev::{EventType, Key, listen};
+use rdev::{EventType, Key, grab, listen};
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{
     Arc, Mutex,
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    mpsc,
 };
 use std::thread;
+use std::time::{Duration, Instant};
+use windows::Win32::Foundation::{LPARAM, WPARAM};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+use windows::Win32::UI::WindowsAndMessaging::{PostThreadMessageW, WM_QUIT};
 
 // 全局热键管理器，支持多热键注册
 pub struct HotKeyManager;
 
 // 全局注册表，支持组合键
 type HotkeyCallback = Box<dyn Fn() + Send + 'static>;
-type HotkeyMap = HashMap<KeyCombo, Vec<HotkeyCallback>>;
+type HotkeyMap = HashMap<KeyCombo, Vec<(HotkeyId, HotkeyCallback)>>;
 static HOTKEY_REGISTRY: Lazy<Arc<Mutex<HotkeyMap>>> =
     Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+// 组合键松开时的回调
+static RELEASE_REGISTRY: Lazy<Arc<Mutex<HotkeyMap>>> =
+    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+// 组合键双击时的回调
+static DOUBLE_TAP_REGISTRY: Lazy<Arc<Mutex<HotkeyMap>>> =
+    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+
+// 双击的两次按下之间的最长间隔（毫秒）
+static DOUBLE_TAP_WINDOW_MS: AtomicU64 = AtomicU64::new(300);
+// 等待第二次按下的组合键：第一次按下的时间与编号，超时后按单击处理
+type PendingTaps = HashMap<KeyCombo, (Instant, u64)>;
+static PENDING_TAPS: Lazy<Mutex<PendingTaps>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static NEXT_TAP: AtomicU64 = AtomicU64::new(0);
+// 按下时不传给其他程序的组合键
+static SWALLOWED_COMBOS: Lazy<Mutex<HashSet<KeyCombo>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+// 只在没有按住其他修饰键时才触发的组合键
+static EXACT_COMBOS: Lazy<Mutex<HashSet<KeyCombo>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+// 暂停时只有不受暂停影响的热键会触发
+static PAUSED: AtomicBool = AtomicBool::new(false);
+static PAUSE_EXEMPT: Lazy<Mutex<HashSet<HotkeyId>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+// 只在前台窗口的标题或进程名匹配时触发，为空时不限制
+static FOCUS_PATTERNS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+// 前台窗口是否匹配，由看门狗线程定期更新，钩子中只读取，不在钩子里查询窗口
+static FOCUSED: AtomicBool = AtomicBool::new(true);
+// 前导键设置
+static LEADER: Mutex<Option<Leader>> = Mutex::new(None);
+// 等待录制的下一个组合键，录制时这次按键不触发热键
+type CaptureCallback = Box<dyn FnOnce(Option<Vec<Key>>) + Send + 'static>;
+static CAPTURE: Mutex<Option<CaptureCallback>> = Mutex::new(None);
+
+/// 前导键：`combos` 中的组合键只在前导键之后的一段时间内按下才触发，例如先按 F9 再按 S
+#[derive(Clone)]
+struct Leader {
+    keys: KeyCombo,
+    combos: HashSet<KeyCombo>,
+    timeout: Duration,
+}
 
 // 监听器启动状态
+// 监听线程退出后重新启动前的等待时间，避免持续失败时空转
+const LISTENER_RESTART_DELAY: Duration = Duration::from_secs(1);
 static LISTENER_STARTED: AtomicBool = AtomicBool::new(false);
+// 看门狗检查的间隔，同时刷新前台窗口是否匹配
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+// 系统连续这么多次检查都有新的输入、钩子却没有收到事件时，认为钩子已被系统移除
+// （处理超过 LowLevelHooksTimeout 时 Windows 会静默移除钩子），重新安装
+const HOOK_MISSES: u32 = 8;
+// AltGr 附带的左 Ctrl 与 AltGr 几乎同时到达，间隔在此之内时视为模拟的按下
+const FAKE_CTRL_WINDOW: Duration = Duration::from_millis(20);
+// 钩子收到的事件数，包括鼠标事件
+static HOOK_EVENTS: AtomicU64 = AtomicU64::new(0);
+// 监听线程的编号，看门狗向它发送 WM_QUIT 使其退出后重新安装钩子
+static LISTENER_THREAD: AtomicU32 = AtomicU32::new(0);
+// 热键回调在分发线程中执行，钩子线程只做按键匹配，尽快返回；附带执行的时间
+static DISPATCHER: Lazy<mpsc::Sender<(Instant, Dispatch)>> = Lazy::new(|| {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || Dispatch::run_queue(rx));
+    tx
+});
+static NEXT_HOTKEY_ID: AtomicU64 = AtomicU64::new(0);
+
+/// 交给分发线程执行的回调
+enum Dispatch {
+    /// 触发注册表中组合键的回调，受前台窗口与暂停的限制
+    Trigger(&'static Mutex<HotkeyMap>, KeyCombo),
+    /// 组合键松开时的回调
+    Release(KeyCombo),
+    /// 双击间隔结束后仍没有第二次按下时按单击触发，附带第一次按下的编号
+    SingleTap(KeyCombo, u64),
+}
+
+impl Dispatch {
+    fn send(self) {
+        self.send_at(Instant::now());
+    }
+
+    /// 到 `at` 时再执行，所有推迟的回调共用分发线程计时
+    fn send_at(self, at: Instant) {
+        if DISPATCHER.send((at, self)).is_err() {
+            log::error!("Hotkey dispatcher stopped");
+        }
+    }
+
+    fn run_queue(rx: mpsc::Receiver<(Instant, Dispatch)>) {
+        // 尚未到时间的回调
+        let mut later: Vec<(Instant, Dispatch)> = Vec::new();
+        loop {
+            let received = match later.iter().map(|(at, _)| *at).min() {
+                Some(at) => rx.recv_timeout(at.saturating_duration_since(Instant::now())),
+                None => rx.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(entry) => later.push(entry),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+            // 按收到的顺序执行已到时间的回调
+            let now = Instant::now();
+            let (due, rest): (Vec<_>, Vec<_>) = later.into_iter().partition(|(at, _)| *at <= now);
+            later = rest;
+            for (_, dispatch) in due {
+                if panic::catch_unwind(AssertUnwindSafe(|| dispatch.run())).is_err() {
+                    log::error!("Hotkey callback panicked");
+                }
+            }
+        }
+    }
+
+    fn run(self) {
+        match self {
+            Dispatch::SingleTap(combo, tap) => {
+                {
+                    let mut taps = PENDING_TAPS.lock().unwrap();
+                    // 已被第二次按下取走，或已开始新的一轮
+                    if taps.get(&combo).is_none_or(|&(_, current)| current != tap) {
+                        return;
+                    }
+                    taps.remove(&combo);
+                }
+                Dispatch::Trigger(&HOTKEY_REGISTRY, combo).run();
+            }
+            Dispatch::Trigger(registry, combo) => {
+                let map = registry.lock().unwrap();
+                if let Some(callbacks) = map.get(&combo) {
+                    HotKeyManager::trigger(&combo, callbacks);
+                }
+            }
+            Dispatch::Release(combo) => {
+                let map = RELEASE_REGISTRY.lock().unwrap();
+                for (_, cb) in map.get(&combo).into_iter().flatten() {
+                    log::debug!("Release callback triggered for combo: {combo:?}");
+                    cb();
+                }
+            }
+        }
+    }
+}
+
+/// 注册热键时返回的编号，用于之后注销或改绑
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct HotkeyId(u64);
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct KeyCombo(pub HashSet<Key>);
@@ -36,6 +183,24 @@ impl std::hash::Hash for KeyCombo {
     }
 }
 
+impl KeyCombo {
+    /// 由配置中的按键组成组合键，左右修饰键视为同一个键
+    pub fn new(keys: &[Key]) -> Self {
+        Self(keys.iter().map(|&key| normalize(key)).collect())
+    }
+}
+
+/// 右侧的修饰键映射为左侧的，按哪一侧都能触发
+fn normalize(key: Key) -> Key {
+    match key {
+        Key::ControlRight => Key::ControlLeft,
+        Key::ShiftRight => Key::ShiftLeft,
+        Key::MetaRight => Key::MetaLeft,
+        Key::AltGr => Key::Alt,
+        key => key,
+    }
+}
+
 impl HotKeyManager {
     /// 初始化热键监听器
     pub fn init() {
@@ -45,56 +210,529 @@ impl HotKeyManager {
         Self::start_global_listener();
     }
 
-    /// 注册组合键或单键
-    pub fn register_hotkey<F>(hotkeys: &[Key], callback: F)
+    /// 注册组合键或单键，未绑定按键时返回 None
+    pub fn register_hotkey<F>(hotkeys: &[Key], callback: F) -> Option<HotkeyId>
+    where
+        F: Fn() + Send + 'static,
+    {
+        Self::register(&HOTKEY_REGISTRY, hotkeys, callback)
+    }
+
+    /// 注册组合键松开时的回调：组合键按下后，其中任一键松开时触发一次
+    pub fn register_release<F>(hotkeys: &[Key], callback: F) -> Option<HotkeyId>
+    where
+        F: Fn() + Send + 'static,
+    {
+        Self::register(&RELEASE_REGISTRY, hotkeys, callback)
+    }
+
+    /// 注册组合键双击时的回调
+    ///
+    /// 同一组合键注册了双击时，单击的回调会推迟到双击间隔结束后才触发，双击时只触发双击的回调。
+    pub fn register_double_tap<F>(hotkeys: &[Key], callback: F) -> Option<HotkeyId>
+    where
+        F: Fn() + Send + 'static,
+    {
+        Self::register(&DOUBLE_TAP_REGISTRY, hotkeys, callback)
+    }
+
+    /// 设置双击的两次按下之间的最长间隔
+    pub fn set_double_tap_window(window: Duration) {
+        DOUBLE_TAP_WINDOW_MS.store(window.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// 注销一个热键，回调及其持有的资源随之释放；编号不存在时返回 false
+    pub fn unregister_hotkey(id: HotkeyId) -> bool {
+        let removed = Self::registries()
+            .iter()
+            .any(|registry| Self::take(&mut registry.lock().unwrap(), id).is_some());
+        if removed {
+            log::info!("Hotkey unregistered: {id:?}");
+        }
+        removed
+    }
+
+    /// 把一个热键改绑到新的组合键，回调与编号不变；新组合键为空时相当于注销
+    ///
+    /// 编号不存在时返回 false。
+    pub fn replace_hotkey(id: HotkeyId, hotkeys: &[Key]) -> bool {
+        for registry in Self::registries() {
+            let mut map = registry.lock().unwrap();
+            let Some((old, callback)) = Self::take(&mut map, id) else {
+                continue;
+            };
+            if hotkeys.is_empty() {
+                log::info!("Hotkey unregistered: {:?}", old.0);
+                return true;
+            }
+            let combo = KeyCombo::new(hotkeys);
+            log::info!("Hotkey rebound: {:?} -> {:?}", old.0, combo.0);
+            map.entry(combo).or_default().push((id, callback));
+            return true;
+        }
+        false
+    }
+
+    /// 设置按下时不传给其他程序的组合键，替换之前的设置
+    ///
+    /// 只吞掉完成组合键的那个键的按下与松开，修饰键照常传递。
+    pub fn set_swallowed<'a>(combos: impl IntoIterator<Item = &'a [Key]>) {
+        let combos = combo_set(combos);
+        log::debug!("Swallowed hotkeys: {combos:?}");
+        *SWALLOWED_COMBOS.lock().unwrap() = combos;
+    }
+
+    /// 设置精确匹配的组合键，替换之前的设置：按住组合键以外的修饰键时不触发，
+    /// 例如 Ctrl+S 在按下 Ctrl+Shift+S 时不触发
+    pub fn set_exact<'a>(combos: impl IntoIterator<Item = &'a [Key]>) {
+        let combos = combo_set(combos);
+        log::debug!("Exact-match hotkeys: {combos:?}");
+        *EXACT_COMBOS.lock().unwrap() = combos;
+    }
+
+    /// 设置前导键，替换之前的设置：`combos` 中的组合键只在按下前导键后 `timeout` 内、
+    /// 紧接着按下时才触发；前导键为空时取消
+    pub fn set_leader<'a>(
+        leader: &[Key],
+        combos: impl IntoIterator<Item = &'a [Key]>,
+        timeout: Duration,
+    ) {
+        let mut current = LEADER.lock().unwrap();
+        if leader.is_empty() {
+            *current = None;
+            return;
+        }
+        let combos = combo_set(combos);
+        log::debug!("Leader {leader:?} for hotkeys: {combos:?}");
+        *current = Some(Leader {
+            keys: KeyCombo::new(leader),
+            combos,
+            timeout,
+        });
+    }
+
+    /// 暂停或恢复所有热键；暂停期间按键照常传给游戏，例如在游戏中打字聊天时
+    pub fn set_paused(paused: bool) {
+        PAUSED.store(paused, Ordering::Relaxed);
+        log::info!("Hotkeys {}", if paused { "paused" } else { "resumed" });
+    }
+
+    pub fn is_paused() -> bool {
+        PAUSED.load(Ordering::Relaxed)
+    }
+
+    /// 限制热键只在前台窗口的标题或进程名包含任一模式时触发，替换之前的设置；为空时不限制
+    pub fn set_focus_patterns(patterns: Vec<String>) {
+        log::debug!("Hotkeys limited to windows matching: {patterns:?}");
+        *FOCUS_PATTERNS.lock().unwrap() = patterns;
+        Self::update_focus();
+    }
+
+    /// 前台窗口是否允许热键触发，取看门狗最近一次检查的结果
+    fn focused() -> bool {
+        FOCUSED.load(Ordering::Relaxed)
+    }
+
+    fn update_focus() {
+        let patterns = FOCUS_PATTERNS.lock().unwrap().clone();
+        FOCUSED.store(
+            patterns.is_empty() || foreground_window_matches(&patterns),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// 让一个热键在暂停期间仍然触发，用于暂停热键本身
+    pub fn exempt_from_pause(id: HotkeyId) {
+        PAUSE_EXEMPT.lock().unwrap().insert(id);
+    }
+
+    /// 把下一次按下的组合键交给 `callback`，用于改绑热键
+    ///
+    /// 按下非修饰键时连同按住的修饰键一起录下，这次按键不触发热键；按 Esc 取消，回调收到 None。
+    /// 回调在监听线程中执行，不能阻塞。
+    pub fn capture_next_combo<F>(callback: F)
+    where
+        F: FnOnce(Option<Vec<Key>>) + Send + 'static,
+    {
+        // 新的录制取代尚未完成的
+        let previous = CAPTURE.lock().unwrap().replace(Box::new(callback));
+        if let Some(previous) = previous {
+            previous(None);
+        }
+    }
+
+    fn register<F>(registry: &Mutex<HotkeyMap>, hotkeys: &[Key], callback: F) -> Option<HotkeyId>
     where
         F: Fn() + Send + 'static,
     {
         if !LISTENER_STARTED.load(Ordering::Relaxed) {
             log::warn!("HotKeyManager not initialized. Call HotKeyManager::init() first.");
-            return;
+            return None;
+        }
+
+        // 空组合键视为未绑定
+        if hotkeys.is_empty() {
+            log::debug!("Empty hotkey, skipping registration");
+            return None;
         }
 
-        let registry = HOTKEY_REGISTRY.clone();
         let mut map = registry.lock().unwrap();
-        let key_set: HashSet<Key> = hotkeys.iter().cloned().collect();
-        let combo = KeyCombo(key_set);
+        let combo = KeyCombo::new(hotkeys);
+        let id = HotkeyId(NEXT_HOTKEY_ID.fetch_add(1, Ordering::Relaxed));
         map.entry(combo.clone())
             .or_default()
-            .push(Box::new(callback));
+            .push((id, Box::new(callback)));
         log::info!("Hotkey registered: {:?}", combo.0);
+        Some(id)
+    }
+
+    fn registries() -> [&'static Mutex<HotkeyMap>; 3] {
+        [&HOTKEY_REGISTRY, &RELEASE_REGISTRY, &DOUBLE_TAP_REGISTRY]
     }
 
+    /// 从注册表中取出一个热键的回调及其组合键，组合键没有其他回调时一并移除
+    fn take(map: &mut HotkeyMap, id: HotkeyId) -> Option<(KeyCombo, HotkeyCallback)> {
+        let combo = map
+            .iter()
+            .find(|(_, callbacks)| callbacks.iter().any(|(cb_id, _)| *cb_id == id))
+            .map(|(combo, _)| combo.clone())?;
+        let callbacks = map.get_mut(&combo)?;
+        let index = callbacks.iter().position(|(cb_id, _)| *cb_id == id)?;
+        let (_, callback) = callbacks.remove(index);
+        if callbacks.is_empty() {
+            map.remove(&combo);
+            PENDING_TAPS.lock().unwrap().remove(&combo);
+        }
+        Some((combo, callback))
+    }
+
+    /// 监听线程退出或 panic 后自动重启，注册表是全局的，重启后热键保持不变
     fn start_global_listener() {
-        let registry = HOTKEY_REGISTRY.clone();
-        thread::spawn(move || {
-            let mut pressed: HashSet<Key> = HashSet::new();
-            listen(move |event| match event.event_type {
-                EventType::KeyPress(key) => {
-                    if pressed.insert(key) {
-                        let map = registry.lock().unwrap();
-                        for (combo, callbacks) in map.iter() {
-                            // 优化匹配逻辑：支持部分组合键匹配
-                            if combo.0.iter().all(|k| pressed.contains(k)) {
-                                log::info!(
-                                    "Triggering {} callback(s) for combo: {:?}",
-                                    callbacks.len(),
-                                    combo
-                                );
-                                for cb in callbacks {
-                                    log::debug!("Callback triggered for combo: {combo:?}");
-                                    cb();
-                                }
-                            }
-                        }
-                    }
-                }
-                EventType::KeyRelease(key) => {
-                    pressed.remove(&key);
+        thread::spawn(|| {
+            loop {
+                match thread::spawn(Self::run_listener).join() {
+                    Ok(()) => log::error!("Hotkey listener stopped, restarting"),
+                    Err(_) => log::error!("Hotkey listener panicked, restarting"),
                 }
-                _ => {}
-            })
-            .unwrap();
+                thread::sleep(LISTENER_RESTART_DELAY);
+            }
+        });
+        thread::spawn(Self::watch_listener);
+    }
+
+    /// 阻塞运行监听，正常情况下不会返回；收到 WM_QUIT 时返回，由上面的循环重新安装钩子
+    fn run_listener() {
+        LISTENER_THREAD.store(unsafe { GetCurrentThreadId() }, Ordering::Relaxed);
+        // grab 可以吞掉事件，不可用时退回只监听
+        let state = RefCell::new(ListenerState::default());
+        let result = grab(move |event| {
+            let swallow = handle_event(&state, &event.event_type);
+            (!swallow).then_some(event)
         });
+        if let Err(e) = result {
+            log::warn!("Failed to grab keyboard events, hotkeys cannot be swallowed: {e:?}");
+            let state = RefCell::new(ListenerState::default());
+            if let Err(e) = listen(move |event| {
+                handle_event(&state, &event.event_type);
+            }) {
+                log::error!("Failed to listen for keyboard events: {e:?}");
+            }
+        }
+    }
+
+    /// 看门狗：定期刷新前台窗口是否匹配，并在系统有输入而钩子收不到事件时让监听线程重启
+    fn watch_listener() {
+        let mut last_input = last_input_tick();
+        let mut last_events = HOOK_EVENTS.load(Ordering::Relaxed);
+        let mut misses = 0;
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            Self::update_focus();
+            let input = last_input_tick();
+            let events = HOOK_EVENTS.load(Ordering::Relaxed);
+            if events != last_events {
+                misses = 0;
+            } else if input != last_input {
+                misses += 1;
+            }
+            (last_input, last_events) = (input, events);
+            if misses >= HOOK_MISSES {
+                misses = 0;
+                log::warn!("Hotkey hook stopped receiving input, reinstalling");
+                let thread = LISTENER_THREAD.load(Ordering::Relaxed);
+                if let Err(e) = unsafe { PostThreadMessageW(thread, WM_QUIT, WPARAM(0), LPARAM(0)) }
+                {
+                    log::error!("Failed to stop hotkey listener: {e}");
+                }
+            }
+        }
+    }
+
+    /// 在分发线程中执行回调
+    fn trigger(combo: &KeyCombo, callbacks: &[(HotkeyId, HotkeyCallback)]) {
+        if !Self::focused() {
+            log::debug!("Foreground window not matched, ignoring combo: {combo:?}");
+            return;
+        }
+        log::info!(
+            "Triggering {} callback(s) for combo: {:?}",
+            callbacks.len(),
+            combo
+        );
+        let paused = Self::is_paused();
+        let exempt = PAUSE_EXEMPT.lock().unwrap();
+        for (id, cb) in callbacks {
+            if paused && !exempt.contains(id) {
+                continue;
+            }
+            log::debug!("Callback triggered for combo: {combo:?}");
+            cb();
+        }
+    }
+
+    /// 记下一次按下：在双击间隔内第二次按下时返回 None，否则返回这次按下的编号
+    fn record_tap(combo: &KeyCombo) -> Option<u64> {
+        let window = Duration::from_millis(DOUBLE_TAP_WINDOW_MS.load(Ordering::Relaxed));
+        let mut taps = PENDING_TAPS.lock().unwrap();
+        match taps.remove(combo) {
+            Some((at, _)) if at.elapsed() <= window => None,
+            _ => {
+                let tap = NEXT_TAP.fetch_add(1, Ordering::Relaxed);
+                taps.insert(combo.clone(), (Instant::now(), tap));
+                Some(tap)
+            }
+        }
+    }
+
+    /// 双击间隔结束后仍没有第二次按下时，按单击触发
+    fn defer_single_tap(combo: KeyCombo, tap: u64) {
+        let window = Duration::from_millis(DOUBLE_TAP_WINDOW_MS.load(Ordering::Relaxed));
+        Dispatch::SingleTap(combo, tap).send_at(Instant::now() + window);
+    }
+}
+
+/// 钩子中处理一个事件：记下事件数供看门狗检查；panic 不能穿过系统回调，
+/// 捕获后清空按键状态并照常传递这个事件
+fn handle_event(state: &RefCell<ListenerState>, event_type: &EventType) -> bool {
+    HOOK_EVENTS.fetch_add(1, Ordering::Relaxed);
+    panic::catch_unwind(AssertUnwindSafe(|| state.borrow_mut().handle(event_type))).unwrap_or_else(
+        |_| {
+            log::error!("Hotkey handler panicked, resetting key state");
+            *state.borrow_mut() = ListenerState::default();
+            false
+        },
+    )
+}
+
+/// 系统最近一次收到键盘或鼠标输入的时间（毫秒计数），获取失败时为 None
+fn last_input_tick() -> Option<u32> {
+    let mut info = LASTINPUTINFO {
+        cbSize: size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    unsafe { GetLastInputInfo(&mut info) }
+        .as_bool()
+        .then_some(info.dwTime)
+}
+
+fn combo_set<'a>(combos: impl IntoIterator<Item = &'a [Key]>) -> HashSet<KeyCombo> {
+    combos
+        .into_iter()
+        .filter(|keys| !keys.is_empty())
+        .map(KeyCombo::new)
+        .collect()
+}
+
+/// 是否为修饰键，按键已经过 `normalize`
+fn is_modifier(key: &Key) -> bool {
+    matches!(
+        key,
+        Key::Alt | Key::ControlLeft | Key::ShiftLeft | Key::MetaLeft
+    )
+}
+
+/// 监听线程中的按键状态
+#[derive(Default)]
+struct ListenerState {
+    pressed: HashSet<Key>,
+    // 已按下、等待松开的组合键
+    held: Vec<KeyCombo>,
+    // 按下事件被吞掉的键，之后的重复按下与松开同样吞掉
+    swallowed: HashSet<Key>,
+    // 上次按下前导键的时间，之后按下修饰键以外的键时清除
+    leader_at: Option<Instant>,
+    // 上一次按下的键（未经 normalize）及时间，用于识别 AltGr 附带的左 Ctrl
+    last_press: Option<(Key, Instant)>,
+    // 按住的 AltGr 附带了系统模拟的左 Ctrl，左 Ctrl 松开前忽略它的按下与松开
+    fake_ctrl: bool,
+}
+
+impl ListenerState {
+    /// 处理一个事件，返回是否吞掉它
+    fn handle(&mut self, event_type: &EventType) -> bool {
+        match *event_type {
+            EventType::KeyPress(key) => {
+                if self.is_fake_ctrl(key) {
+                    return false;
+                }
+                let key = normalize(key);
+                // 按住时的重复按下
+                if !self.pressed.insert(key) {
+                    return self.swallowed.contains(&key);
+                }
+                if !is_modifier(&key) && self.capture(key) {
+                    self.swallowed.insert(key);
+                    return true;
+                }
+                let swallow = self.on_press(key) && HotKeyManager::focused();
+                if swallow {
+                    self.swallowed.insert(key);
+                }
+                swallow
+            }
+            EventType::KeyRelease(key) => {
+                if key == Key::ControlLeft && std::mem::take(&mut self.fake_ctrl) {
+                    return false;
+                }
+                let key = normalize(key);
+                self.pressed.remove(&key);
+                self.on_release(key);
+                self.swallowed.remove(&key)
+            }
+            _ => false,
+        }
+    }
+
+    /// 使用带 AltGr 的键盘布局时，Windows 在 AltGr 之前紧接着模拟一次左 Ctrl 按下，
+    /// 不识别的话 AltGr+S 会被当作 Ctrl+Alt+S；识别后从按住的键中去掉这个左 Ctrl
+    fn is_fake_ctrl(&mut self, key: Key) -> bool {
+        let last = self.last_press.replace((key, Instant::now()));
+        match key {
+            // 按住 AltGr 时的重复按下同样带有模拟的左 Ctrl
+            Key::ControlLeft => self.fake_ctrl,
+            Key::AltGr => {
+                if let Some((Key::ControlLeft, at)) = last
+                    && at.elapsed() <= FAKE_CTRL_WINDOW
+                {
+                    self.pressed.remove(&Key::ControlLeft);
+                    self.fake_ctrl = true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// 有等待中的录制时把按住的修饰键与 `key` 交给它，返回是否录下了这次按键
+    fn capture(&self, key: Key) -> bool {
+        let Some(callback) = CAPTURE.lock().unwrap().take() else {
+            return false;
+        };
+        if key == Key::Escape {
+            callback(None);
+            return true;
+        }
+        let mut keys: Vec<Key> = self.pressed.iter().copied().filter(is_modifier).collect();
+        keys.sort_by_key(|k| format!("{k:?}"));
+        keys.push(key);
+        callback(Some(keys));
+        true
+    }
+
+    fn matches(&self, combo: &KeyCombo) -> bool {
+        if !combo.0.iter().all(|k| self.pressed.contains(k)) {
+            return false;
+        }
+        // 精确匹配时不允许按住组合键以外的修饰键
+        !EXACT_COMBOS.lock().unwrap().contains(combo)
+            || self
+                .pressed
+                .iter()
+                .all(|k| combo.0.contains(k) || !is_modifier(k))
+    }
+
+    /// 触发按下 `key` 后生效的热键，返回是否应吞掉这次按下
+    fn on_press(&mut self, key: Key) -> bool {
+        if HotKeyManager::is_paused() {
+            let map = HOTKEY_REGISTRY.lock().unwrap();
+            for combo in map.keys().filter(|combo| self.matches(combo)) {
+                Dispatch::Trigger(&HOTKEY_REGISTRY, combo.clone()).send();
+            }
+            return false;
+        }
+        let leader = LEADER.lock().unwrap().clone();
+        let armed = self
+            .leader_at
+            .zip(leader.as_ref())
+            .is_some_and(|(at, leader)| at.elapsed() <= leader.timeout);
+        // 按下修饰键时前导键仍然有效，例如先按 F9 再按 Ctrl+S
+        if !is_modifier(&key) {
+            self.leader_at = None;
+        }
+        if let Some(leader) = &leader
+            && leader.keys.0.contains(&key)
+            && self.matches(&leader.keys)
+        {
+            self.leader_at = Some(Instant::now());
+        }
+        // 需要前导键的组合键只在紧接着前导键按下时生效
+        let ready = |state: &Self, combo: &KeyCombo| {
+            state.matches(combo)
+                && (armed || !leader.as_ref().is_some_and(|l| l.combos.contains(combo)))
+        };
+
+        let release_map = RELEASE_REGISTRY.lock().unwrap();
+        let held: Vec<KeyCombo> = release_map
+            .keys()
+            .filter(|combo| ready(self, combo) && !self.held.contains(combo))
+            .cloned()
+            .collect();
+        drop(release_map);
+        self.held.extend(held);
+
+        // 注册了双击的组合键：第二次按下时触发双击，第一次按下时推迟单击
+        let double_map = DOUBLE_TAP_REGISTRY.lock().unwrap();
+        for combo in double_map.keys() {
+            if ready(self, combo) {
+                match HotKeyManager::record_tap(combo) {
+                    Some(tap) => HotKeyManager::defer_single_tap(combo.clone(), tap),
+                    None => Dispatch::Trigger(&DOUBLE_TAP_REGISTRY, combo.clone()).send(),
+                }
+            }
+        }
+        let tap_combos: HashSet<KeyCombo> = double_map.keys().cloned().collect();
+        drop(double_map);
+
+        let map = HOTKEY_REGISTRY.lock().unwrap();
+        for combo in map.keys() {
+            // 优化匹配逻辑：支持部分组合键匹配
+            if ready(self, combo) && !tap_combos.contains(combo) {
+                Dispatch::Trigger(&HOTKEY_REGISTRY, combo.clone()).send();
+            }
+        }
+        drop(map);
+
+        // 与触发使用同样的判断，等待前导键的组合键没有生效时照常传递
+        SWALLOWED_COMBOS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|combo| combo.0.contains(&key) && ready(self, combo))
+    }
+
+    fn on_release(&mut self, key: Key) {
+        let released: Vec<KeyCombo> = self
+            .held
+            .iter()
+            .filter(|combo| combo.0.contains(&key))
+            .cloned()
+            .collect();
+        if released.is_empty() {
+            return;
+        }
+        self.held.retain(|combo| !combo.0.contains(&key));
+        for combo in released {
+            Dispatch::Release(combo).send();
+        }
     }
 }
@@ -0,0 +1,78 @@
+use log::{debug, error};
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+use tokio::{
+    runtime::Handle,
+    sync::{Notify, Semaphore},
+};
+
+/// 同时执行的编码/上传任务数
+const MAX_CONCURRENT_JOBS: usize = 2;
+
+/// 后台任务队列，截图与录音的编码、上传都放在这里执行，
+/// 采集结束后即可开始下一次采集
+#[derive(Clone)]
+pub struct JobQueue {
+    permits: Arc<Semaphore>,
+    pending: Arc<AtomicUsize>,
+    idle: Arc<Notify>,
+}
+
+impl JobQueue {
+    pub fn new() -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_JOBS)),
+            pending: Arc::new(AtomicUsize::new(0)),
+            idle: Arc::new(Notify::new()),
+        }
+    }
+
+    /// 提交任务，有空闲名额时执行，不保证按提交顺序；任务需自行记录错误
+    ///
+    /// 编码会长时间占用 CPU，任务放在阻塞线程中执行，不占用异步运行时的工作线程。
+    pub fn submit<F>(&self, name: &str, job: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let queued = self.pending.fetch_add(1, Ordering::SeqCst) + 1;
+        debug!("Job queued: {name} ({queued} pending)");
+        let permits = Arc::clone(&self.permits);
+        let pending = Arc::clone(&self.pending);
+        let idle = Arc::clone(&self.idle);
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let permit = permits.acquire_owned().await;
+            let handle = Handle::current();
+            if let Err(e) = tokio::task::spawn_blocking(move || handle.block_on(job)).await {
+                error!("Job failed: {name}: {e}");
+            }
+            drop(permit);
+            debug!("Job finished: {name}");
+            if pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                idle.notify_waiters();
+            }
+        });
+    }
+
+    /// 等待所有已提交的任务完成
+    pub async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            if self.pending.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,72 +1,845 @@
 mod anki;
 mod audio;
+mod check;
+mod cleanup;
 mod config;
 mod hotkey_manager;
+mod jobs;
+mod notetype;
+mod offline;
 mod screenshot;
+mod tray;
 mod utils;
+mod video;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anki::AnkiClient;
 use anyhow::Result;
-use audio::AudioRecorder;
-use hotkey_manager::HotKeyManager;
+use audio::{AudioRecorder, CondensedRecorder};
+use clap::{Parser, Subcommand};
+use hotkey_manager::{HotKeyManager, HotkeyId};
+use jobs::JobQueue;
+use rdev::Key;
 use tokio::sync::mpsc;
+use utils::border::{BorderOverlay, OverlayOptions};
+
+/// 录音期间推迟的重新加载，每隔这么久检查一次录音是否已结束
+const DEFERRED_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// 使用指定的配置文件，不存在时创建默认配置
+    #[arg(long, value_name = "PATH")]
+    config: Option<std::path::PathBuf>,
+
+    /// 覆盖配置中的日志级别
+    #[arg(long, value_enum)]
+    log_level: Option<config::LogLevel>,
+
+    /// 覆盖配置中的 AnkiConnect 地址
+    #[arg(long, value_name = "URL")]
+    anki_url: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// 维护命令，执行完即退出
+#[derive(Subcommand)]
+enum Command {
+    /// 创建推荐的笔记类型
+    InstallNotetype,
+    /// 列出本程序生成、已没有卡片引用的媒体文件，加上 --yes 时删除它们
+    CleanMedia {
+        /// 删除列出的文件，不加时只列出
+        #[arg(long)]
+        yes: bool,
+    },
+    /// 检查配置文件与 Anki 中的字段，有问题时以非零状态退出
+    CheckConfig,
+    /// 以 TOML 输出实际生效的配置：默认值、配置文件、环境变量与命令行参数合并后的结果
+    PrintConfig,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    config::set_overrides(config::Overrides {
+        config_file: cli.config,
+        log_level: cli.log_level,
+        anki_connect_url: cli.anki_url,
+    });
+    // 检查与输出配置时不能像正常启动那样在解析失败时重建配置文件
+    match cli.command {
+        Some(Command::CheckConfig) => {
+            simple_logger::init_with_level(log::Level::Warn)?;
+            return check::check_config().await;
+        }
+        Some(Command::PrintConfig) => {
+            let mut cfg = config::reload_user_config()?;
+            // 输出可能被贴到别处求助，不显示密钥
+            for secret in [&mut cfg.anki.api_key, &mut cfg.anki.client_cert_password] {
+                if !secret.is_empty() {
+                    *secret = "***".to_string();
+                }
+            }
+            print!("{}", toml::to_string_pretty(&cfg)?);
+            return Ok(());
+        }
+        _ => {}
+    }
     let cfg = Arc::new(config::load_user_config()?);
 
     // 初始化日志系统
     let log_level: log::Level = cfg.log_level.clone().into();
     simple_logger::init_with_level(log_level)?;
+    if let Some(dir) = config::portable_directory() {
+        log::info!("Portable mode, data is stored in {}", dir.display());
+    }
+
+    let anki = Arc::new(AnkiClient::new(&cfg.anki)?);
 
-    let anki = Arc::new(AnkiClient::new(&cfg.anki));
+    // 维护命令执行完即退出，不注册热键
+    match cli.command {
+        Some(Command::InstallNotetype) => return notetype::install(&anki, &cfg).await,
+        Some(Command::CleanMedia { yes }) => {
+            return cleanup::clean_media(&anki, &cfg, yes).await;
+        }
+        Some(Command::CheckConfig | Command::PrintConfig) | None => {}
+    }
+    anki.start_offline_retry();
+    let fields = configured_fields(&cfg);
+    let checker = anki.clone();
+    tokio::spawn(async move {
+        if let Err(e) = checker.health_check(&fields).await {
+            log::error!("AnkiConnect health check failed: {e}");
+        }
+    });
+
+    let jobs = JobQueue::new();
 
     HotKeyManager::init();
-    setup_screenshot_hotkey(cfg.clone(), anki.clone());
-    setup_audio_record_hotkey(cfg.clone(), anki.clone());
+    let (control_tx, mut control_rx) = mpsc::channel(1);
+    let mut hotkeys = Hotkeys::register(cfg.clone(), anki.clone(), jobs.clone(), &control_tx);
+
+    let condensed = if cfg.condensed_audio.enabled {
+        // 编码参数取第一个录音配置
+        let audio_cfg = cfg.audio_record.first().cloned().unwrap_or_default();
+        match CondensedRecorder::start(cfg.condensed_audio.clone(), audio_cfg) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                log::error!("Failed to start condensed audio recording: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // 配置文件保存后自动重新加载，与重新加载热键的效果相同
+    let reload_tx = control_tx.clone();
+    if let Err(e) = config::watch_user_config(move || {
+        if let Err(e) = reload_tx.blocking_send(Control::Reload) {
+            log::error!("Failed to send reload signal: {e}");
+        }
+    }) {
+        log::warn!("Failed to watch config file: {e:#}");
+    }
+
+    // 托盘菜单中的修改写回配置文件，由上面的监视重新加载
+    let tray = if cfg.tray {
+        let quit_tx = control_tx.clone();
+        tray::Tray::start(cfg.clone(), move || {
+            if let Err(e) = quit_tx.blocking_send(Control::Quit) {
+                log::error!("Failed to send quit signal: {e}");
+            }
+        })
+        .inspect_err(|e| log::warn!("Failed to create tray icon: {e:#}"))
+        .ok()
+    } else {
+        None
+    };
+    if let Some(tray) = tray.clone() {
+        let anki = anki.clone();
+        tokio::spawn(async move {
+            let fields = match anki.target_model().await {
+                Ok(model) => anki.model_field_names(&model).await,
+                Err(e) => Err(e),
+            };
+            match fields {
+                Ok(fields) => tray.set_fields(fields),
+                Err(e) => log::debug!("Failed to get note fields for tray menu: {e}"),
+            }
+        });
+    }
 
     log::info!("Application started. Press Ctrl+C to exit.");
-    tokio::signal::ctrl_c().await?;
+    let mut pause_border: Option<BorderOverlay> = None;
+    // 重新注册会换掉录音器，录音期间的重新加载推迟到录音结束后
+    let mut reload_deferred = false;
+    loop {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                break;
+            }
+            _ = tokio::time::sleep(DEFERRED_RELOAD_INTERVAL), if reload_deferred => {
+                if !hotkeys.is_recording() {
+                    reload_deferred = false;
+                    log::info!("Recording finished, reloading config");
+                    reload_config(&mut hotkeys, &anki, &jobs, &control_tx, tray.as_ref());
+                }
+            }
+            Some(control) = control_rx.recv() => match control {
+                Control::Quit => break,
+                Control::Reload if hotkeys.is_recording() => {
+                    if !reload_deferred {
+                        log::info!("Recording in progress, config will be reloaded when it ends");
+                    }
+                    reload_deferred = true;
+                }
+                Control::Reload => {
+                    reload_deferred = false;
+                    reload_config(&mut hotkeys, &anki, &jobs, &control_tx, tray.as_ref());
+                }
+                Control::Pause => {
+                    let paused = !HotKeyManager::is_paused();
+                    HotKeyManager::set_paused(paused);
+                    if let Some(border) = pause_border.take() {
+                        border.stop();
+                    }
+                    // 暂停期间一直显示灰色边框
+                    if paused {
+                        pause_border = BorderOverlay::new(OverlayOptions {
+                            paused: true,
+                            ..Default::default()
+                        })
+                        .inspect_err(|e| log::warn!("Failed to show pause overlay: {e}"))
+                        .ok();
+                    }
+                }
+            },
+        }
+    }
     log::info!("Shutting down...");
+    // 正在进行的录音照常保存
+    for recorder in hotkeys.recorders.iter().filter(|r| r.is_recording()) {
+        if let Err(e) = recorder.save_on_exit().await {
+            log::error!("Failed to save recording on exit: {e}");
+        }
+    }
+    if let Some(recorder) = condensed
+        && let Err(e) = recorder.finish().await
+    {
+        log::error!("Failed to export condensed audio: {e}");
+    }
+    // 等待尚未完成的编码与上传
+    jobs.wait_idle().await;
+    anki.sync_pending().await;
     Ok(())
 }
 
-fn setup_screenshot_hotkey(cfg: Arc<config::Config>, anki: Arc<AnkiClient>) {
+/// 重新加载配置，并把结果同步到托盘菜单
+fn reload_config(
+    hotkeys: &mut Hotkeys,
+    anki: &Arc<AnkiClient>,
+    jobs: &JobQueue,
+    control_tx: &mpsc::Sender<Control>,
+    tray: Option<&tray::Tray>,
+) {
+    if let Err(e) = hotkeys.reload(anki.clone(), jobs.clone(), control_tx) {
+        log::error!("Failed to reload config: {e:#}");
+    }
+    // 重新加载失败时也要更新，恢复菜单中已勾选的新值
+    if let Some(tray) = tray {
+        tray.set_config(hotkeys.cfg.clone());
+    }
+}
+
+/// 由热键触发、在主循环中处理的操作
+#[derive(Clone, Copy, Debug)]
+enum Control {
+    Quit,
+    Reload,
+    Pause,
+}
+
+/// 已注册的热键及对应的录音器，重新加载配置时据此注销或改绑
+struct Hotkeys {
+    /// 当前生效的配置，重新加载时用来找出改动的项
+    cfg: Arc<config::Config>,
+    recorders: Vec<AudioRecorder>,
+    /// 截图、录音等热键，重新加载时全部注销后按新的配置重新注册
+    ids: Vec<HotkeyId>,
+    quit: Option<HotkeyId>,
+    reload: Option<HotkeyId>,
+    /// 暂停期间仍然生效
+    pause: Option<HotkeyId>,
+}
+
+impl Hotkeys {
+    fn register(
+        cfg: Arc<config::Config>,
+        anki: Arc<AnkiClient>,
+        jobs: JobQueue,
+        control_tx: &mpsc::Sender<Control>,
+    ) -> Self {
+        let mut ids = Vec::new();
+        let recorders = setup_hotkeys(cfg.clone(), anki, jobs, &mut ids);
+        let pause = bind_control(None, &cfg.hot_key.pause, control_tx, Control::Pause);
+        if let Some(id) = pause {
+            HotKeyManager::exempt_from_pause(id);
+        }
+        Self {
+            cfg: cfg.clone(),
+            recorders,
+            ids,
+            // 全屏游戏中不方便关掉控制台窗口，提供退出热键
+            quit: bind_control(None, &cfg.hot_key.quit, control_tx, Control::Quit),
+            reload: bind_control(
+                None,
+                &cfg.hot_key.reload_config,
+                control_tx,
+                Control::Reload,
+            ),
+            pause,
+        }
+    }
+
+    /// 是否有正在进行的录音
+    fn is_recording(&self) -> bool {
+        self.recorders.iter().any(|r| r.is_recording())
+    }
+
+    /// 重新读取配置文件并重新注册热键，编码等设置随新的配置生效
+    ///
+    /// 重新注册会换掉录音器，调用前需确认没有正在进行的录音。
+    fn reload(
+        &mut self,
+        anki: Arc<AnkiClient>,
+        jobs: JobQueue,
+        control_tx: &mpsc::Sender<Control>,
+    ) -> Result<()> {
+        let cfg = Arc::new(config::reload_user_config()?);
+        let changed = config::changed_keys(&self.cfg, &cfg)?;
+        if changed.is_empty() {
+            log::debug!("Config unchanged, skipping reload");
+            return Ok(());
+        }
+        for key in &changed {
+            // 这些设置只在启动时读取
+            if matches!(
+                key.split('.').next(),
+                Some("anki" | "condensedAudio" | "logLevel" | "tray")
+            ) {
+                log::warn!("Config changed: {key} (takes effect after restart)");
+            } else {
+                log::info!("Config changed: {key}");
+            }
+        }
+        for id in self.ids.drain(..) {
+            HotKeyManager::unregister_hotkey(id);
+        }
+        self.recorders = setup_hotkeys(cfg.clone(), anki, jobs, &mut self.ids);
+        // 退出与重新加载热键只改绑按键
+        self.quit = bind_control(self.quit, &cfg.hot_key.quit, control_tx, Control::Quit);
+        self.reload = bind_control(
+            self.reload,
+            &cfg.hot_key.reload_config,
+            control_tx,
+            Control::Reload,
+        );
+        self.pause = bind_control(self.pause, &cfg.hot_key.pause, control_tx, Control::Pause);
+        if let Some(id) = self.pause {
+            HotKeyManager::exempt_from_pause(id);
+        }
+        self.cfg = cfg;
+        log::info!("Config reloaded, hotkeys re-registered");
+        Ok(())
+    }
+}
+
+/// 把一个控制热键改绑到新的按键，原先未绑定时重新注册
+fn bind_control(
+    id: Option<HotkeyId>,
+    keys: &[Key],
+    control_tx: &mpsc::Sender<Control>,
+    control: Control,
+) -> Option<HotkeyId> {
+    if let Some(id) = id
+        && HotKeyManager::replace_hotkey(id, keys)
+    {
+        return (!keys.is_empty()).then_some(id);
+    }
+    let control_tx = control_tx.clone();
+    HotKeyManager::register_hotkey(keys, move || {
+        if let Err(e) = control_tx.try_send(control) {
+            eprintln!("Failed to send {control:?} signal: {e}");
+        }
+    })
+}
+
+/// 注册截图、录音等热键，返回各录音配置对应的录音器
+fn setup_hotkeys(
+    cfg: Arc<config::Config>,
+    anki: Arc<AnkiClient>,
+    jobs: JobQueue,
+    ids: &mut Vec<HotkeyId>,
+) -> Vec<AudioRecorder> {
+    HotKeyManager::set_double_tap_window(std::time::Duration::from_millis(
+        cfg.hot_key.double_tap_ms,
+    ));
+    let mut swallowed = named_hotkeys(&cfg.hot_key, &cfg.hot_key.swallow);
+    swallowed.extend(
+        cfg.audio_record
+            .iter()
+            .filter(|record| record.swallow)
+            .map(|record| record.hot_key.as_slice()),
+    );
+    HotKeyManager::set_swallowed(swallowed);
+    let mut exact = named_hotkeys(&cfg.hot_key, &cfg.hot_key.exact);
+    exact.extend(
+        cfg.audio_record
+            .iter()
+            .filter(|record| record.exact)
+            .map(|record| record.hot_key.as_slice()),
+    );
+    HotKeyManager::set_exact(exact);
+    HotKeyManager::set_focus_patterns(cfg.hot_key.only_in_windows.clone());
+    HotKeyManager::set_leader(
+        &cfg.hot_key.leader,
+        named_hotkeys(&cfg.hot_key, &cfg.hot_key.leader_hotkeys),
+        std::time::Duration::from_millis(cfg.hot_key.leader_timeout_ms),
+    );
+    let screenshot_tool = setup_screenshot_hotkey(cfg.clone(), anki.clone(), jobs.clone(), ids);
+    let recorders = setup_audio_record_hotkey(cfg.clone(), anki.clone(), jobs.clone(), ids);
+    setup_video_hotkey(cfg.clone(), anki.clone(), jobs, ids);
+    setup_combo_hotkey(
+        cfg.clone(),
+        anki.clone(),
+        screenshot_tool,
+        recorders.clone(),
+        ids,
+    );
+    setup_undo_hotkey(cfg.clone(), anki.clone(), ids);
+    setup_commit_hotkey(cfg.clone(), anki.clone(), ids);
+    setup_lock_note_hotkey(cfg, anki, ids);
+    recorders
+}
+
+/// 按热键名取配置中的热键，忽略未知的名称
+fn named_hotkeys<'a>(hot_key: &'a config::HotKey, names: &[String]) -> Vec<&'a [Key]> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let keys = hot_key.keys_by_name(name);
+            if keys.is_none() {
+                log::warn!("Unknown hotkey name: {name}");
+            }
+            keys
+        })
+        .collect()
+}
+
+/// 配置中会写入卡片的字段，供启动检查使用
+fn configured_fields(cfg: &config::Config) -> Vec<String> {
+    let shot = &cfg.screen_shot;
+    let mut fields = vec![shot.field_name.clone()];
+    if shot.thumbnail.enabled {
+        fields.push(shot.thumbnail.full_field_name.clone());
+    }
+    if shot.ocr.enabled {
+        fields.push(shot.ocr.field_name.clone());
+    }
+    fields.extend(shot.extra_fields.keys().cloned());
+    for record in &cfg.audio_record {
+        fields.push(record.field_name.clone());
+        if record.slowed.enabled {
+            fields.push(record.slowed.field_name.clone());
+        }
+        fields.extend(record.extra_fields.keys().cloned());
+    }
+    if !cfg.hot_key.video.is_empty() {
+        fields.push(cfg.video.field_name.clone());
+        fields.extend(cfg.video.extra_fields.keys().cloned());
+    }
+    if !cfg.anki.source_field.is_empty() {
+        fields.push(cfg.anki.source_field.clone());
+    }
+    fields.sort();
+    fields.dedup();
+    fields
+}
+
+fn setup_screenshot_hotkey(
+    cfg: Arc<config::Config>,
+    anki: Arc<AnkiClient>,
+    jobs: JobQueue,
+    ids: &mut Vec<HotkeyId>,
+) -> screenshot::AnkiScreenshot {
     let (screenshot_tx, mut screenshot_rx) = mpsc::channel(1);
 
-    let screenshot_tool = screenshot::AnkiScreenshot::new(cfg.screen_shot.clone(), anki);
+    let screenshot_tool = screenshot::AnkiScreenshot::new(cfg.screen_shot.clone(), anki, jobs);
+    screenshot::keep_warm(&cfg.screen_shot);
+
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.screen_shot,
+        move || {
+            if let Err(e) = screenshot_tx.try_send(()) {
+                eprintln!("Failed to send screenshot signal: {e}");
+            }
+        },
+    ));
 
-    HotKeyManager::register_hotkey(&cfg.hot_key.screen_shot, move || {
-        if let Err(e) = screenshot_tx.try_send(()) {
-            eprintln!("Failed to send screenshot signal: {e}");
+    // 重拍上一张截图，写回同一张卡片；预览截图时则重新截图
+    let (retake_tx, mut retake_rx) = mpsc::channel(1);
+    let review_tool = screenshot_tool.clone();
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.retake_screenshot,
+        move || {
+            if review_tool.resolve_review(screenshot::Review::Retake) {
+                return;
+            }
+            if let Err(e) = retake_tx.try_send(()) {
+                eprintln!("Failed to send retake screenshot signal: {e}");
+            }
+        },
+    ));
+    let retake_tool = screenshot_tool.clone();
+    tokio::spawn(async move {
+        while retake_rx.recv().await.is_some() {
+            if let Err(e) = retake_tool.retake_last_screenshot().await {
+                eprintln!("Failed to retake screenshot: {e}");
+            }
         }
     });
 
+    // 截图预览的确认/丢弃，与录音预览共用热键
+    let confirm_tool = screenshot_tool.clone();
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.confirm,
+        move || {
+            confirm_tool.resolve_review(screenshot::Review::Keep);
+        },
+    ));
+    let discard_tool = screenshot_tool.clone();
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.discard,
+        move || {
+            discard_tool.resolve_review(screenshot::Review::Discard);
+        },
+    ));
+
+    let tool = screenshot_tool.clone();
     tokio::spawn(async move {
         while screenshot_rx.recv().await.is_some() {
-            if let Err(e) = screenshot_tool.on_hotkey_clicked().await {
+            if let Err(e) = tool.on_hotkey_clicked().await {
                 eprintln!("Failed to take screenshot: {e}");
             }
         }
     });
+    screenshot_tool
 }
 
-fn setup_audio_record_hotkey(cfg: Arc<config::Config>, anki: Arc<AnkiClient>) {
-    let (audio_tx, mut audio_rx) = mpsc::channel(1);
-    let recorder = AudioRecorder::new(cfg.audio_record.clone(), anki);
-    HotKeyManager::register_hotkey(&cfg.hot_key.audio_record, move || {
-        if let Err(e) = audio_tx.try_send(()) {
-            eprintln!("Failed to send audio record signal: {e}");
+fn setup_video_hotkey(
+    cfg: Arc<config::Config>,
+    anki: Arc<AnkiClient>,
+    jobs: JobQueue,
+    ids: &mut Vec<HotkeyId>,
+) {
+    let (video_tx, mut video_rx) = mpsc::channel(1);
+
+    let video_tool = video::AnkiVideo::new(cfg.video.clone(), anki, jobs);
+
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.video,
+        move || {
+            if let Err(e) = video_tx.try_send(()) {
+                eprintln!("Failed to send video signal: {e}");
+            }
+        },
+    ));
+
+    tokio::spawn(async move {
+        while video_rx.recv().await.is_some() {
+            if let Err(e) = video_tool.on_hotkey_clicked().await {
+                eprintln!("Failed to record video: {e}");
+            }
         }
     });
+}
 
+fn setup_audio_record_hotkey(
+    cfg: Arc<config::Config>,
+    anki: Arc<AnkiClient>,
+    jobs: JobQueue,
+    ids: &mut Vec<HotkeyId>,
+) -> Vec<AudioRecorder> {
+    // 每个 [[audioRecord]] 条目是一个独立的录音器，拥有自己的热键
+    let recorders: Vec<AudioRecorder> = cfg
+        .audio_record
+        .iter()
+        .map(|record_cfg| {
+            AudioRecorder::new(
+                record_cfg.clone(),
+                cfg.overlay.clone(),
+                anki.clone(),
+                jobs.clone(),
+                record_cfg.interval_screenshots.enabled.then(|| {
+                    screenshot::AnkiScreenshot::new(
+                        cfg.screen_shot.clone(),
+                        anki.clone(),
+                        jobs.clone(),
+                    )
+                }),
+            )
+        })
+        .collect();
+
+    for (record_cfg, recorder) in cfg.audio_record.iter().zip(&recorders) {
+        if record_cfg.mode == config::HotKeyMode::Hold {
+            setup_hold_to_record(record_cfg, recorder.clone(), ids);
+            continue;
+        }
+        let (audio_tx, mut audio_rx) = mpsc::channel(1);
+        ids.extend(HotKeyManager::register_hotkey(
+            &record_cfg.hot_key,
+            move || {
+                if let Err(e) = audio_tx.try_send(()) {
+                    eprintln!("Failed to send audio record signal: {e}");
+                }
+            },
+        ));
+
+        let recorder = recorder.clone();
+        tokio::spawn(async move {
+            while audio_rx.recv().await.is_some() {
+                if let Err(e) = audio::on_hotkey_clicked(&recorder) {
+                    eprintln!("Failed to start recording: {e}");
+                }
+            }
+        });
+    }
+
+    // 预览确认/丢弃，仅在有等待中的预览时生效
+    let confirm_recorders = recorders.clone();
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.confirm,
+        move || {
+            confirm_recorders.iter().any(|r| r.resolve_review(true));
+        },
+    ));
+    let discard_recorders = recorders.clone();
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.discard,
+        move || {
+            discard_recorders.iter().any(|r| r.resolve_review(false));
+        },
+    ));
+
+    // 中止所有正在进行的录音
+    let (cancel_tx, mut cancel_rx) = mpsc::channel(1);
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.cancel_audio,
+        move || {
+            if let Err(e) = cancel_tx.try_send(()) {
+                eprintln!("Failed to send cancel audio signal: {e}");
+            }
+        },
+    ));
+    let cancel_recorders = recorders.clone();
     tokio::spawn(async move {
-        let recorder = recorder; // move into async block
-        while audio_rx.recv().await.is_some() {
-            if let Err(e) = audio::on_hotkey_clicked(&recorder) {
+        while cancel_rx.recv().await.is_some() {
+            let mut cancelled = false;
+            for recorder in &cancel_recorders {
+                cancelled |= recorder.cancel_recording().await;
+            }
+            if !cancelled {
+                log::debug!("No recording in progress to cancel");
+            }
+        }
+    });
+
+    // 重录作用于最近一次保存过录音的录音器
+    let (redo_tx, mut redo_rx) = mpsc::channel(1);
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.redo_audio,
+        move || {
+            if let Err(e) = redo_tx.try_send(()) {
+                eprintln!("Failed to send redo audio signal: {e}");
+            }
+        },
+    ));
+    let redo_recorders = recorders.clone();
+    tokio::spawn(async move {
+        while redo_rx.recv().await.is_some() {
+            let latest = redo_recorders
+                .iter()
+                .filter_map(|r| r.last_saved_at().map(|t| (t, r)))
+                .max_by_key(|(t, _)| *t);
+            let Some((_, recorder)) = latest else {
+                eprintln!("Failed to redo recording: no saved recording");
+                continue;
+            };
+            if let Err(e) = recorder.redo_last_recording().await {
+                eprintln!("Failed to redo recording: {e}");
+            }
+        }
+    });
+    recorders
+}
+
+fn setup_combo_hotkey(
+    cfg: Arc<config::Config>,
+    anki: Arc<AnkiClient>,
+    screenshot_tool: screenshot::AnkiScreenshot,
+    recorders: Vec<AudioRecorder>,
+    ids: &mut Vec<HotkeyId>,
+) {
+    let (combo_tx, mut combo_rx) = mpsc::channel(1);
+    let double_tap_tx = combo_tx.clone();
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.combo,
+        move || {
+            if let Err(e) = combo_tx.try_send(()) {
+                eprintln!("Failed to send combo signal: {e}");
+            }
+        },
+    ));
+    ids.extend(HotKeyManager::register_double_tap(
+        &cfg.hot_key.combo_double_tap,
+        move || {
+            if let Err(e) = double_tap_tx.try_send(()) {
+                eprintln!("Failed to send combo signal: {e}");
+            }
+        },
+    ));
+
+    let combo = cfg.combo.clone();
+    tokio::spawn(async move {
+        while combo_rx.recv().await.is_some() {
+            // 卡片只解析一次，录音与截图都写入这张卡片
+            let note_ids = match anki.resolve_note_ids().await {
+                Ok(ids) => ids,
+                Err(e) => {
+                    eprintln!("Failed to run combo action: {e}");
+                    continue;
+                }
+            };
+            log::info!("Combo action on note {}", note_ids[0]);
+            if combo.stop_audio {
+                for recorder in recorders.iter().filter(|r| r.is_recording()) {
+                    if let Err(e) = recorder.stop_and_save_to(note_ids.clone()).await {
+                        eprintln!("Failed to stop recording: {e}");
+                    }
+                }
+            }
+            if combo.screenshot
+                && let Err(e) = screenshot_tool.capture_to_note(note_ids.clone()).await
+            {
+                eprintln!("Failed to take screenshot: {e}");
+            }
+        }
+    });
+}
+
+/// 按住热键时录音，松开时停止并保存
+fn setup_hold_to_record(
+    record_cfg: &config::AudioRecord,
+    recorder: AudioRecorder,
+    ids: &mut Vec<HotkeyId>,
+) {
+    // 快速按下再松开时两个事件可能同时在队列中，不能丢掉松开事件
+    let (hold_tx, mut hold_rx) = mpsc::channel(2);
+    let release_tx = hold_tx.clone();
+    ids.extend(HotKeyManager::register_hotkey(
+        &record_cfg.hot_key,
+        move || {
+            if let Err(e) = hold_tx.try_send(true) {
+                eprintln!("Failed to send audio record signal: {e}");
+            }
+        },
+    ));
+    ids.extend(HotKeyManager::register_release(
+        &record_cfg.hot_key,
+        move || {
+            if let Err(e) = release_tx.try_send(false) {
+                eprintln!("Failed to send audio stop signal: {e}");
+            }
+        },
+    ));
+
+    tokio::spawn(async move {
+        while let Some(pressed) = hold_rx.recv().await {
+            if let Err(e) = audio::on_hotkey_held(&recorder, pressed) {
                 eprintln!("Failed to start recording: {e}");
             }
         }
     });
 }
+
+fn setup_undo_hotkey(cfg: Arc<config::Config>, anki: Arc<AnkiClient>, ids: &mut Vec<HotkeyId>) {
+    let (undo_tx, mut undo_rx) = mpsc::channel(1);
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.undo,
+        move || {
+            if let Err(e) = undo_tx.try_send(()) {
+                eprintln!("Failed to send undo signal: {e}");
+            }
+        },
+    ));
+
+    tokio::spawn(async move {
+        while undo_rx.recv().await.is_some() {
+            if let Err(e) = anki.undo_last_update().await {
+                eprintln!("Failed to undo last update: {e}");
+            }
+        }
+    });
+}
+
+fn setup_commit_hotkey(cfg: Arc<config::Config>, anki: Arc<AnkiClient>, ids: &mut Vec<HotkeyId>) {
+    let (commit_tx, mut commit_rx) = mpsc::channel(1);
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.commit,
+        move || {
+            if let Err(e) = commit_tx.try_send(()) {
+                eprintln!("Failed to send commit signal: {e}");
+            }
+        },
+    ));
+
+    tokio::spawn(async move {
+        while commit_rx.recv().await.is_some() {
+            if let Err(e) = anki.commit_staged().await {
+                eprintln!("Failed to commit staged captures: {e}");
+            }
+        }
+    });
+}
+
+fn setup_lock_note_hotkey(
+    cfg: Arc<config::Config>,
+    anki: Arc<AnkiClient>,
+    ids: &mut Vec<HotkeyId>,
+) {
+    let (lock_tx, mut lock_rx) = mpsc::channel(1);
+    ids.extend(HotKeyManager::register_hotkey(
+        &cfg.hot_key.lock_note,
+        move || {
+            if let Err(e) = lock_tx.try_send(()) {
+                eprintln!("Failed to send lock note signal: {e}");
+            }
+        },
+    ));
+
+    tokio::spawn(async move {
+        while lock_rx.recv().await.is_some() {
+            if let Err(e) = anki.toggle_note_lock().await {
+                eprintln!("Failed to lock note: {e}");
+            }
+        }
+    });
+}
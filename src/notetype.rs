@@ -0,0 +1,76 @@
+use crate::anki::AnkiClient;
+use crate::config::Config;
+use anyhow::{Result, anyhow};
+use log::info;
+
+/// 推荐笔记类型的名称
+const MODEL_NAME: &str = "game2anki";
+
+const CSS: &str = ".card {
+    font-family: sans-serif;
+    font-size: 24px;
+    text-align: center;
+    color: black;
+    background-color: white;
+}
+
+.picture img {
+    max-width: 100%;
+    max-height: 60vh;
+}
+
+.source {
+    margin-top: 1em;
+    font-size: 14px;
+    color: gray;
+}
+";
+
+/// 创建推荐的笔记类型，截图、句子、录音与来源字段的名称取自配置，已存在时不做修改
+pub async fn install(anki: &AnkiClient, cfg: &Config) -> Result<()> {
+    if anki
+        .model_names()
+        .await?
+        .iter()
+        .any(|name| name == MODEL_NAME)
+    {
+        return Err(anyhow!("笔记类型 {MODEL_NAME} 已存在"));
+    }
+    let sentence = cfg.screen_shot.ocr.field_name.as_str();
+    let picture = cfg.screen_shot.field_name.as_str();
+    let audio = cfg
+        .audio_record
+        .first()
+        .map_or("SentenceAudio", |record| record.field_name.as_str());
+    let source = if cfg.anki.source_field.is_empty() {
+        "Source"
+    } else {
+        cfg.anki.source_field.as_str()
+    };
+
+    // 正面显示句子和截图，背面播放录音并显示来源
+    let front = format!(
+        "<div class=\"sentence\">{}</div>\n<div class=\"picture\">{}</div>",
+        field_ref(sentence),
+        field_ref(picture)
+    );
+    let back = format!(
+        "{{{{FrontSide}}}}\n<hr id=answer>\n{}\n<div class=\"source\">{}</div>",
+        field_ref(audio),
+        field_ref(source)
+    );
+    anki.create_model(
+        MODEL_NAME,
+        &[sentence, picture, audio, source],
+        CSS,
+        &[("Card 1", front, back)],
+    )
+    .await?;
+    info!("已创建笔记类型 {MODEL_NAME}，字段: {sentence}, {picture}, {audio}, {source}");
+    Ok(())
+}
+
+/// 模板中引用字段的写法
+fn field_ref(field: &str) -> String {
+    format!("{{{{{field}}}}}")
+}
@@ -0,0 +1,201 @@
+use crate::anki::Capture;
+use crate::config::FieldMode;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const QUEUE_FILE: &str = "queue.json";
+/// 放弃重试的记录移到这个子目录
+const FAILED_DIR: &str = "failed";
+
+/// 尚未写入 Anki 的一次写入：AnkiConnect 无法连接时未能完成的，或暂存模式下等待提交的
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PendingUpdate {
+    pub id: u64,
+    /// 为空时在重试时再决定写入哪张卡片
+    #[serde(rename = "noteId")]
+    pub note_id: Option<u64>,
+    /// 随目标卡片一起写入的其他卡片
+    #[serde(rename = "linkedNotes", default)]
+    pub linked_notes: Vec<u64>,
+    /// 媒体文件名，数据暂存在队列目录下以 id 命名的子目录中
+    pub media: Vec<String>,
+    /// 要写入的字段及其内容
+    pub fields: Vec<(String, String)>,
+    #[serde(rename = "fieldMode")]
+    pub field_mode: FieldMode,
+    /// 采集时的游戏窗口标题，用于填写标签
+    #[serde(default)]
+    pub game: String,
+    /// 采集时渲染好的其他字段，覆盖写入
+    #[serde(rename = "extraFields", default)]
+    pub extra_fields: BTreeMap<String, String>,
+    /// 连接以外的原因导致写入失败的次数
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+impl PendingUpdate {
+    /// 要写入的卡片，目标卡片在最前；为空时由写入时决定
+    pub fn note_ids(&self) -> Option<Vec<u64>> {
+        let note_id = self.note_id?;
+        Some(
+            [note_id]
+                .into_iter()
+                .chain(self.linked_notes.iter().copied())
+                .collect(),
+        )
+    }
+
+    /// 还原采集时的内容，写入时按采集时的游戏与字段填写
+    pub fn capture(&self) -> Capture {
+        Capture {
+            game: self.game.clone(),
+            extra_fields: self.extra_fields.clone(),
+        }
+    }
+}
+
+/// 保存在磁盘上的待写入队列，用于离线队列与暂存区，程序重启后仍保留
+pub struct OfflineQueue {
+    dir: PathBuf,
+    /// 队列文件的读写互斥，不跨越 await
+    lock: Mutex<()>,
+}
+
+impl OfflineQueue {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// 暂存媒体数据并追加一条待写入记录
+    pub fn push(
+        &self,
+        note_ids: Option<&[u64]>,
+        media: &[(&str, &[u8])],
+        fields: &[(String, String)],
+        field_mode: &FieldMode,
+        capture: &Capture,
+    ) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries = self.read_entries()?;
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+        // 同一时刻入队的记录依次加一，保证 id 唯一
+        let id = entries.iter().map(|e| e.id + 1).fold(nanos, u64::max);
+        let entry_dir = self.dir.join(id.to_string());
+        fs::create_dir_all(&entry_dir)
+            .with_context(|| format!("Failed to create queue directory: {entry_dir:?}"))?;
+        for (filename, data) in media {
+            fs::write(entry_dir.join(filename), data)?;
+        }
+        let (note_id, linked_notes) = match note_ids {
+            Some([note_id, others @ ..]) => (Some(*note_id), others.to_vec()),
+            _ => (None, Vec::new()),
+        };
+        entries.push(PendingUpdate {
+            id,
+            note_id,
+            linked_notes,
+            media: media.iter().map(|(name, _)| name.to_string()).collect(),
+            fields: fields.to_vec(),
+            field_mode: field_mode.clone(),
+            game: capture.game.clone(),
+            extra_fields: capture.extra_fields.clone(),
+            attempts: 0,
+        });
+        self.write_entries(&entries)
+    }
+
+    /// 当前排队中的所有记录，按入队顺序
+    pub fn entries(&self) -> Result<Vec<PendingUpdate>> {
+        let _guard = self.lock.lock().unwrap();
+        self.read_entries()
+    }
+
+    pub fn media_path(&self, entry: &PendingUpdate, filename: &str) -> PathBuf {
+        self.dir.join(entry.id.to_string()).join(filename)
+    }
+
+    /// 写入完成后移除记录及其暂存的媒体
+    pub fn remove(&self, id: u64) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries = self.read_entries()?;
+        entries.retain(|e| e.id != id);
+        self.write_entries(&entries)?;
+        let entry_dir = self.dir.join(id.to_string());
+        if entry_dir.exists() {
+            fs::remove_dir_all(&entry_dir)?;
+        }
+        Ok(())
+    }
+
+    /// 记下一次写入失败，返回累计的失败次数
+    pub fn record_failure(&self, id: u64) -> Result<u32> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries = self.read_entries()?;
+        let entry = entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .with_context(|| format!("No queued update {id}"))?;
+        entry.attempts += 1;
+        let attempts = entry.attempts;
+        self.write_entries(&entries)?;
+        Ok(attempts)
+    }
+
+    /// 不再重试这条记录：连同媒体移到 failed 子目录，附带 update.json 记录原本要写入的内容与错误，
+    /// 之后可以手动补上；返回移到的目录
+    pub fn move_to_failed(&self, id: u64, error: &str) -> Result<PathBuf> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries = self.read_entries()?;
+        let index = entries
+            .iter()
+            .position(|e| e.id == id)
+            .with_context(|| format!("No queued update {id}"))?;
+        let entry = entries.remove(index);
+        let failed_dir = self.dir.join(FAILED_DIR).join(id.to_string());
+        fs::create_dir_all(self.dir.join(FAILED_DIR))?;
+        let entry_dir = self.dir.join(id.to_string());
+        if entry_dir.exists() {
+            fs::rename(&entry_dir, &failed_dir)
+                .with_context(|| format!("Failed to move {entry_dir:?} to {failed_dir:?}"))?;
+        } else {
+            fs::create_dir_all(&failed_dir)?;
+        }
+        let sidecar = serde_json::json!({
+            "error": error,
+            "update": entry,
+        });
+        fs::write(
+            failed_dir.join("update.json"),
+            serde_json::to_string_pretty(&sidecar)?,
+        )?;
+        self.write_entries(&entries)?;
+        Ok(failed_dir)
+    }
+
+    fn read_entries(&self) -> Result<Vec<PendingUpdate>> {
+        let path = self.dir.join(QUEUE_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read offline queue: {path:?}"))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse offline queue: {path:?}"))
+    }
+
+    fn write_entries(&self, entries: &[PendingUpdate]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(QUEUE_FILE);
+        fs::write(&path, serde_json::to_string_pretty(entries)?)
+            .with_context(|| format!("Failed to write offline queue: {path:?}"))
+    }
+}
@@ -0,0 +1,53 @@
+use image::{DynamicImage, GrayImage};
+
+/// 亮度不超过该值的像素视为黑色，缩放与压缩后的黑边往往不是纯黑
+const BLACK_THRESHOLD: u8 = 16;
+/// 一行（列）中黑色像素达到该比例即视为黑边
+const BLACK_RATIO: f32 = 0.98;
+/// 裁剪后每个方向至少保留的比例，避免把整体偏暗的画面裁掉
+const MIN_KEEP_RATIO: f32 = 0.5;
+
+/// 检测上下（letterbox）与左右（pillarbox）的黑边
+///
+/// 返回去掉黑边后的区域 `(x, y, width, height)`，没有黑边时返回 None
+pub fn detect_black_bars(image: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let top = (0..height).find(|&y| !is_black_row(&luma, y))?;
+    let bottom = (top..height).rev().find(|&y| !is_black_row(&luma, y))? + 1;
+    // 左右只统计有内容的行，避免上下黑边拉高黑色比例
+    let left = (0..width)
+        .find(|&x| !is_black_column(&luma, x, top, bottom))
+        .unwrap_or(0);
+    let right = (left..width)
+        .rev()
+        .find(|&x| !is_black_column(&luma, x, top, bottom))
+        .map_or(width, |x| x + 1);
+
+    let (w, h) = (right - left, bottom - top);
+    if w == width && h == height {
+        return None;
+    }
+    if (w as f32) < width as f32 * MIN_KEEP_RATIO || (h as f32) < height as f32 * MIN_KEEP_RATIO {
+        return None;
+    }
+    Some((left, top, w, h))
+}
+
+fn is_black_row(luma: &GrayImage, y: u32) -> bool {
+    let dark = (0..luma.width())
+        .filter(|&x| luma.get_pixel(x, y)[0] <= BLACK_THRESHOLD)
+        .count();
+    dark as f32 >= luma.width() as f32 * BLACK_RATIO
+}
+
+fn is_black_column(luma: &GrayImage, x: u32, start: u32, end: u32) -> bool {
+    let dark = (start..end)
+        .filter(|&y| luma.get_pixel(x, y)[0] <= BLACK_THRESHOLD)
+        .count();
+    dark as f32 >= (end - start) as f32 * BLACK_RATIO
+}
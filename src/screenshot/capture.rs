@@ -1,28 +1,300 @@
-use crate::config::Screenshot;
+use crate::config::{BurstPick, CaptureTarget, HdrCapture, HdrMode, Screenshot};
+use crate::screenshot::hdr::tone_map;
+use crate::screenshot::warm::capture_warm;
+use crate::utils::selection::Placement;
 use anyhow::{Result, anyhow};
 use image::DynamicImage;
 use log::{debug, info};
 use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 use windows_capture::{
     capture::{Context, GraphicsCaptureApiHandler},
     frame::Frame,
     graphics_capture_api::InternalCaptureControl,
+    monitor::Monitor,
     settings::{
         ColorFormat, CursorCaptureSettings, DirtyRegionSettings, DrawBorderSettings,
         MinimumUpdateIntervalSettings, SecondaryWindowSettings, Settings,
+        TryIntoCaptureItemWithType,
     },
     window::Window,
 };
 struct Flags {
     image_data: Arc<(Mutex<Option<DynamicImage>>, Condvar)>,
     exclude_title_bar: bool,
+    hdr: HdrCapture,
+    /// 连拍时持续接收帧，按此规则保留其中一帧
+    burst: Option<BurstPick>,
+    best_sharpness: Mutex<f64>,
 }
 
-pub fn capture_active_window(cfg: Screenshot) -> Result<DynamicImage> {
+/// 清晰度评分：亮度拉普拉斯响应的方差，隔行隔列采样以加快计算
+fn sharpness(image: &image::RgbaImage) -> f64 {
+    let (width, height) = image.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+    let luma = |x: u32, y: u32| {
+        let p = image.get_pixel(x, y);
+        0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64
+    };
+    let (mut sum, mut sum_sq, mut count) = (0.0, 0.0, 0.0);
+    for y in (1..height - 1).step_by(2) {
+        for x in (1..width - 1).step_by(2) {
+            let laplacian = luma(x - 1, y) + luma(x + 1, y) + luma(x, y - 1) + luma(x, y + 1)
+                - 4.0 * luma(x, y);
+            sum += laplacian;
+            sum_sq += laplacian * laplacian;
+            count += 1.0;
+        }
+    }
+    let mean = sum / count;
+    sum_sq / count - mean * mean
+}
+
+/// 截图在屏幕上的位置，供框选窗口对齐
+pub fn capture_placement(cfg: &Screenshot) -> Option<Placement> {
+    match cfg.target {
+        CaptureTarget::Window => {
+            let window = Window::foreground().ok()?;
+            let rect = window.rect().ok()?;
+            let title_bar = if cfg.exclude_title_bar {
+                window.title_bar_height().unwrap_or(0) as i32
+            } else {
+                0
+            };
+            Some(Placement::Position(rect.left, rect.top + title_bar))
+        }
+        CaptureTarget::Monitor => find_monitor(&cfg.monitor)
+            .and_then(|m| Ok(m.device_name()?))
+            .ok()
+            .map(|device_name| Placement::Monitor {
+                device_name,
+                offset: (0, 0),
+            }),
+    }
+}
+
+/// 按配置查找显示器：留空为主显示器，数字为从 1 开始的序号，否则按名称匹配
+pub(super) fn find_monitor(spec: &str) -> Result<Monitor> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return Ok(Monitor::primary()?);
+    }
+    if let Ok(index) = spec.parse::<usize>() {
+        return Ok(Monitor::from_index(index)?);
+    }
+    for monitor in Monitor::enumerate()? {
+        let name = monitor.name().unwrap_or_default();
+        let device_name = monitor.device_name().unwrap_or_default();
+        if name.eq_ignore_ascii_case(spec) || device_name.eq_ignore_ascii_case(spec) {
+            return Ok(monitor);
+        }
+    }
+    Err(anyhow!("找不到显示器: {spec}"))
+}
+
+pub(super) fn cursor_settings(cfg: &Screenshot) -> CursorCaptureSettings {
+    if cfg.include_cursor {
+        CursorCaptureSettings::WithCursor
+    } else {
+        CursorCaptureSettings::WithoutCursor
+    }
+}
+
+/// HDR 自动模式下以 16 位浮点采集，保留超出 SDR 范围的高光
+pub(super) fn color_format(hdr: &HdrCapture) -> ColorFormat {
+    match hdr.mode {
+        HdrMode::Auto => ColorFormat::Rgba16F,
+        HdrMode::Off => ColorFormat::Rgba8,
+    }
+}
+
+fn frame_to_image(
+    frame: &mut Frame,
+    exclude_title_bar: bool,
+    hdr: &HdrCapture,
+) -> Result<image::RgbaImage> {
+    let color_format = frame.color_format();
+    let mut frame_buffer = if exclude_title_bar {
+        frame.buffer_without_title_bar()?
+    } else {
+        frame.buffer()?
+    };
+    let width = frame_buffer.width();
+    let height = frame_buffer.height();
+    info!("捕获到帧: {width}x{height}, 格式: {color_format:?}");
+    let raw = frame_buffer.as_nopadding_buffer()?;
+    decode_frame(raw, width, height, color_format, hdr)
+}
+
+/// 8 位格式是合成后的 sRGB，16 位浮点是线性的 scRGB，统一转换为 8 位 sRGB
+pub(super) fn decode_frame(
+    raw: &[u8],
+    width: u32,
+    height: u32,
+    color_format: ColorFormat,
+    hdr: &HdrCapture,
+) -> Result<image::RgbaImage> {
+    match color_format {
+        ColorFormat::Rgba16F => Ok(tone_map(raw, width, height, hdr)),
+        ColorFormat::Rgba8 => image::RgbaImage::from_raw(width, height, raw.to_vec())
+            .ok_or_else(|| anyhow!("无法创建图像对象")),
+        ColorFormat::Bgra8 => {
+            let mut image = image::RgbaImage::from_raw(width, height, raw.to_vec())
+                .ok_or_else(|| anyhow!("无法创建图像对象"))?;
+            for pixel in image.pixels_mut() {
+                pixel.0.swap(0, 2);
+            }
+            Ok(image)
+        }
+    }
+}
+
+/// 动图录制的共享状态
+struct AnimationFlags {
+    exclude_title_bar: bool,
+    hdr: HdrCapture,
+    interval: Duration,
+    max_frames: usize,
+    started: Mutex<Option<Instant>>,
+    /// 帧及其相对第一帧的时间（毫秒）
+    frames: Mutex<Vec<(DynamicImage, i32)>>,
+}
+
+/// 在一段时间内按固定间隔采集多帧，用于生成动图
+pub fn capture_animation(cfg: &Screenshot) -> Result<Vec<(DynamicImage, i32)>> {
+    match cfg.target {
+        CaptureTarget::Window => {
+            let focus_window = Window::foreground()?;
+            debug!("当前焦点窗口: {focus_window:?}");
+            capture_animation_item(focus_window, cfg.exclude_title_bar, cfg)
+        }
+        CaptureTarget::Monitor => {
+            let monitor = find_monitor(&cfg.monitor)?;
+            capture_animation_item(monitor, false, cfg)
+        }
+    }
+}
+
+fn capture_animation_item<T: TryIntoCaptureItemWithType + Send + 'static>(
+    item: T,
+    exclude_title_bar: bool,
+    cfg: &Screenshot,
+) -> Result<Vec<(DynamicImage, i32)>> {
+    let animation = &cfg.animation;
+    let max_frames = animation.frames.max(1) as usize;
+    let duration = Duration::from_millis(animation.duration_ms as u64);
+    let flags = Arc::new(AnimationFlags {
+        exclude_title_bar,
+        hdr: cfg.hdr.clone(),
+        interval: duration / max_frames as u32,
+        max_frames,
+        started: Mutex::new(None),
+        frames: Mutex::new(Vec::with_capacity(max_frames)),
+    });
+
+    struct Handler {
+        flags: Arc<AnimationFlags>,
+    }
+    impl GraphicsCaptureApiHandler for Handler {
+        type Flags = Arc<AnimationFlags>;
+        type Error = Box<dyn std::error::Error + Send + Sync>;
+
+        fn new(context: Context<Self::Flags>) -> Result<Self, Self::Error> {
+            Ok(Self {
+                flags: context.flags.clone(),
+            })
+        }
+
+        fn on_frame_arrived(
+            &mut self,
+            frame: &mut Frame,
+            capture_control: InternalCaptureControl,
+        ) -> Result<(), Self::Error> {
+            let now = Instant::now();
+            let started = *self.flags.started.lock().unwrap().get_or_insert(now);
+            let elapsed = now - started;
+            let mut frames = self.flags.frames.lock().unwrap();
+            // 画面只在变化时才有新帧，因此按间隔取样而不是逐帧保存
+            let due = frames.last().is_none_or(|&(_, t)| {
+                elapsed >= Duration::from_millis(t as u64) + self.flags.interval
+            });
+            if due {
+                let img = frame_to_image(frame, self.flags.exclude_title_bar, &self.flags.hdr)?;
+                frames.push((DynamicImage::ImageRgba8(img), elapsed.as_millis() as i32));
+            }
+            if frames.len() >= self.flags.max_frames {
+                capture_control.stop();
+            }
+            Ok(())
+        }
+
+        fn on_closed(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    let settings = Settings::new(
+        item,
+        cursor_settings(cfg),
+        DrawBorderSettings::Default,
+        SecondaryWindowSettings::Default,
+        MinimumUpdateIntervalSettings::Default,
+        DirtyRegionSettings::Default,
+        color_format(&cfg.hdr),
+        Arc::clone(&flags),
+    );
+    let control = Handler::start_free_threaded(settings)?;
+    // 最多等待录制时长再多一秒，用于等第一帧
+    let deadline = Instant::now() + duration + Duration::from_secs(1);
+    while !control.is_finished() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    control
+        .stop()
+        .map_err(|e| anyhow!("停止动图录制失败: {e}"))?;
+
+    let frames = std::mem::take(&mut *flags.frames.lock().unwrap());
+    if frames.is_empty() {
+        return Err(anyhow!("截图超时"));
+    }
+    debug!("动图共 {} 帧", frames.len());
+    Ok(frames)
+}
+
+pub fn capture_screenshot(cfg: Screenshot) -> Result<DynamicImage> {
+    // 连拍需要等待新帧，不能使用缓存的画面
+    if cfg.warm_session && !cfg.burst.enabled {
+        return Ok(DynamicImage::ImageRgba8(capture_warm(&cfg)?));
+    }
+    match cfg.target {
+        CaptureTarget::Window => {
+            let focus_window = Window::foreground()?;
+            debug!("当前焦点窗口: {focus_window:?}");
+            capture_item(focus_window, cfg.exclude_title_bar, &cfg)
+        }
+        CaptureTarget::Monitor => {
+            let monitor = find_monitor(&cfg.monitor)?;
+            debug!("截取显示器: {}", monitor.name().unwrap_or_default());
+            capture_item(monitor, false, &cfg)
+        }
+    }
+}
+
+fn capture_item<T: TryIntoCaptureItemWithType + Send + 'static>(
+    item: T,
+    exclude_title_bar: bool,
+    cfg: &Screenshot,
+) -> Result<DynamicImage> {
+    let burst = &cfg.burst;
     let pair = Arc::new((Mutex::new(None::<DynamicImage>), Condvar::new()));
     let flags = Arc::new(Flags {
         image_data: Arc::clone(&pair),
-        exclude_title_bar: cfg.exclude_title_bar,
+        exclude_title_bar,
+        hdr: cfg.hdr.clone(),
+        burst: burst.enabled.then(|| burst.pick.clone()),
+        best_sharpness: Mutex::new(f64::MIN),
     });
 
     struct Handler {
@@ -43,23 +315,28 @@ pub fn capture_active_window(cfg: Screenshot) -> Result<DynamicImage> {
             frame: &mut Frame,
             capture_control: InternalCaptureControl,
         ) -> Result<(), Self::Error> {
-            let color_format = frame.color_format();
-            let mut frame_buffer = if self.flags.exclude_title_bar {
-                frame.buffer_without_title_bar().unwrap()
-            } else {
-                frame.buffer().unwrap()
+            let img = frame_to_image(frame, self.flags.exclude_title_bar, &self.flags.hdr)?;
+            let keep = match &self.flags.burst {
+                Some(BurstPick::Sharpest) => {
+                    let score = sharpness(&img);
+                    let mut best = self.flags.best_sharpness.lock().unwrap();
+                    debug!("连拍帧清晰度: {score:.1}");
+                    let sharper = score >= *best;
+                    if sharper {
+                        *best = score;
+                    }
+                    sharper
+                }
+                _ => true,
             };
-            let width = frame_buffer.width();
-            let height = frame_buffer.height();
-            let rgba = frame_buffer.as_raw_buffer();
-            info!("捕获到帧: {width}x{height}, 格式: {color_format:?}");
-            let img = image::RgbaImage::from_raw(width, height, rgba.to_vec())
-                .map(DynamicImage::ImageRgba8)
-                .ok_or_else(|| anyhow!("无法创建图像对象"))?;
-            let (lock, cvar) = &*self.flags.image_data;
-            *lock.lock().unwrap() = Some(img);
-            cvar.notify_one();
-            capture_control.stop();
+            if keep {
+                let (lock, cvar) = &*self.flags.image_data;
+                *lock.lock().unwrap() = Some(DynamicImage::ImageRgba8(img));
+                cvar.notify_one();
+            }
+            if self.flags.burst.is_none() {
+                capture_control.stop();
+            }
             Ok(())
         }
 
@@ -68,25 +345,35 @@ pub fn capture_active_window(cfg: Screenshot) -> Result<DynamicImage> {
         }
     }
 
-    let focus_window = Window::foreground()?;
-    debug!("当前焦点窗口: {focus_window:?}");
-
     let settings = Settings::new(
-        focus_window,
-        CursorCaptureSettings::WithoutCursor,
+        item,
+        cursor_settings(cfg),
         DrawBorderSettings::Default,
         SecondaryWindowSettings::Default,
         MinimumUpdateIntervalSettings::Default,
         DirtyRegionSettings::Default,
-        ColorFormat::Rgba8,
+        color_format(&cfg.hdr),
         Arc::clone(&flags),
     );
 
-    Handler::start(settings)?;
+    if !burst.enabled {
+        Handler::start(settings)?;
+    } else {
+        // 连拍：收到第一帧后继续接收一段时间再停止，画面静止时可能只有一帧
+        let control = Handler::start_free_threaded(settings)?;
+        {
+            let (lock, cvar) = &*pair;
+            let guard = lock.lock().unwrap();
+            let timeout = Duration::from_secs(3);
+            let _ = cvar.wait_timeout_while(guard, timeout, |img| img.is_none());
+        }
+        std::thread::sleep(Duration::from_millis(burst.duration_ms as u64));
+        control.stop().map_err(|e| anyhow!("停止连拍失败: {e}"))?;
+    }
 
     let (lock, cvar) = &*pair;
     let guard = lock.lock().unwrap();
-    let timeout = std::time::Duration::from_secs(3);
+    let timeout = Duration::from_secs(3);
     let (guard, _result) = cvar
         .wait_timeout_while(guard, timeout, |img| img.is_none())
         .unwrap();
@@ -1,20 +1,150 @@
-use crate::config::ScreenshotFormat;
-use anyhow::Result;
-use image::DynamicImage;
+use crate::config::{
+    AvifEncodeConfig, AvifEncoder, ImageFilter, JxlEncodeConfig, Screenshot, ScreenshotFormat,
+};
+use anyhow::{Result, anyhow};
+use image::{DynamicImage, imageops::FilterType};
+use log::debug;
 use rgb::FromSlice;
+use std::borrow::Cow;
 use std::io::Cursor;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::{env, fs, process};
 
-pub fn encode(
-    format: ScreenshotFormat,
-    quality: u8,
-    speed: u8,
-    image: &DynamicImage,
-) -> Result<Vec<u8>> {
-    match format {
-        ScreenshotFormat::Avif => encode_to_avif(quality, speed, image),
-        ScreenshotFormat::Webp => encode_to_webp(quality, image),
+pub fn encode(cfg: &Screenshot, image: &DynamicImage) -> Result<Vec<u8>> {
+    match cfg.format {
+        ScreenshotFormat::Avif => match cfg.avif.encoder {
+            AvifEncoder::Ravif => encode_to_avif(cfg.quality, cfg.speed, image),
+            AvifEncoder::Ffmpeg => encode_to_avif_ffmpeg(&cfg.avif, cfg.quality, cfg.speed, image),
+        },
+        ScreenshotFormat::Webp => encode_to_webp(cfg.quality, image),
         ScreenshotFormat::Png => encode_to_png(image),
+        ScreenshotFormat::Jxl => encode_to_jxl(&cfg.jxl, image),
+    }
+}
+
+/// 二分搜索质量，返回不超过目标大小的最高质量结果；都超出时返回最低质量的结果
+///
+/// `encode_once` 按传入配置中的质量编码一次，JPEG XL 的质量换算为 distance。
+pub fn encode_to_target_size(
+    cfg: &Screenshot,
+    encode_once: impl Fn(&Screenshot) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    if let ScreenshotFormat::Png = cfg.format {
+        debug!("PNG 为无损格式，忽略目标文件大小");
+        return encode_once(cfg);
+    }
+    let target = cfg.target_size_kb as usize * 1024;
+    let (mut lo, mut hi) = (1u8, 100u8);
+    let mut best: Option<(u8, Vec<u8>)> = None;
+    let mut smallest: Option<Vec<u8>> = None;
+    while lo <= hi {
+        let quality = lo + (hi - lo) / 2;
+        let mut attempt = cfg.clone();
+        attempt.quality = quality;
+        attempt.jxl.distance = jxl_distance(quality);
+        let data = encode_once(&attempt)?;
+        debug!("质量 {quality}: {} KB", data.len() / 1024);
+        if data.len() <= target {
+            lo = quality + 1;
+            best = Some((quality, data));
+        } else {
+            hi = quality - 1;
+            if smallest.as_ref().is_none_or(|s| data.len() < s.len()) {
+                smallest = Some(data);
+            }
+        }
+    }
+    match best {
+        Some((quality, data)) => {
+            debug!("按目标大小 {} KB 选用质量 {quality}", cfg.target_size_kb);
+            Ok(data)
+        }
+        None => {
+            debug!("最低质量仍超出目标大小 {} KB", cfg.target_size_kb);
+            smallest.ok_or_else(|| anyhow!("没有可用的编码结果"))
+        }
+    }
+}
+
+/// 与 cjxl --quality 相同的质量到 distance 的换算
+fn jxl_distance(quality: u8) -> f32 {
+    let q = quality as f32;
+    if q >= 30.0 {
+        0.1 + (100.0 - q) * 0.09
+    } else {
+        53.0 / 3000.0 * q * q - 23.0 / 20.0 * q + 25.0
+    }
+}
+
+/// 等比缩小到不超过给定尺寸，0 表示该方向不限制；不会放大
+pub fn downscale(image: &DynamicImage, max_width: u32, max_height: u32) -> Cow<'_, DynamicImage> {
+    let bound = |max: u32| if max == 0 { u32::MAX } else { max };
+    let (max_width, max_height) = (bound(max_width), bound(max_height));
+    if image.width() <= max_width && image.height() <= max_height {
+        return Cow::Borrowed(image);
+    }
+    debug!(
+        "缩小截图: {}x{} -> 最大 {max_width}x{max_height}",
+        image.width(),
+        image.height()
+    );
+    Cow::Owned(image.resize(max_width, max_height, FilterType::Lanczos3))
+}
+
+/// 编码前的灰度与色调分离处理，未启用时原样返回
+pub fn apply_filter<'a>(
+    image: Cow<'a, DynamicImage>,
+    filter: &ImageFilter,
+) -> Cow<'a, DynamicImage> {
+    let mut image = image;
+    if filter.grayscale {
+        image = Cow::Owned(DynamicImage::ImageLuma8(image.to_luma8()));
+    }
+    if filter.posterize_levels >= 2 {
+        let steps = (filter.posterize_levels - 1) as f32;
+        let mut posterized = image.into_owned();
+        let quantize =
+            |v: &mut u8| *v = ((*v as f32 / 255.0 * steps).round() / steps * 255.0) as u8;
+        match &mut posterized {
+            DynamicImage::ImageLuma8(buffer) => buffer.iter_mut().for_each(quantize),
+            other => {
+                let mut rgba = other.to_rgba8();
+                // 只处理颜色通道，保留透明度
+                rgba.pixels_mut()
+                    .for_each(|p| p.0[..3].iter_mut().for_each(quantize));
+                *other = DynamicImage::ImageRgba8(rgba);
+            }
+        }
+        image = Cow::Owned(posterized);
     }
+    image
+}
+
+/// 将多帧编码为动画 WebP，帧时间为相对第一帧的毫秒数
+pub fn encode_animation(
+    cfg: &Screenshot,
+    frames: &[(Cow<'_, DynamicImage>, i32)],
+) -> Result<Vec<u8>> {
+    let (first, _) = frames.first().ok_or_else(|| anyhow!("没有可编码的帧"))?;
+    let (width, height) = (first.width(), first.height());
+    let mut config =
+        webp::WebPConfig::new().map_err(|_| anyhow!("Failed to create WebP config"))?;
+    config.quality = cfg.quality as f32;
+
+    let pixels: Vec<Vec<u8>> = frames
+        .iter()
+        .map(|(frame, _)| frame.to_rgba8().into_raw())
+        .collect();
+    let mut encoder = webp::AnimEncoder::new(width, height, &config);
+    for ((_, timestamp), rgba) in frames.iter().zip(&pixels) {
+        encoder.add_frame(webp::AnimFrame::from_rgba(rgba, width, height, *timestamp));
+    }
+    let data = encoder
+        .try_encode()
+        .map_err(|e| anyhow!("Failed to encode animated WebP: {e:?}"))?;
+    Ok(data.to_vec())
 }
 
 pub fn encode_to_avif(quality: u8, speed: u8, image: &DynamicImage) -> Result<Vec<u8>> {
@@ -36,7 +166,12 @@ pub fn encode_to_avif(quality: u8, speed: u8, image: &DynamicImage) -> Result<Ve
 }
 
 pub fn encode_to_webp(quality: u8, image: &DynamicImage) -> Result<Vec<u8>> {
-    let encoder = webp::Encoder::from_image(image)
+    // webp 只接受 RGB/RGBA，灰度图需要先转换
+    let image = match image {
+        DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => Cow::Borrowed(image),
+        other => Cow::Owned(DynamicImage::ImageRgba8(other.to_rgba8())),
+    };
+    let encoder = webp::Encoder::from_image(&image)
         .map_err(|e| anyhow::anyhow!("Failed to create WebP encoder: {}", e))?;
 
     let img = encoder.encode(quality as f32).to_vec();
@@ -50,3 +185,85 @@ pub fn encode_to_png(image: &DynamicImage) -> Result<Vec<u8>> {
     image.write_to(&mut cursor, image::ImageFormat::Png)?;
     Ok(buffer)
 }
+
+/// 调用 libjxl 的 cjxl 编码
+pub fn encode_to_jxl(cfg: &JxlEncodeConfig, image: &DynamicImage) -> Result<Vec<u8>> {
+    encode_with_command(&cfg.encoder_path, "jxl", image, |command, input, output| {
+        command
+            .arg(input)
+            .arg(output)
+            .arg("--distance")
+            .arg(cfg.distance.to_string())
+            .arg("--effort")
+            .arg(cfg.effort.to_string())
+            .arg("--quiet");
+    })
+    .map_err(|e| anyhow!("JPEG XL 编码失败: {e}"))
+}
+
+/// 调用 ffmpeg 的 libaom-av1 编码，质量与速度换算到 crf / cpu-used
+pub fn encode_to_avif_ffmpeg(
+    cfg: &AvifEncodeConfig,
+    quality: u8,
+    speed: u8,
+    image: &DynamicImage,
+) -> Result<Vec<u8>> {
+    let crf = (100 - quality.min(100) as u32) * 63 / 100;
+    let cpu_used = speed.min(8);
+    encode_with_command(&cfg.ffmpeg_path, "avif", image, |command, input, output| {
+        command
+            .args(["-y", "-loglevel", "error", "-i"])
+            .arg(input)
+            // 明确 RGB 到 YUV 的转换矩阵并标注 sRGB，否则播放器可能按其他色彩空间解码而偏色
+            .args(["-vf", "scale=out_color_matrix=bt709:out_range=pc"])
+            .args(["-colorspace", "bt709", "-color_primaries", "bt709"])
+            .args(["-color_trc", "iec61966-2-1", "-color_range", "pc"])
+            .args(["-c:v", "libaom-av1", "-still-picture", "1"])
+            .arg("-crf")
+            .arg(crf.to_string())
+            .arg("-cpu-used")
+            .arg(cpu_used.to_string())
+            .arg(output);
+    })
+    .map_err(|e| anyhow!("AVIF 编码失败: {e}"))
+}
+
+/// 通过外部编码器编码：先写出 PNG 中间文件，再读回编码结果，中间文件放在临时目录
+fn encode_with_command(
+    program: &str,
+    output_ext: &str,
+    image: &DynamicImage,
+    build: impl FnOnce(&mut Command, &Path, &Path),
+) -> Result<Vec<u8>> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let stem = format!(
+        "game2anki_{}_{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let input = env::temp_dir().join(format!("{stem}.png"));
+    let output = env::temp_dir().join(format!("{stem}.{output_ext}"));
+    fs::write(&input, encode_to_png(image)?)?;
+
+    let mut command = Command::new(program);
+    build(&mut command, &input, &output);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let result = command.output();
+    let _ = fs::remove_file(&input);
+    let result = result.map_err(|e| anyhow!("无法运行 {program}: {e}"))?;
+    if !result.status.success() {
+        let _ = fs::remove_file(&output);
+        return Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    let data = fs::read(&output)?;
+    let _ = fs::remove_file(&output);
+    Ok(data)
+}
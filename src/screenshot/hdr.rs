@@ -0,0 +1,106 @@
+use crate::config::{HdrCapture, ToneMapOperator};
+use half::f16;
+use image::RgbaImage;
+use log::debug;
+
+/// scRGB 中 1.0 对应的亮度（尼特）
+const SCRGB_NITS: f32 = 80.0;
+
+/// 把 Rgba16F（scRGB，线性）的采集结果转换为 8 位 sRGB
+///
+/// 画面没有超出 [0, 1] 时视为 SDR 内容，只做 gamma 编码；
+/// 否则按 SDR 白色亮度缩放后再压缩高光。
+/// 广色域显示器上超出 sRGB 色域的颜色（scRGB 中的负值）先向灰度收缩到色域内。
+pub fn tone_map(raw: &[u8], width: u32, height: u32, cfg: &HdrCapture) -> RgbaImage {
+    let linear: Vec<f32> = raw
+        .chunks_exact(2)
+        .map(|b| f16::from_le_bytes([b[0], b[1]]).to_f32())
+        .collect();
+    let peak = linear
+        .chunks_exact(4)
+        .flat_map(|p| &p[..3])
+        .fold(0.0f32, |peak, &v| peak.max(v));
+    let is_hdr = peak > 1.0;
+    let scale = if is_hdr {
+        SCRGB_NITS / cfg.sdr_white_nits.max(1.0)
+    } else {
+        1.0
+    };
+    debug!(
+        "HDR 采集峰值: {:.0} 尼特，{}",
+        peak * SCRGB_NITS,
+        if is_hdr {
+            "进行色调映射"
+        } else {
+            "视为 SDR"
+        }
+    );
+    let white = (peak * scale).max(1.0);
+
+    let mut image = RgbaImage::new(width, height);
+    for (out, p) in image.pixels_mut().zip(linear.chunks_exact(4)) {
+        let rgb = fit_gamut([p[0] * scale, p[1] * scale, p[2] * scale]);
+        let mapped = if is_hdr {
+            map_pixel(rgb, white, &cfg.tone_map)
+        } else {
+            rgb
+        };
+        *out = image::Rgba([
+            encode_srgb(mapped[0]),
+            encode_srgb(mapped[1]),
+            encode_srgb(mapped[2]),
+            255,
+        ]);
+    }
+    image
+}
+
+/// 保持亮度与色相，向同亮度的灰色收缩直到没有负分量
+///
+/// 直接截断负值会让饱和的红绿色偏向黄色，截图看起来偏色。
+fn fit_gamut(rgb: [f32; 3]) -> [f32; 3] {
+    let min = rgb[0].min(rgb[1]).min(rgb[2]);
+    if min >= 0.0 {
+        return rgb;
+    }
+    let luma = luminance(rgb);
+    if luma <= 0.0 {
+        return [0.0; 3];
+    }
+    let t = luma / (luma - min);
+    rgb.map(|c| luma + (c - luma) * t)
+}
+
+fn luminance(rgb: [f32; 3]) -> f32 {
+    0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+}
+
+fn map_pixel(rgb: [f32; 3], white: f32, operator: &ToneMapOperator) -> [f32; 3] {
+    match operator {
+        ToneMapOperator::Reinhard => {
+            // 扩展 Reinhard：以画面峰值为白点，只压缩亮度以保持色相
+            let luma = luminance(rgb);
+            if luma <= 0.0 {
+                return rgb;
+            }
+            let mapped = luma * (1.0 + luma / (white * white)) / (1.0 + luma);
+            rgb.map(|c| c * mapped / luma)
+        }
+        ToneMapOperator::Aces => rgb.map(|c| {
+            // Narkowicz 对 ACES 曲线的近似
+            let x = c * 0.6;
+            (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+        }),
+        ToneMapOperator::Clip => rgb,
+    }
+}
+
+fn encode_srgb(linear: f32) -> u8 {
+    let c = linear.clamp(0.0, 1.0);
+    let encoded = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
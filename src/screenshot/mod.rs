@@ -1,55 +1,657 @@
-use crate::screenshot::capture::capture_active_window;
-use crate::{anki::AnkiClient, config::Screenshot};
-use anyhow::Result;
-use log::{debug, info};
-use std::sync::Arc;
+use crate::screenshot::capture::{capture_animation, capture_placement, capture_screenshot};
+use crate::{
+    anki::{AnkiClient, Capture, render_media_value},
+    config::{CropRegion, CropUnit, Screenshot},
+    jobs::JobQueue,
+};
+use anyhow::{Result, anyhow};
+use image::DynamicImage;
+use log::{debug, error, info, warn};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+mod bars;
 mod capture;
 mod encode;
-use crate::utils::file::generate_safe_filename;
-use encode::encode;
+mod hdr;
+mod ocr;
+mod warm;
+use crate::utils::{
+    border::{BorderOverlay, OverlayOptions},
+    file::generate_safe_filename,
+    selection::select_region,
+};
+use encode::{apply_filter, downscale, encode, encode_animation, encode_to_target_size};
+pub use warm::keep_warm;
 
+/// 关闭覆盖层后等待其从屏幕上消失的时间
+const OVERLAY_HIDE_DELAY: Duration = Duration::from_millis(100);
+
+/// 文件名、各帧及其时间、字幕区域
+type Captured = (String, Vec<(DynamicImage, i32)>, Option<DynamicImage>);
+/// 媒体文件名与数据、要写入的字段及其内容
+type MediaAndFields<'a> = (Vec<(&'a str, &'a [u8])>, Vec<(String, String)>);
+
+/// 预览截图时的操作
+#[derive(Clone, Copy, Debug)]
+pub enum Review {
+    Keep,
+    Discard,
+    Retake,
+}
+
+/// 最近一次写入 Anki 的截图，供“重拍”使用
+struct SavedShot {
+    note_id: u64,
+    /// 存入 Anki 的媒体文件名
+    files: Vec<String>,
+    /// 写入的字段及其内容
+    fields: Vec<(String, String)>,
+}
+
+#[derive(Clone)]
 pub struct AnkiScreenshot {
     cfg: Screenshot,
     anki: Arc<AnkiClient>,
+    jobs: JobQueue,
+    review: Arc<Mutex<Option<oneshot::Sender<Review>>>>,
+    last_saved: Arc<Mutex<Option<SavedShot>>>,
 }
 
 impl AnkiScreenshot {
-    pub fn new(cfg: Screenshot, anki: Arc<AnkiClient>) -> Self {
-        Self { cfg, anki }
+    pub fn new(cfg: Screenshot, anki: Arc<AnkiClient>, jobs: JobQueue) -> Self {
+        Self {
+            cfg,
+            anki,
+            jobs,
+            review: Arc::new(Mutex::new(None)),
+            last_saved: Arc::new(Mutex::new(None)),
+        }
     }
 
     pub async fn on_hotkey_clicked(&self) -> Result<()> {
-        let filename = generate_safe_filename(&self.cfg.field_name, &self.cfg.format.to_string());
+        self.capture_and_submit(None, None).await
+    }
+
+    /// 截图并写入指定的卡片，而不是提交时的最新卡片
+    pub async fn capture_to_note(&self, note_ids: Vec<u64>) -> Result<()> {
+        self.capture_and_submit(None, Some(note_ids)).await
+    }
+
+    /// 重拍上一张已保存的截图：删除旧文件，新截图写回同一张卡片
+    pub async fn retake_last_screenshot(&self) -> Result<()> {
+        let last = self.last_saved.lock().unwrap().take();
+        let Some(last) = last else {
+            return Err(anyhow!("没有可重拍的截图"));
+        };
+        info!("重拍卡片 {} 的截图", last.note_id);
+        // 重拍时写回原来的卡片，最新卡片可能已经变了
+        let note_ids = vec![last.note_id];
+        self.capture_and_submit(Some(last), Some(note_ids)).await
+    }
+
+    /// 截图并提交编码任务；`replace` 为要替换的上一张截图，`note_ids` 为空时写入最新卡片
+    async fn capture_and_submit(
+        &self,
+        replace: Option<SavedShot>,
+        note_ids: Option<Vec<u64>>,
+    ) -> Result<()> {
+        let (filename, frames, subtitle) = match self.capture().await {
+            Ok(Some(captured)) => captured,
+            result => {
+                // 没有拍到新截图时保留上一张，之后仍可重拍
+                if let Some(last) = replace {
+                    *self.last_saved.lock().unwrap() = Some(last);
+                }
+                return result.map(|_| ());
+            }
+        };
+        // 重拍只替换图片，字幕文字沿用第一次的识别结果
+        let subtitle = subtitle.filter(|_| replace.is_none());
+        let capture = self.anki.capture(&self.cfg.extra_fields);
+
+        // 编码与上传放到后台队列，不阻塞下一次截图
+        let this = self.clone();
+        self.jobs.submit("encode screenshot", async move {
+            if this.anki.is_staging() {
+                if let Err(e) = this
+                    .stage(&filename, &frames, subtitle, note_ids.as_deref(), &capture)
+                    .await
+                {
+                    error!("Failed to stage screenshot: {e}");
+                }
+                return;
+            }
+            // 字幕文字写入截图所在的卡片，新建卡片模式下不会再建一张
+            let note_ids = match this
+                .encode_and_save(&filename, &frames, replace, note_ids.as_deref(), &capture)
+                .await
+            {
+                Ok(ids) => Some(ids),
+                Err(e) => {
+                    error!("Failed to save screenshot: {e}");
+                    note_ids
+                }
+            };
+            if let Some(subtitle) = subtitle
+                && let Err(e) = this
+                    .save_subtitle(subtitle, note_ids.as_deref(), &capture.game)
+                    .await
+            {
+                error!("Failed to save OCR text: {e}");
+            }
+        });
+        Ok(())
+    }
+
+    /// 截图并完成裁剪、框选与预览，返回文件名、各帧和字幕区域；被取消时返回 None
+    async fn capture(&self) -> Result<Option<Captured>> {
+        // 动图固定使用 WebP，ravif 不支持动画 AVIF
+        let ext = if self.cfg.animation.enabled {
+            "webp".to_string()
+        } else {
+            self.cfg.format.to_string()
+        };
+        self.wait_delay().await;
+        let filename = generate_safe_filename(&self.cfg.field_name, &ext);
+
+        let mut placement = capture_placement(&self.cfg);
+        // 单张截图视为只有一帧的动图，裁剪与框选对每一帧相同地处理
+        let mut frames = if self.cfg.animation.enabled {
+            capture_animation(&self.cfg)?
+        } else {
+            vec![(capture_screenshot(self.cfg.clone())?, 0)]
+        };
+        // 字幕识别使用完整截图，不受裁剪与框选影响
+        let subtitle = self
+            .cfg
+            .ocr
+            .enabled
+            .then(|| Self::crop_fixed(frames[0].0.clone(), &self.cfg.ocr.region).0);
+        if let Some((x, y)) = self.trim_black_bars(&mut frames) {
+            placement = placement.map(|p| p.shifted(x as i32, y as i32));
+        }
+        if self.cfg.crop.enabled {
+            let mut offset = (0, 0);
+            for (frame, _) in frames.iter_mut() {
+                let (cropped, x, y) = Self::crop_fixed(std::mem::take(frame), &self.cfg.crop);
+                *frame = cropped;
+                offset = (x, y);
+            }
+            placement = placement.map(|p| p.shifted(offset.0 as i32, offset.1 as i32));
+        }
+        if self.cfg.select_region {
+            // 在定格的截图上框选，取消则不保存
+            let Some(region) = select_region(&frames[0].0.to_rgba8(), placement).await? else {
+                info!("截图已取消");
+                return Ok(None);
+            };
+            debug!("选中区域: {region:?}");
+            for (frame, _) in frames.iter_mut() {
+                *frame = frame.crop_imm(region.x, region.y, region.width, region.height);
+            }
+        }
+
+        if self.cfg.preview {
+            match self.review(&frames[0].0).await? {
+                Review::Keep => {}
+                Review::Discard => {
+                    info!("截图已丢弃，可重新截图");
+                    return Ok(None);
+                }
+                Review::Retake => {
+                    info!("重新截图");
+                    return Box::pin(self.capture()).await;
+                }
+            }
+        }
+
+        Ok(Some((filename, frames, subtitle)))
+    }
+
+    /// 延迟截图：等待期间可以切回游戏窗口，到时截取的是当时的前台窗口
+    async fn wait_delay(&self) {
+        if self.cfg.delay_ms == 0 {
+            return;
+        }
+        let delay = Duration::from_millis(self.cfg.delay_ms as u64);
+        let countdown = if self.cfg.show_countdown {
+            BorderOverlay::new(OverlayOptions {
+                countdown: Some(delay),
+                ..Default::default()
+            })
+            .inspect_err(|e| warn!("Failed to show countdown overlay: {e}"))
+            .ok()
+        } else {
+            None
+        };
+        debug!("{}ms 后截图", self.cfg.delay_ms);
+        tokio::time::sleep(delay).await;
+        if let Some(countdown) = countdown {
+            countdown.stop();
+            // 等倒计时窗口消失，以免被截进整屏截图
+            tokio::time::sleep(OVERLAY_HIDE_DELAY).await;
+        }
+    }
+
+    // 显示缩略图并等待确认/丢弃/重拍热键，超时没有操作时保存
+    async fn review(&self, image: &DynamicImage) -> Result<Review> {
+        let (tx, rx) = oneshot::channel();
+        *self.review.lock().unwrap() = Some(tx);
+        let preview = BorderOverlay::preview(&image.to_rgba8())
+            .inspect_err(|e| warn!("Failed to show screenshot preview: {e}"))
+            .ok();
+        info!("预览截图中，按确认热键保存，按丢弃热键放弃，按重拍热键重新截图");
+        let review = if self.cfg.preview_timeout_ms == 0 {
+            rx.await.unwrap_or(Review::Discard)
+        } else {
+            let timeout = Duration::from_millis(self.cfg.preview_timeout_ms);
+            match tokio::time::timeout(timeout, rx).await {
+                Ok(review) => review.unwrap_or(Review::Discard),
+                Err(_) => {
+                    self.review.lock().unwrap().take();
+                    info!("预览超时，保存截图");
+                    Review::Keep
+                }
+            }
+        };
+        if let Some(preview) = preview {
+            preview.stop();
+            if matches!(review, Review::Retake) {
+                // 等预览窗口消失，以免被截进重拍的整屏截图
+                tokio::time::sleep(OVERLAY_HIDE_DELAY).await;
+            }
+        }
+        Ok(review)
+    }
+
+    /// 响应确认/丢弃/重拍热键，没有等待中的预览时返回 false
+    pub fn resolve_review(&self, review: Review) -> bool {
+        match self.review.lock().unwrap().take() {
+            Some(tx) => tx.send(review).is_ok(),
+            None => false,
+        }
+    }
+
+    /// 按第一帧检测到的黑边裁剪所有帧，返回保留区域在原图中的左上角
+    fn trim_black_bars(&self, frames: &mut [(DynamicImage, i32)]) -> Option<(u32, u32)> {
+        if !self.cfg.crop_black_bars {
+            return None;
+        }
+        let (x, y, width, height) = bars::detect_black_bars(&frames[0].0)?;
+        debug!("裁掉黑边，保留区域: {width}x{height} @ ({x}, {y})");
+        for (frame, _) in frames.iter_mut() {
+            *frame = frame.crop_imm(x, y, width, height);
+        }
+        Some((x, y))
+    }
+
+    /// 按配置裁剪固定区域，返回裁剪后的图像及其在原图中的左上角
+    fn crop_fixed(image: DynamicImage, crop: &CropRegion) -> (DynamicImage, u32, u32) {
+        let (width, height) = (image.width(), image.height());
+        let to_px = |value: f32, full: u32| -> u32 {
+            let px = match crop.unit {
+                CropUnit::Pixel => value,
+                CropUnit::Percent => value / 100.0 * full as f32,
+            };
+            (px.max(0.0) as u32).min(full)
+        };
+        let x = to_px(crop.x, width);
+        let y = to_px(crop.y, height);
+        let w = to_px(crop.width, width).min(width - x);
+        let h = to_px(crop.height, height).min(height - y);
+        if w == 0 || h == 0 {
+            warn!("裁剪区域超出截图范围，保留完整截图");
+            return (image, 0, 0);
+        }
+        (image.crop_imm(x, y, w, h), x, y)
+    }
+
+    /// 识别字幕区域的文字，没有识别到文字时返回 None
+    async fn recognize_subtitle(&self, image: DynamicImage) -> Result<Option<String>> {
+        let language = self.cfg.ocr.language.clone();
+        let text = tokio::task::spawn_blocking(move || ocr::recognize(&image, &language)).await??;
+        if text.trim().is_empty() {
+            warn!("字幕区域没有识别到文字");
+            return Ok(None);
+        }
+        Ok(Some(text.trim().to_string()))
+    }
+
+    /// 识别字幕区域的文字并写入句子字段
+    async fn save_subtitle(
+        &self,
+        image: DynamicImage,
+        note_ids: Option<&[u64]>,
+        game: &str,
+    ) -> Result<()> {
+        let Some(text) = self.recognize_subtitle(image).await? else {
+            return Ok(());
+        };
+
+        let note_ids = match note_ids {
+            Some(ids) => ids.to_vec(),
+            None => self.anki.resolve_note_ids().await?,
+        };
+        self.anki
+            .write_note_field(
+                &note_ids,
+                &self.cfg.ocr.field_name,
+                &text,
+                &self.cfg.ocr.field_mode,
+                game,
+            )
+            .await?;
+        info!("字幕文字已写入卡片 ID: {}", note_ids[0]);
+        Ok(())
+    }
+
+    /// 截取一张静态截图，只做固定裁剪，不经延迟、框选与预览；阻塞直到截图完成
+    pub fn capture_still(&self) -> Result<DynamicImage> {
+        let mut frames = vec![(capture_screenshot(self.cfg.clone())?, 0)];
+        self.trim_black_bars(&mut frames);
+        let image = frames.remove(0).0;
+        if self.cfg.crop.enabled {
+            Ok(Self::crop_fixed(image, &self.cfg.crop).0)
+        } else {
+            Ok(image)
+        }
+    }
+
+    /// 录音附带的截图按此刻的游戏与时间填写其他字段
+    pub fn still_capture(&self) -> Capture {
+        self.anki.capture(&self.cfg.extra_fields)
+    }
+
+    /// 编码一组静态截图并写入指定卡片，返回保存的文件与写入的字段内容
+    pub async fn save_stills(
+        &self,
+        note_ids: &[u64],
+        images: Vec<DynamicImage>,
+        capture: &Capture,
+    ) -> Result<(Vec<String>, (String, String))> {
+        let note_id = note_ids[0];
+        let mut files = Vec::with_capacity(images.len());
+        let mut value = String::new();
+        for (name, data) in self.encode_stills(images)? {
+            if self
+                .anki
+                .is_duplicate_media(note_id, &self.cfg.field_name, &data)
+                .await
+            {
+                continue;
+            }
+            let name = self.anki.store_media(&name, &data).await?;
+            debug!("截图已保存: {name}");
+            value.push_str(&self.img_tag(&name, &capture.game));
+            files.push(name);
+        }
+        if files.is_empty() {
+            return Ok((files, (self.cfg.field_name.clone(), value)));
+        }
+        let field = (self.cfg.field_name.clone(), value);
+        self.anki
+            .write_note_fields(
+                note_ids,
+                std::slice::from_ref(&field),
+                &self.cfg.field_mode,
+                capture,
+            )
+            .await?;
+        info!("{} 张截图已保存到Anki卡片 ID: {note_id}", files.len());
+        Ok((files, field))
+    }
+
+    /// 编码一组静态截图并放入暂存区
+    pub fn stage_stills(
+        &self,
+        note_ids: Option<&[u64]>,
+        images: Vec<DynamicImage>,
+        capture: &Capture,
+    ) -> Result<()> {
+        let stills = self.encode_stills(images)?;
+        let media: Vec<_> = stills
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+            .collect();
+        let value: String = stills
+            .iter()
+            .map(|(name, _)| self.img_tag(name, &capture.game))
+            .collect();
+        let fields = [(self.cfg.field_name.clone(), value)];
+        self.anki
+            .stage(note_ids, &media, &fields, &self.cfg.field_mode, capture)
+    }
+
+    /// 逐张编码静态截图，返回文件名与数据
+    fn encode_stills(&self, images: Vec<DynamicImage>) -> Result<Vec<(String, Vec<u8>)>> {
+        // 静态截图总是单帧编码
+        let mut this = self.clone();
+        this.cfg.animation.enabled = false;
+        let ext = self.cfg.format.to_string();
+        let count = images.len();
+        images
+            .into_iter()
+            .enumerate()
+            .map(|(i, image)| {
+                let data =
+                    this.encode_frames(&[(image, 0)], self.cfg.max_width, self.cfg.max_height)?;
+                // 文件名的时间戳只精确到秒，多张时带序号
+                let name = if count > 1 {
+                    generate_safe_filename(&format!("{}_{}", self.cfg.field_name, i + 1), &ext)
+                } else {
+                    generate_safe_filename(&self.cfg.field_name, &ext)
+                };
+                Ok((name, data))
+            })
+            .collect()
+    }
+
+    /// 删除上一张截图的文件，并从字段中移除当时写入的内容
+    async fn discard(&self, last: SavedShot) -> Result<()> {
+        for file in &last.files {
+            if let Err(e) = self.anki.delete_media(file).await {
+                error!("Failed to delete {file}: {e}");
+            }
+        }
+        // 只移除上次写入的内容，追加模式下保留字段原有部分
+        for (field, value) in &last.fields {
+            let current = self.anki.get_note_field(last.note_id, field).await?;
+            self.anki
+                .update_note_field(last.note_id, field, &current.replace(value, ""))
+                .await?;
+        }
+        Ok(())
+    }
 
-        let screenshot = capture_active_window(self.cfg.clone())?;
+    /// 缩小到给定尺寸后编码所有帧
+    fn encode_frames(
+        &self,
+        frames: &[(DynamicImage, i32)],
+        max_width: u32,
+        max_height: u32,
+    ) -> Result<Vec<u8>> {
+        let frames: Vec<_> = frames
+            .iter()
+            .map(|(frame, t)| {
+                let frame = downscale(frame, max_width, max_height);
+                (apply_filter(frame, &self.cfg.filter), *t)
+            })
+            .collect();
+        let encode_once = |cfg: &Screenshot| {
+            if cfg.animation.enabled {
+                encode_animation(cfg, &frames)
+            } else {
+                encode(cfg, &frames[0].0)
+            }
+        };
+        if self.cfg.target_size_kb > 0 {
+            encode_to_target_size(&self.cfg, encode_once)
+        } else {
+            encode_once(&self.cfg)
+        }
+    }
+
+    async fn encode_and_save(
+        &self,
+        filename: &str,
+        frames: &[(DynamicImage, i32)],
+        replace: Option<SavedShot>,
+        note_ids: Option<&[u64]>,
+        capture: &Capture,
+    ) -> Result<Vec<u64>> {
+        let files = self.encode_files(filename, frames)?;
+        match self.save_files(&files, replace, note_ids, capture).await {
+            Err(e) => {
+                // Anki 没有运行时先存到离线队列，之后自动重试
+                let (media, fields) = self.media_and_fields(&files, &capture.game);
+                if self.anki.queue_if_unreachable(
+                    &*e,
+                    note_ids,
+                    &media,
+                    &fields,
+                    &self.cfg.field_mode,
+                    capture,
+                ) {
+                    return Err(anyhow!("AnkiConnect 无法连接，截图已加入离线队列"));
+                }
+                if self
+                    .anki
+                    .save_fallback(&*e, note_ids, &media, &fields, capture)
+                {
+                    return Err(anyhow!("{e}，截图已保存到备用目录"));
+                }
+                Err(e)
+            }
+            saved => saved,
+        }
+    }
+
+    /// 编码截图并放入暂存区，字幕文字单独暂存
+    async fn stage(
+        &self,
+        filename: &str,
+        frames: &[(DynamicImage, i32)],
+        subtitle: Option<DynamicImage>,
+        note_ids: Option<&[u64]>,
+        capture: &Capture,
+    ) -> Result<()> {
+        let files = self.encode_files(filename, frames)?;
+        let (media, fields) = self.media_and_fields(&files, &capture.game);
+        self.anki
+            .stage(note_ids, &media, &fields, &self.cfg.field_mode, capture)?;
+        if let Some(subtitle) = subtitle
+            && let Some(text) = self.recognize_subtitle(subtitle).await?
+        {
+            let fields = [(self.cfg.ocr.field_name.clone(), text)];
+            self.anki.stage(
+                note_ids,
+                &[],
+                &fields,
+                &self.cfg.ocr.field_mode,
+                &Capture {
+                    game: capture.game.clone(),
+                    ..Capture::default()
+                },
+            )?;
+        }
+        Ok(())
+    }
 
-        let _data = encode(
-            self.cfg.format.clone(),
-            self.cfg.quality,
-            self.cfg.speed,
-            &screenshot,
-        )?; // 耗时操作，要放在获取窗口名之类的后面
+    /// 编码各帧，开启缩略图时额外编码一份完整尺寸的图；返回文件名、字段、数据
+    fn encode_files(
+        &self,
+        filename: &str,
+        frames: &[(DynamicImage, i32)],
+    ) -> Result<Vec<(String, &String, Vec<u8>)>> {
+        // 耗时操作，要放在获取窗口名之类的后面
+        let full = self.encode_frames(frames, self.cfg.max_width, self.cfg.max_height)?;
+        // 文件名、字段、数据
+        let files = if self.cfg.thumbnail.enabled {
+            let thumbnail = &self.cfg.thumbnail;
+            let small = self.encode_frames(frames, thumbnail.max_width, thumbnail.max_height)?;
+            let full_name = match filename.rsplit_once('.') {
+                Some((stem, ext)) => format!("{stem}_full.{ext}"),
+                None => format!("{filename}_full"),
+            };
+            vec![
+                (filename.to_string(), &self.cfg.field_name, small),
+                (full_name, &thumbnail.full_field_name, full),
+            ]
+        } else {
+            vec![(filename.to_string(), &self.cfg.field_name, full)]
+        };
         debug!(
             "截图格式：{:?}, 质量：{}, 速度：{}",
             self.cfg.format, self.cfg.quality, self.cfg.speed
         );
+        Ok(files)
+    }
 
-        let note_id = self.anki.get_latest_note_id().await?;
+    /// 存入编码好的文件并更新卡片字段，返回写入的卡片
+    async fn save_files(
+        &self,
+        files: &[(String, &String, Vec<u8>)],
+        replace: Option<SavedShot>,
+        note_ids: Option<&[u64]>,
+        capture: &Capture,
+    ) -> Result<Vec<u64>> {
+        let note_ids = match note_ids {
+            Some(ids) => ids.to_vec(),
+            None => self.anki.resolve_note_ids().await?,
+        };
+        let note_id = note_ids[0];
+        if let Some(last) = replace {
+            self.discard(last).await?;
+        }
 
-        let media_dir = self.anki.get_media_dir().await?;
-        let file_path = std::path::Path::new(&media_dir).join(&filename);
-        std::fs::write(&file_path, &_data)?;
-        debug!("截图已保存到文件: {}", file_path.display());
-        // 更新卡片字段
-        self.anki
-            .update_note_field(
-                note_id,
-                &self.cfg.field_name,
-                &format!("<img src=\"{filename}\">"),
-            )
-            .await?;
+        let mut saved = SavedShot {
+            note_id,
+            files: Vec::new(),
+            fields: Vec::new(),
+        };
+        for (name, field, data) in files {
+            if self.anki.is_duplicate_media(note_id, field, data).await {
+                continue;
+            }
+            let name = self.anki.store_media(name, data).await?;
+            debug!("截图已保存: {name}");
+            saved
+                .fields
+                .push((field.to_string(), self.img_tag(&name, &capture.game)));
+            saved.files.push(name);
+        }
+        // 截图字段与其他字段一次写入
+        if !saved.fields.is_empty() {
+            self.anki
+                .write_note_fields(&note_ids, &saved.fields, &self.cfg.field_mode, capture)
+                .await?;
+        }
+        *self.last_saved.lock().unwrap() = Some(saved);
 
         info!("截图已成功保存到Anki卡片 ID: {note_id}");
-        Ok(())
+        Ok(note_ids)
+    }
+
+    /// 编码好的文件对应的媒体与字段内容，供离线队列和暂存区使用
+    fn media_and_fields<'a>(
+        &self,
+        files: &'a [(String, &String, Vec<u8>)],
+        game: &str,
+    ) -> MediaAndFields<'a> {
+        let media = files
+            .iter()
+            .map(|(name, _, data)| (name.as_str(), data.as_slice()))
+            .collect();
+        let fields = files
+            .iter()
+            .map(|(name, field, _)| (field.to_string(), self.img_tag(name, game)))
+            .collect();
+        (media, fields)
+    }
+
+    /// 按模板生成写入字段的图片内容
+    fn img_tag(&self, filename: &str, game: &str) -> String {
+        render_media_value(&self.cfg.value_template, filename, game)
     }
 }
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use image::DynamicImage;
+use log::debug;
+use windows::{
+    Globalization::Language,
+    Graphics::Imaging::{BitmapPixelFormat, SoftwareBitmap},
+    Media::Ocr::OcrEngine,
+    Security::Cryptography::CryptographicBuffer,
+    core::HSTRING,
+};
+
+/// 用 Windows 自带的 OCR 识别图中文字，阻塞直到识别完成
+///
+/// # 参数
+/// - image: 待识别的图像，通常是裁剪出的字幕区域
+/// - language: BCP-47 语言标签，留空使用系统的用户语言
+pub fn recognize(image: &DynamicImage, language: &str) -> Result<String> {
+    let engine = if language.is_empty() {
+        OcrEngine::TryCreateFromUserProfileLanguages()?
+    } else {
+        OcrEngine::TryCreateFromLanguage(&Language::CreateLanguage(&HSTRING::from(language))?)?
+    };
+
+    // OCR 引擎限制了图像边长，超出时等比缩小
+    let max = OcrEngine::MaxImageDimension()?;
+    let image = if image.width() > max || image.height() > max {
+        image.resize(max, max, image::imageops::FilterType::Triangle)
+    } else {
+        image.clone()
+    };
+    let mut bgra = image.to_rgba8();
+    let (width, height) = bgra.dimensions();
+    for pixel in bgra.pixels_mut() {
+        pixel.0.swap(0, 2);
+    }
+    let buffer = CryptographicBuffer::CreateFromByteArray(bgra.as_raw())?;
+    let bitmap = SoftwareBitmap::CreateCopyFromBuffer(
+        &buffer,
+        BitmapPixelFormat::Bgra8,
+        width as i32,
+        height as i32,
+    )?;
+
+    let result = engine.RecognizeAsync(&bitmap)?.get()?;
+    let text = result.Text()?.to_string_lossy();
+    let tag = engine
+        .RecognizerLanguage()?
+        .LanguageTag()?
+        .to_string_lossy();
+    debug!("OCR 识别语言: {tag}, 结果: {text}");
+    // 中日文的识别结果在每个字之间都有空格
+    if tag.starts_with("ja") || tag.starts_with("zh") {
+        Ok(text.split_whitespace().collect())
+    } else {
+        Ok(text)
+    }
+}
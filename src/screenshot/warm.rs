@@ -0,0 +1,240 @@
+use crate::config::{CaptureTarget, Screenshot};
+use crate::screenshot::capture::{color_format, cursor_settings, decode_frame, find_monitor};
+use anyhow::{Result, anyhow};
+use image::RgbaImage;
+use log::{debug, info, warn};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Once};
+use std::time::Duration;
+use windows_capture::{
+    capture::{CaptureControl, Context, GraphicsCaptureApiHandler},
+    frame::Frame,
+    graphics_capture_api::{GraphicsCaptureApi, InternalCaptureControl},
+    monitor::Monitor,
+    settings::{
+        ColorFormat, DirtyRegionSettings, DrawBorderSettings, MinimumUpdateIntervalSettings,
+        SecondaryWindowSettings, Settings,
+    },
+    window::Window,
+};
+
+/// 常驻会话接收新帧的最小间隔，避免每一帧都从显存拷贝
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+/// 新建会话后等待第一帧的时间
+const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(3);
+/// 后台检查采集目标是否变化的间隔，切换到游戏窗口后在这段时间内建好会话
+const KEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+type HandlerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// 最近一帧的原始数据，截图时才转换，省去每帧的色调映射
+struct RawFrame {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: ColorFormat,
+}
+
+struct Flags {
+    latest: Arc<(Mutex<Option<RawFrame>>, Condvar)>,
+    exclude_title_bar: bool,
+}
+
+struct Handler {
+    flags: Arc<Flags>,
+}
+
+impl GraphicsCaptureApiHandler for Handler {
+    type Flags = Arc<Flags>;
+    type Error = HandlerError;
+
+    fn new(context: Context<Self::Flags>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            flags: context.flags.clone(),
+        })
+    }
+
+    fn on_frame_arrived(
+        &mut self,
+        frame: &mut Frame,
+        _capture_control: InternalCaptureControl,
+    ) -> Result<(), Self::Error> {
+        let format = frame.color_format();
+        let mut buffer = if self.flags.exclude_title_bar {
+            frame.buffer_without_title_bar()?
+        } else {
+            frame.buffer()?
+        };
+        let (width, height) = (buffer.width(), buffer.height());
+        let data = buffer.as_nopadding_buffer()?.to_vec();
+        let (lock, cvar) = &*self.flags.latest;
+        *lock.lock().unwrap() = Some(RawFrame {
+            data,
+            width,
+            height,
+            format,
+        });
+        cvar.notify_all();
+        Ok(())
+    }
+
+    fn on_closed(&mut self) -> Result<(), Self::Error> {
+        debug!("常驻采集的目标已关闭");
+        Ok(())
+    }
+}
+
+#[derive(PartialEq)]
+enum Target {
+    Window(Window),
+    Monitor(Monitor),
+}
+
+struct WarmSession {
+    target: Target,
+    control: CaptureControl<Handler, HandlerError>,
+    latest: Arc<(Mutex<Option<RawFrame>>, Condvar)>,
+}
+
+/// 当前的常驻会话，同一时间只保留一个
+static SESSION: Mutex<Option<WarmSession>> = Mutex::new(None);
+/// 后台维持会话所用的截图配置，未启用常驻会话时为 None
+static KEEP_CFG: Mutex<Option<Screenshot>> = Mutex::new(None);
+static KEEPER: Once = Once::new();
+
+impl WarmSession {
+    fn start(target: Target, cfg: &Screenshot) -> Result<Self> {
+        let latest = Arc::new((Mutex::new(None), Condvar::new()));
+        let flags = Arc::new(Flags {
+            latest: Arc::clone(&latest),
+            // 与单次截图一致，显示器没有标题栏
+            exclude_title_bar: matches!(target, Target::Window(_)) && cfg.exclude_title_bar,
+        });
+        // 会话一直开着，系统默认的黄色采集边框会一直显示，支持时关闭它
+        let border = if GraphicsCaptureApi::is_border_settings_supported().unwrap_or(false) {
+            DrawBorderSettings::WithoutBorder
+        } else {
+            DrawBorderSettings::Default
+        };
+        let interval =
+            if GraphicsCaptureApi::is_minimum_update_interval_supported().unwrap_or(false) {
+                MinimumUpdateIntervalSettings::Custom(FRAME_INTERVAL)
+            } else {
+                MinimumUpdateIntervalSettings::Default
+            };
+        let color_format = color_format(&cfg.hdr);
+        let cursor = cursor_settings(cfg);
+        let secondary = SecondaryWindowSettings::Default;
+        let dirty = DirtyRegionSettings::Default;
+        let control = match target {
+            Target::Window(window) => Handler::start_free_threaded(Settings::new(
+                window,
+                cursor,
+                border,
+                secondary,
+                interval,
+                dirty,
+                color_format,
+                flags,
+            )),
+            Target::Monitor(monitor) => Handler::start_free_threaded(Settings::new(
+                monitor,
+                cursor,
+                border,
+                secondary,
+                interval,
+                dirty,
+                color_format,
+                flags,
+            )),
+        }
+        .map_err(|e| anyhow!("启动常驻采集失败: {e}"))?;
+        Ok(Self {
+            target,
+            control,
+            latest,
+        })
+    }
+
+    /// 转换最近一帧，会话刚建立时等待第一帧到达
+    fn latest_image(&self, cfg: &Screenshot) -> Result<RgbaImage> {
+        let (lock, cvar) = &*self.latest;
+        let guard = lock.lock().unwrap();
+        let (guard, _) = cvar
+            .wait_timeout_while(guard, FIRST_FRAME_TIMEOUT, |frame| frame.is_none())
+            .unwrap();
+        let Some(frame) = &*guard else {
+            return Err(anyhow!("截图超时"));
+        };
+        info!(
+            "使用常驻采集的最近一帧: {}x{}, 格式: {:?}",
+            frame.width, frame.height, frame.format
+        );
+        decode_frame(
+            &frame.data,
+            frame.width,
+            frame.height,
+            frame.format,
+            &cfg.hdr,
+        )
+    }
+
+    fn stop(self) {
+        if let Err(e) = self.control.stop() {
+            warn!("Failed to stop warm capture session: {e}");
+        }
+    }
+}
+
+/// 注册热键时调用：启用常驻会话时在后台提前建立，并跟随前台窗口的变化重建，
+/// 第一次截图就不必等待会话建立；未启用时关闭已有的会话
+pub fn keep_warm(cfg: &Screenshot) {
+    let enabled = cfg.warm_session && !cfg.burst.enabled;
+    *KEEP_CFG.lock().unwrap() = enabled.then(|| cfg.clone());
+    if !enabled {
+        if let Some(old) = SESSION.lock().unwrap().take() {
+            old.stop();
+        }
+        return;
+    }
+    KEEPER.call_once(|| {
+        std::thread::spawn(|| {
+            loop {
+                let cfg = KEEP_CFG.lock().unwrap().clone();
+                if let Some(cfg) = cfg
+                    && let Err(e) = current_session(&cfg)
+                {
+                    debug!("Failed to keep warm capture session: {e}");
+                }
+                std::thread::sleep(KEEP_INTERVAL);
+            }
+        });
+    });
+}
+
+/// 从常驻会话取当前目标的最近一帧；目标变化或会话已结束时重新建立
+pub fn capture_warm(cfg: &Screenshot) -> Result<RgbaImage> {
+    let session = current_session(cfg)?;
+    match session.as_ref() {
+        Some(session) => session.latest_image(cfg),
+        None => Err(anyhow!("常驻采集会话不可用")),
+    }
+}
+
+fn current_session(cfg: &Screenshot) -> Result<MutexGuard<'static, Option<WarmSession>>> {
+    let target = match cfg.target {
+        CaptureTarget::Window => Target::Window(Window::foreground()?),
+        CaptureTarget::Monitor => Target::Monitor(find_monitor(&cfg.monitor)?),
+    };
+    let mut session = SESSION.lock().unwrap();
+    let stale = session
+        .as_ref()
+        .is_none_or(|s| s.target != target || s.control.is_finished());
+    if stale {
+        if let Some(old) = session.take() {
+            old.stop();
+        }
+        debug!("建立常驻采集会话");
+        *session = Some(WarmSession::start(target, cfg)?);
+    }
+    Ok(session)
+}
@@ -0,0 +1,429 @@
+//! 通知区域的托盘图标，菜单中可以修改常用设置，修改写回配置文件后自动重新加载
+
+use crate::config::{self, AudioFormat, Config, HotKey, Mp3Mode};
+use crate::hotkey_manager::HotKeyManager;
+use crate::utils::keyboard::key_to_str;
+use anyhow::{Context, Result};
+use rdev::Key;
+use std::collections::HashMap;
+use std::sync::{Arc, mpsc};
+use std::thread;
+use tray_icon::menu::{
+    CheckMenuItem, Menu, MenuEvent, MenuId, MenuItem, PredefinedMenuItem, Submenu,
+};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+use windows::Win32::Foundation::{LPARAM, WPARAM};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::WindowsAndMessaging::{
+    DispatchMessageW, GetMessageW, MSG, PostThreadMessageW, TranslateMessage, WM_APP,
+};
+
+const TOOLTIP: &str = "Game2Anki";
+const ICON_SIZE: u32 = 32;
+const ICON_COLOR: [u8; 3] = [0x2F, 0x80, 0xED];
+
+const SCREENSHOT_FORMATS: &[(&str, &str)] = &[
+    ("AVIF", "avif"),
+    ("WebP", "webp"),
+    ("PNG", "png"),
+    ("JPEG XL", "jxl"),
+];
+const SCREENSHOT_QUALITIES: &[i64] = &[50, 60, 70, 80, 90, 100];
+const AUDIO_FORMATS: &[(&str, &str)] = &[("Opus", "opus"), ("MP3", "mp3")];
+/// 码率（kbps）
+const OPUS_BITRATES: &[i64] = &[32, 48, 64, 96, 128];
+const MP3_BITRATES: &[i64] = &[96, 128, 192, 256, 320];
+/// MP3 VBR 质量，数值越小质量越高
+const MP3_QUALITIES: &[i64] = &[0, 2, 4, 6];
+/// 视频码率（bit/s）
+const VIDEO_BITRATES: &[i64] = &[1_000_000, 2_000_000, 4_000_000, 8_000_000];
+
+/// 发给托盘线程的更新
+enum Update {
+    Config(Arc<Config>),
+    Fields(Vec<String>),
+}
+
+/// 菜单项对应的操作
+enum Action {
+    /// 把配置中的一项改为给定的值
+    Set {
+        key: String,
+        value: toml::Value,
+    },
+    /// 录下一个组合键作为热键
+    Rebind(String),
+    OpenConfig,
+    Quit,
+}
+
+/// 托盘图标的句柄，可以在任意线程中更新菜单
+#[derive(Clone)]
+pub struct Tray {
+    thread_id: u32,
+    tx: mpsc::Sender<Update>,
+}
+
+impl Tray {
+    /// 在单独的线程中创建托盘图标，在菜单中选择退出时调用 `on_quit`
+    pub fn start(cfg: Arc<Config>, on_quit: impl Fn() + Send + 'static) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+        thread::Builder::new().name("Tray".into()).spawn(move || {
+            let tray = Tray {
+                thread_id: unsafe { GetCurrentThreadId() },
+                tx,
+            };
+            match TrayState::new(cfg, Box::new(on_quit), tray.clone()) {
+                Ok(state) => {
+                    ready_tx.send(Ok(tray)).ok();
+                    state.run(rx);
+                }
+                Err(e) => {
+                    ready_tx.send(Err(e)).ok();
+                }
+            }
+        })?;
+        ready_rx.recv().context("Tray thread exited")?
+    }
+
+    /// 配置重新加载后按新的配置更新菜单
+    pub fn set_config(&self, cfg: Arc<Config>) {
+        self.send(Update::Config(cfg));
+    }
+
+    /// 目标笔记类型的字段，作为字段菜单中的选项
+    pub fn set_fields(&self, fields: Vec<String>) {
+        self.send(Update::Fields(fields));
+    }
+
+    fn send(&self, update: Update) {
+        if self.tx.send(update).is_ok() {
+            self.notify();
+        }
+    }
+
+    /// 唤醒托盘线程的消息循环，处理更新并重建菜单
+    fn notify(&self) {
+        let posted = unsafe { PostThreadMessageW(self.thread_id, WM_APP, WPARAM(0), LPARAM(0)) };
+        if let Err(e) = posted {
+            log::warn!("Failed to notify tray thread: {e}");
+        }
+    }
+}
+
+struct TrayState {
+    icon: TrayIcon,
+    cfg: Arc<Config>,
+    /// 目标笔记类型的字段，连上 Anki 之前为空
+    fields: Vec<String>,
+    actions: HashMap<MenuId, Action>,
+    on_quit: Box<dyn Fn()>,
+    tray: Tray,
+}
+
+impl TrayState {
+    fn new(cfg: Arc<Config>, on_quit: Box<dyn Fn()>, tray: Tray) -> Result<Self> {
+        let (menu, actions) = build_menu(&cfg, &[])?;
+        let icon = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip(TOOLTIP)
+            .with_icon(icon()?)
+            .build()?;
+        Ok(Self {
+            icon,
+            cfg,
+            fields: Vec::new(),
+            actions,
+            on_quit,
+            tray,
+        })
+    }
+
+    /// 托盘图标的消息发到创建它的线程，这里运行消息循环直到程序退出
+    fn run(mut self, rx: mpsc::Receiver<Update>) {
+        let mut msg = MSG::default();
+        while unsafe { GetMessageW(&mut msg, None, 0, 0) }.0 > 0 {
+            if msg.message == WM_APP {
+                for update in rx.try_iter() {
+                    match update {
+                        Update::Config(cfg) => self.cfg = cfg,
+                        Update::Fields(fields) => self.fields = fields,
+                    }
+                }
+                self.rebuild();
+            } else {
+                unsafe {
+                    let _ = TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+            }
+            for event in MenuEvent::receiver().try_iter() {
+                self.on_menu_event(&event.id);
+            }
+        }
+    }
+
+    /// 按当前的配置重建菜单，同时恢复录制热键时修改的提示
+    fn rebuild(&mut self) {
+        match build_menu(&self.cfg, &self.fields) {
+            Ok((menu, actions)) => {
+                self.icon.set_menu(Some(Box::new(menu)));
+                self.actions = actions;
+            }
+            Err(e) => log::error!("Failed to build tray menu: {e}"),
+        }
+        self.icon.set_tooltip(Some(TOOLTIP)).ok();
+    }
+
+    fn on_menu_event(&mut self, id: &MenuId) {
+        let Some(action) = self.actions.get(id) else {
+            return;
+        };
+        match action {
+            // 勾选状态在配置重新加载后随菜单一起更新
+            Action::Set { key, value } => {
+                if let Err(e) = config::update_user_config(key, value.clone()) {
+                    log::error!("Failed to update config: {e:#}");
+                    self.rebuild();
+                }
+            }
+            Action::Rebind(key) => {
+                log::info!("Press the new hotkey for {key}, or Escape to cancel");
+                self.icon
+                    .set_tooltip(Some(format!("按下 {key} 的新热键，Esc 取消")))
+                    .ok();
+                let key = key.clone();
+                let tray = self.tray.clone();
+                HotKeyManager::capture_next_combo(move |keys| {
+                    // 回调在热键监听线程中执行，写文件放到单独的线程
+                    thread::spawn(move || rebind(&tray, &key, keys));
+                });
+            }
+            Action::OpenConfig => open_config(),
+            Action::Quit => (self.on_quit)(),
+        }
+    }
+}
+
+/// 把录下的组合键写回配置，取消或保存失败时恢复菜单
+fn rebind(tray: &Tray, key: &str, keys: Option<Vec<Key>>) {
+    let Some(keys) = keys else {
+        log::info!("Hotkey change cancelled");
+        tray.notify();
+        return;
+    };
+    let value = toml::Value::Array(
+        keys.into_iter()
+            .map(|key| toml::Value::String(key_to_str(key)))
+            .collect(),
+    );
+    if let Err(e) = config::update_user_config(key, value) {
+        log::error!("Failed to update config: {e:#}");
+        tray.notify();
+    }
+}
+
+/// 用关联的程序打开配置文件，修改菜单中没有的设置
+fn open_config() {
+    let result = config::config_file_path().and_then(|path| {
+        std::process::Command::new("explorer").arg(path).spawn()?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        log::error!("Failed to open config file: {e:#}");
+    }
+}
+
+/// 按配置生成菜单，返回菜单与各菜单项对应的操作；配置变化时整个重建
+fn build_menu(cfg: &Config, fields: &[String]) -> Result<(Menu, HashMap<MenuId, Action>)> {
+    let mut builder = MenuBuilder {
+        current: toml::Value::try_from(cfg)?,
+        actions: HashMap::new(),
+    };
+    let menu = Menu::new();
+
+    let screenshot = Submenu::new("截图", true);
+    screenshot.append_items(&[
+        &builder.choices("格式", "screenShot.format", &labeled(SCREENSHOT_FORMATS))?,
+        &builder.choices(
+            "质量",
+            "screenShot.quality",
+            &numbers(SCREENSHOT_QUALITIES, ""),
+        )?,
+        &builder.choices("字段", "screenShot.fieldName", &field_options(fields))?,
+    ])?;
+    menu.append(&screenshot)?;
+
+    for (i, record) in cfg.audio_record.iter().enumerate() {
+        let title = if cfg.audio_record.len() > 1 {
+            format!("录音 {}", i + 1)
+        } else {
+            "录音".to_string()
+        };
+        let prefix = format!("audioRecord.{i}");
+        // 质量设置随格式与编码模式不同
+        let (quality_key, qualities) = match (&record.format, &record.mp3.mode) {
+            (AudioFormat::Opus, _) => ("opus.bitrate", numbers(OPUS_BITRATES, " kbps")),
+            (AudioFormat::Mp3, Mp3Mode::Cbr) => ("mp3.bitrate", numbers(MP3_BITRATES, " kbps")),
+            (AudioFormat::Mp3, Mp3Mode::Vbr) => ("mp3.quality", numbers(MP3_QUALITIES, "")),
+        };
+        let submenu = Submenu::new(title, true);
+        submenu.append_items(&[
+            &builder.choices("格式", &format!("{prefix}.format"), &labeled(AUDIO_FORMATS))?,
+            &builder.choices("质量", &format!("{prefix}.{quality_key}"), &qualities)?,
+            &builder.choices(
+                "字段",
+                &format!("{prefix}.fieldName"),
+                &field_options(fields),
+            )?,
+        ])?;
+        menu.append(&submenu)?;
+    }
+
+    let video_bitrates: Vec<_> = VIDEO_BITRATES
+        .iter()
+        .map(|&bitrate| {
+            (
+                format!("{} Mbps", bitrate / 1_000_000),
+                toml::Value::Integer(bitrate),
+            )
+        })
+        .collect();
+    let video = Submenu::new("视频", true);
+    video.append_items(&[
+        &builder.choices("码率", "video.bitrate", &video_bitrates)?,
+        &builder.choices("字段", "video.fieldName", &field_options(fields))?,
+    ])?;
+    menu.append(&video)?;
+
+    // 点击后按下新的组合键改绑
+    let hotkeys = Submenu::new("热键", true);
+    for name in HotKey::NAMES {
+        let keys = cfg.hot_key.keys_by_name(name).unwrap_or_default();
+        hotkeys.append(&builder.item(
+            &format!("{name}: {}", describe(keys)),
+            Action::Rebind(format!("hotKey.{name}")),
+        ))?;
+    }
+    for (i, record) in cfg.audio_record.iter().enumerate() {
+        hotkeys.append(&builder.item(
+            &format!("audioRecord {}: {}", i + 1, describe(&record.hot_key)),
+            Action::Rebind(format!("audioRecord.{i}.hotKey")),
+        ))?;
+    }
+    menu.append_items(&[
+        &hotkeys,
+        &PredefinedMenuItem::separator(),
+        &builder.item("打开配置文件", Action::OpenConfig),
+        &builder.item("退出", Action::Quit),
+    ])?;
+    Ok((menu, builder.actions))
+}
+
+struct MenuBuilder {
+    /// 当前的配置，用于勾选与之相同的选项
+    current: toml::Value,
+    actions: HashMap<MenuId, Action>,
+}
+
+impl MenuBuilder {
+    /// 一组单选的选项，勾选与当前配置相同的一项；当前值不在选项中时也列出
+    fn choices(
+        &mut self,
+        text: &str,
+        key: &str,
+        options: &[(String, toml::Value)],
+    ) -> Result<Submenu> {
+        let current = lookup(&self.current, key);
+        let mut options = options.to_vec();
+        if let Some(current) = current
+            && !options.iter().any(|(_, value)| value == current)
+        {
+            options.push((display(current), current.clone()));
+        }
+        let submenu = Submenu::new(text, true);
+        for (label, value) in options {
+            let item = CheckMenuItem::new(label, true, current == Some(&value), None);
+            submenu.append(&item)?;
+            self.actions.insert(
+                item.id().clone(),
+                Action::Set {
+                    key: key.to_string(),
+                    value,
+                },
+            );
+        }
+        Ok(submenu)
+    }
+
+    fn item(&mut self, text: &str, action: Action) -> MenuItem {
+        let item = MenuItem::new(text, true, None);
+        self.actions.insert(item.id().clone(), action);
+        item
+    }
+}
+
+/// 按点分隔的路径取配置中的值，数组中的条目用序号表示
+fn lookup<'a>(value: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.')
+        .try_fold(value, |value, key| match key.parse::<usize>() {
+            Ok(index) => value.get(index),
+            Err(_) => value.get(key),
+        })
+}
+
+fn display(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn labeled(options: &[(&str, &str)]) -> Vec<(String, toml::Value)> {
+    options
+        .iter()
+        .map(|&(label, value)| (label.to_string(), toml::Value::String(value.to_string())))
+        .collect()
+}
+
+fn numbers(values: &[i64], unit: &str) -> Vec<(String, toml::Value)> {
+    values
+        .iter()
+        .map(|&value| (format!("{value}{unit}"), toml::Value::Integer(value)))
+        .collect()
+}
+
+fn field_options(fields: &[String]) -> Vec<(String, toml::Value)> {
+    fields
+        .iter()
+        .map(|field| (field.clone(), toml::Value::String(field.clone())))
+        .collect()
+}
+
+/// 热键在菜单中的写法
+fn describe(keys: &[Key]) -> String {
+    if keys.is_empty() {
+        return "未设置".to_string();
+    }
+    keys.iter()
+        .map(|&key| key_to_str(key))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// 托盘图标：带抗锯齿边缘的蓝色圆点
+fn icon() -> Result<Icon> {
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f32 / 2.0;
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let distance = (x as f32 - center).hypot(y as f32 - center);
+            let alpha = (radius - distance).clamp(0.0, 1.0);
+            rgba.extend_from_slice(&ICON_COLOR);
+            rgba.push((alpha * 255.0) as u8);
+        }
+    }
+    Ok(Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE)?)
+}
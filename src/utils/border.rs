@@ -1,92 +1,243 @@
-use anyhow::Result;
-use softbuffer::{Context, Surface};
+use crate::utils::font;
+use crate::utils::preview::{PreviewRequest, PreviewWindow};
+use crate::utils::selection::{SelectRequest, SelectionWindow};
+use anyhow::{Result, anyhow};
+use image::RgbaImage;
+use once_cell::sync::Lazy;
+use softbuffer::{Context, Rect, Surface};
+use std::collections::HashMap;
 use std::num::NonZeroU32;
 use std::rc::Rc;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, mpsc};
 use std::thread;
+use std::time::{Duration, Instant};
 use winit::{
     application::ApplicationHandler,
     event::WindowEvent,
-    event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy},
     platform::windows::EventLoopBuilderExtWindows,
     window::{Window, WindowId, WindowLevel},
 };
 
 const BORDER_THICKNESS: u32 = 8;
 const BORDER_COLOR: u32 = 0x00FF0000; // Red
+const WARNING_COLOR: u32 = 0x00FFC000; // Yellow
+const PAUSED_COLOR: u32 = 0x00808080; // Grey
+const TEXT_COLOR: u32 = 0x00FFFFFF; // White
+const TEXT_SCALE: u32 = 4;
+const TEXT_PADDING: u32 = 6;
+const METER_WIDTH: u32 = 200;
+const METER_HEIGHT: u32 = 12;
+const METER_BACKGROUND: u32 = 0x00303030;
+const METER_COLOR: u32 = 0x0000FF00; // Green
+const METER_CLIP_COLOR: u32 = 0x00FFFF00; // Yellow
+/// Lowest level shown on the meter, in dBFS.
+const METER_FLOOR_DB: f32 = -60.0;
+/// Largest size of a capture preview thumbnail.
+const PREVIEW_MAX_WIDTH: u32 = 480;
+const PREVIEW_MAX_HEIGHT: u32 = 270;
 
-#[derive(Debug)]
-enum UserEvent {
-    Shutdown,
+/// Proxy of the shared overlay event loop.
+///
+/// winit only allows one event loop per process, so every overlay is a window on
+/// a single long-lived thread that is started on first use.
+static OVERLAY_LOOP: Lazy<Mutex<Option<EventLoopProxy<UserEvent>>>> =
+    Lazy::new(|| Mutex::new(None));
+static NEXT_OVERLAY_ID: AtomicU64 = AtomicU64::new(0);
+
+/// What the overlay shows besides the border.
+#[derive(Clone, Debug, Default)]
+pub struct OverlayOptions {
+    /// Draw the elapsed recording time.
+    pub show_timer: bool,
+    /// Number shown next to the timer, e.g. how many clips were saved so far.
+    pub clip_count: Option<usize>,
+    /// Draw a VU bar fed through [`LevelMeter`].
+    pub show_level_meter: bool,
+    /// Flash the border yellow once the overlay has been shown for this long.
+    pub flash_after: Option<Duration>,
+    /// Draw the seconds left until this much time has passed, e.g. before a delayed screenshot.
+    pub countdown: Option<Duration>,
+    /// Draw a grey border instead of a red one, marking that hotkeys are paused.
+    pub paused: bool,
+}
+
+pub(crate) enum UserEvent {
+    Show { id: u64, options: OverlayOptions },
+    Hide { id: u64 },
+    Level { id: u64, peak: f32, rms: f32 },
+    Preview { id: u64, request: PreviewRequest },
+    Select(SelectRequest),
+}
+
+/// Sends an event to the shared overlay thread, starting it if needed.
+pub(crate) fn post_event(event: UserEvent) -> Result<()> {
+    BorderOverlay::event_loop_proxy()?
+        .send_event(event)
+        .map_err(|_| anyhow!("Overlay thread is no longer running"))
+}
+
+/// A cloneable handle for pushing audio levels to an overlay from any thread.
+#[derive(Clone)]
+pub struct LevelMeter {
+    id: u64,
+    proxy: EventLoopProxy<UserEvent>,
+}
+
+impl LevelMeter {
+    /// Updates the meter with linear peak and RMS amplitudes (0.0 - 1.0).
+    pub fn set(&self, peak: f32, rms: f32) {
+        let id = self.id;
+        self.proxy
+            .send_event(UserEvent::Level { id, peak, rms })
+            .ok();
+    }
 }
 
 /// An overlay window that draws a red border and can be safely closed.
 pub struct BorderOverlay {
-    /// The handle to the thread that manages the window.
-    thread_handle: Option<thread::JoinHandle<()>>,
+    id: u64,
     proxy: Option<EventLoopProxy<UserEvent>>,
 }
 
 impl BorderOverlay {
     /// Creates and displays the border overlay window.
     ///
-    /// This function spawns a new thread to handle the window's message loop.
-    /// It waits until the window is successfully created before returning.
-    pub fn new() -> Result<Self> {
+    /// The window lives on the shared overlay thread, which is spawned the first
+    /// time an overlay is requested.
+    pub fn new(options: OverlayOptions) -> Result<Self> {
+        let proxy = Self::event_loop_proxy()?;
+        let id = NEXT_OVERLAY_ID.fetch_add(1, Ordering::Relaxed);
+        proxy
+            .send_event(UserEvent::Show { id, options })
+            .map_err(|_| anyhow!("Overlay thread is no longer running"))?;
+        Ok(Self {
+            id,
+            proxy: Some(proxy),
+        })
+    }
+
+    /// Shows a thumbnail of `image` in the bottom-right corner instead of a border.
+    ///
+    /// Hidden like any other overlay, by [`BorderOverlay::stop`] or dropping the handle.
+    pub fn preview(image: &RgbaImage) -> Result<Self> {
+        let request = PreviewRequest::new(image, PREVIEW_MAX_WIDTH, PREVIEW_MAX_HEIGHT);
+        let proxy = Self::event_loop_proxy()?;
+        let id = NEXT_OVERLAY_ID.fetch_add(1, Ordering::Relaxed);
+        proxy
+            .send_event(UserEvent::Preview { id, request })
+            .map_err(|_| anyhow!("Overlay thread is no longer running"))?;
+        Ok(Self {
+            id,
+            proxy: Some(proxy),
+        })
+    }
+
+    /// Returns the proxy of the overlay event loop, starting the thread if needed.
+    fn event_loop_proxy() -> Result<EventLoopProxy<UserEvent>> {
+        let mut guard = OVERLAY_LOOP.lock().unwrap();
+        if let Some(proxy) = guard.as_ref() {
+            return Ok(proxy.clone());
+        }
+
         // Create a channel to receive a signal from the spawned thread.
         let (tx, rx) = mpsc::channel();
-
-        let thread_handle = thread::spawn(move || {
-            // This closure runs on the new thread.
-            if let Err(e) = Self::window_thread_main(tx) {
-                eprintln!("Window thread failed: {e}");
-            }
-        });
+        thread::Builder::new()
+            .name("Overlay".into())
+            .spawn(move || {
+                // This closure runs on the new thread.
+                if let Err(e) = Self::window_thread_main(tx) {
+                    eprintln!("Window thread failed: {e}");
+                }
+                *OVERLAY_LOOP.lock().unwrap() = None;
+            })?;
 
         // Block and wait for the spawned thread to send the signal.
         let proxy = rx.recv()?;
-
-        Ok(Self {
-            thread_handle: Some(thread_handle),
-            proxy: Some(proxy),
-        })
+        *guard = Some(proxy.clone());
+        Ok(proxy)
     }
 
     /// The main function for the windowing thread.
     fn window_thread_main(tx: mpsc::Sender<EventLoopProxy<UserEvent>>) -> Result<()> {
-        let event_loop = EventLoop::with_user_event()
-            .with_any_thread(true)
-            .build()?;
+        let event_loop = EventLoop::with_user_event().with_any_thread(true).build()?;
         let proxy = event_loop.create_proxy();
         let mut state = State::default();
         // Send signal once the event loop is created
         tx.send(proxy).expect("Main thread disconnected");
-        let _ = event_loop.run_app(&mut state);
+        event_loop.run_app(&mut state)?;
         Ok(())
     }
 
-    /// Posts a message to destroy the window and joins the thread.
+    /// Returns a handle for feeding the level meter, if the overlay is still alive.
+    pub fn level_meter(&self) -> Option<LevelMeter> {
+        self.proxy.as_ref().map(|proxy| LevelMeter {
+            id: self.id,
+            proxy: proxy.clone(),
+        })
+    }
+
+    /// Posts a message to destroy the window.
     pub fn stop(mut self) {
+        self.hide();
+    }
+
+    fn hide(&mut self) {
         if let Some(proxy) = self.proxy.take() {
-            proxy.send_event(UserEvent::Shutdown).ok();
-        }
-        if let Some(handle) = self.thread_handle.take() {
-            let _ = handle.join();
+            proxy.send_event(UserEvent::Hide { id: self.id }).ok();
         }
     }
 }
 
-#[derive(Default)]
-struct State {
-    window: Option<Rc<Window>>,
-    context: Option<Context<Rc<Window>>>,
-    surface: Option<Surface<Rc<Window>, Rc<Window>>>,
+struct OverlayWindow {
+    window: Rc<Window>,
+    _context: Context<Rc<Window>>,
+    surface: Surface<Rc<Window>, Rc<Window>>,
+    options: OverlayOptions,
+    started: Instant,
+    /// Elapsed seconds shown by the last redraw, used to redraw only when the timer changes.
+    drawn_secs: Option<u64>,
+    /// Latest (peak, rms) levels.
+    level: (f32, f32),
+    /// Status panel drawn by the last redraw; when it is unchanged only the panel is repainted.
+    drawn_panel: Option<Area>,
+    drawn_color: u32,
 }
 
-impl ApplicationHandler<UserEvent> for State {
-    // This is a common indicator that you can create a window.
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let primary_monitor = event_loop.primary_monitor().unwrap();
+/// A rectangle in buffer pixels.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Area {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+impl Area {
+    fn fill(&self, buffer: &mut [u32], width: u32, height: u32, color: u32) {
+        for y in self.y..(self.y + self.h).min(height) {
+            for x in self.x..(self.x + self.w).min(width) {
+                buffer[(y * width + x) as usize] = color;
+            }
+        }
+    }
+
+    fn to_rect(self) -> Option<Rect> {
+        Some(Rect {
+            x: self.x,
+            y: self.y,
+            width: NonZeroU32::new(self.w)?,
+            height: NonZeroU32::new(self.h)?,
+        })
+    }
+}
+
+impl OverlayWindow {
+    fn create(event_loop: &ActiveEventLoop, options: OverlayOptions) -> Result<Self> {
+        let primary_monitor = event_loop
+            .primary_monitor()
+            .ok_or_else(|| anyhow!("No primary monitor"))?;
         let monitor_size = primary_monitor.size();
         let monitor_pos = primary_monitor.position();
 
@@ -97,86 +248,328 @@ impl ApplicationHandler<UserEvent> for State {
             .with_inner_size(monitor_size)
             .with_active(false)
             .with_window_level(WindowLevel::AlwaysOnTop);
-        let window = Rc::new(event_loop.create_window(window_attributes).unwrap());
+        let window = Rc::new(event_loop.create_window(window_attributes)?);
         if let Err(e) = window.set_cursor_hittest(false) {
             eprintln!("Failed to set cursor hittest: {e}");
         }
-        let context = Context::new(window.clone()).unwrap();
-        let surface = Surface::new(&context, window.clone()).unwrap();
-        self.window = Some(window);
-        self.context = Some(context);
-        self.surface = Some(surface);
+        let context = Context::new(window.clone()).map_err(|e| anyhow!("{e}"))?;
+        let surface = Surface::new(&context, window.clone()).map_err(|e| anyhow!("{e}"))?;
+        Ok(Self {
+            window,
+            _context: context,
+            surface,
+            options,
+            started: Instant::now(),
+            drawn_secs: None,
+            level: (0.0, 0.0),
+            drawn_panel: None,
+            drawn_color: BORDER_COLOR,
+        })
+    }
+
+    fn elapsed_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// Whether the contents change over time and need a redraw every second.
+    fn ticks(&self) -> bool {
+        self.options.show_timer
+            || self.options.flash_after.is_some()
+            || self.options.countdown.is_some()
+    }
+
+    /// Border colour, alternating with yellow every second past `flash_after`.
+    fn border_color(&self) -> u32 {
+        if self.options.paused {
+            return PAUSED_COLOR;
+        }
+        match self.options.flash_after {
+            Some(limit)
+                if self.started.elapsed() >= limit && self.elapsed_secs().is_multiple_of(2) =>
+            {
+                WARNING_COLOR
+            }
+            _ => BORDER_COLOR,
+        }
+    }
+
+    fn status_text(&self) -> String {
+        let mut text = String::new();
+        if let Some(countdown) = self.options.countdown {
+            let total = countdown.as_millis().div_ceil(1000) as u64;
+            text.push_str(&total.saturating_sub(self.elapsed_secs()).to_string());
+        } else if self.options.show_timer {
+            let secs = self.elapsed_secs();
+            text.push_str(&format!("{:02}:{:02}", secs / 60, secs % 60));
+        }
+        if let Some(count) = self.options.clip_count {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(&format!("#{count}"));
+        }
+        text
+    }
+
+    /// Layout of the status panel, or `None` when there is nothing to show.
+    fn panel_area(&self, text: &str, width: u32) -> Option<Area> {
+        let mut content_w = 0;
+        let mut content_h = 0;
+        if !text.is_empty() {
+            content_w = font::text_width(text, TEXT_SCALE);
+            content_h = font::GLYPH_HEIGHT * TEXT_SCALE;
+        }
+        if self.options.show_level_meter {
+            content_w = content_w.max(METER_WIDTH);
+            if content_h > 0 {
+                content_h += TEXT_PADDING;
+            }
+            content_h += METER_HEIGHT;
+        }
+        if content_h == 0 {
+            return None;
+        }
+        let w = content_w + TEXT_PADDING * 2;
+        Some(Area {
+            x: width.saturating_sub(BORDER_THICKNESS + w),
+            y: BORDER_THICKNESS,
+            w,
+            h: content_h + TEXT_PADDING * 2,
+        })
+    }
+
+    fn draw_meter(level: (f32, f32), buffer: &mut [u32], width: u32, height: u32, x: u32, y: u32) {
+        let to_fraction = |amplitude: f32| {
+            let db = 20.0 * amplitude.max(1e-6).log10();
+            ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0)
+        };
+        let (peak, rms) = level;
+        let track = Area {
+            x,
+            y,
+            w: METER_WIDTH,
+            h: METER_HEIGHT,
+        };
+        track.fill(buffer, width, height, METER_BACKGROUND);
+        let bar = Area {
+            w: (to_fraction(rms) * METER_WIDTH as f32) as u32,
+            ..track
+        };
+        bar.fill(buffer, width, height, METER_COLOR);
+        // Peak hold marker, yellow when the signal clips
+        let peak_x = (to_fraction(peak) * METER_WIDTH as f32) as u32;
+        if peak_x > 0 {
+            let marker = Area {
+                x: x + peak_x.saturating_sub(2),
+                w: 2,
+                ..track
+            };
+            let color = if peak >= 1.0 {
+                METER_CLIP_COLOR
+            } else {
+                TEXT_COLOR
+            };
+            marker.fill(buffer, width, height, color);
+        }
+    }
+
+    fn redraw(&mut self) {
+        let size = self.window.inner_size();
+        let (Some(width), Some(height)) =
+            (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+        else {
+            return;
+        };
+        if let Err(e) = self.surface.resize(width, height) {
+            eprintln!("Failed to resize overlay surface: {e}");
+            return;
+        }
+        let (width, height) = (width.get(), height.get());
+        let text = self.status_text();
+        let panel = self.panel_area(&text, width);
+        let border_color = self.border_color();
+        let mut buffer = match self.surface.buffer_mut() {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                eprintln!("Failed to get overlay buffer: {e}");
+                return;
+            }
+        };
+
+        // The buffer keeps its previous contents once presented, so the border only
+        // needs painting the first time or when its colour or the panel layout changes.
+        let full =
+            buffer.age() == 0 || self.drawn_panel != panel || self.drawn_color != border_color;
+        if full {
+            for y in 0..height {
+                for x in 0..width {
+                    let color = if x < BORDER_THICKNESS
+                        || x >= width - BORDER_THICKNESS
+                        || y < BORDER_THICKNESS
+                        || y >= height - BORDER_THICKNESS
+                    {
+                        border_color
+                    } else {
+                        0x00000000 // Transparent
+                    };
+                    buffer[(y * width + x) as usize] = color;
+                }
+            }
+        }
+
+        // Status text and level meter sit on a border-coloured tab in the top-right corner.
+        if let Some(panel) = panel {
+            panel.fill(&mut buffer, width, height, border_color);
+            let mut y = panel.y + TEXT_PADDING;
+            if !text.is_empty() {
+                font::draw_text(
+                    &mut buffer,
+                    width,
+                    panel.x + TEXT_PADDING,
+                    y,
+                    &text,
+                    TEXT_SCALE,
+                    TEXT_COLOR,
+                );
+                y += font::GLYPH_HEIGHT * TEXT_SCALE + TEXT_PADDING;
+            }
+            if self.options.show_level_meter {
+                Self::draw_meter(
+                    self.level,
+                    &mut buffer,
+                    width,
+                    height,
+                    panel.x + TEXT_PADDING,
+                    y,
+                );
+            }
+        }
+
+        let presented = match panel.and_then(Area::to_rect) {
+            Some(rect) if !full => buffer.present_with_damage(&[rect]),
+            _ => buffer.present(),
+        };
+        if let Err(e) = presented {
+            eprintln!("Failed to present overlay: {e}");
+        }
+        self.drawn_panel = panel;
+        self.drawn_color = border_color;
+        self.drawn_secs = Some(self.elapsed_secs());
     }
+}
+
+#[derive(Default)]
+struct State {
+    overlays: HashMap<u64, OverlayWindow>,
+    previews: HashMap<u64, PreviewWindow>,
+    selection: Option<SelectionWindow>,
+}
+
+impl ApplicationHandler<UserEvent> for State {
+    // Windows are created on demand from user events.
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {}
 
     fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
         match event {
-            UserEvent::Shutdown => {
-                event_loop.exit();
+            UserEvent::Show { id, options } => match OverlayWindow::create(event_loop, options) {
+                Ok(overlay) => {
+                    overlay.window.request_redraw();
+                    self.overlays.insert(id, overlay);
+                }
+                Err(e) => eprintln!("Failed to create overlay window: {e}"),
+            },
+            UserEvent::Hide { id } => {
+                self.overlays.remove(&id);
+                self.previews.remove(&id);
             }
+            UserEvent::Level { id, peak, rms } => {
+                if let Some(overlay) = self.overlays.get_mut(&id)
+                    && overlay.options.show_level_meter
+                {
+                    overlay.level = (peak, rms);
+                    overlay.window.request_redraw();
+                }
+            }
+            UserEvent::Preview { id, request } => {
+                match PreviewWindow::create(event_loop, request) {
+                    Ok(preview) => {
+                        self.previews.insert(id, preview);
+                    }
+                    Err(e) => eprintln!("Failed to create preview window: {e}"),
+                }
+            }
+            // A new request replaces any selection still in progress, which resolves as cancelled.
+            UserEvent::Select(request) => match SelectionWindow::create(event_loop, request) {
+                Ok(selection) => self.selection = Some(selection),
+                Err(e) => eprintln!("Failed to create selection window: {e}"),
+            },
         }
     }
 
     fn window_event(
         &mut self,
-        event_loop: &ActiveEventLoop,
+        _event_loop: &ActiveEventLoop,
         window_id: WindowId,
         event: WindowEvent,
     ) {
-        let window = match self.window.as_ref() {
-            Some(window) => window,
-            None => return,
-        };
-
-        if window.id() != window_id {
+        if let Some(selection) = self.selection.as_mut()
+            && selection.window.id() == window_id
+        {
+            if selection.handle(event) {
+                self.selection = None;
+            }
             return;
         }
-        let surface = self.surface.as_mut().unwrap();
-        match event {
-            WindowEvent::RedrawRequested => {
-                let (width, height) = {
-                    let size = window.inner_size();
-                    (size.width, size.height)
-                };
-
-                if let (Some(width), Some(height)) =
-                    (NonZeroU32::new(width), NonZeroU32::new(height))
-                {
-                    surface.resize(width, height).unwrap();
-
-                    let mut buffer = surface.buffer_mut().unwrap();
-                    for y in 0..height.get() {
-                        for x in 0..width.get() {
-                            let color = if x < BORDER_THICKNESS
-                                || x >= width.get() - BORDER_THICKNESS
-                                || y < BORDER_THICKNESS
-                                || y >= height.get() - BORDER_THICKNESS
-                            {
-                                BORDER_COLOR
-                            } else {
-                                0x00000000 // Transparent
-                            };
-                            buffer[(y * width.get() + x) as usize] = color;
-                        }
-                    }
-
-                    buffer.present().unwrap();
-                }
+        if let Some(preview) = self
+            .previews
+            .values_mut()
+            .find(|preview| preview.window.id() == window_id)
+        {
+            if let WindowEvent::RedrawRequested = event {
+                preview.redraw();
             }
+            return;
+        }
+        let Some(overlay) = self
+            .overlays
+            .values_mut()
+            .find(|overlay| overlay.window.id() == window_id)
+        else {
+            return;
+        };
+        match event {
+            WindowEvent::RedrawRequested => overlay.redraw(),
             WindowEvent::CloseRequested => {
-                event_loop.exit();
+                self.overlays
+                    .retain(|_, overlay| overlay.window.id() != window_id);
             }
             _ => (),
         }
     }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        // Wake up once per second while any timer or warning flash is visible.
+        let mut next_tick: Option<Instant> = None;
+        for overlay in self.overlays.values() {
+            if !overlay.ticks() {
+                continue;
+            }
+            let elapsed = overlay.elapsed_secs();
+            if overlay.drawn_secs != Some(elapsed) {
+                overlay.window.request_redraw();
+            }
+            let tick = overlay.started + Duration::from_secs(elapsed + 1);
+            next_tick = Some(next_tick.map_or(tick, |t| t.min(tick)));
+        }
+        event_loop.set_control_flow(match next_tick {
+            Some(tick) => ControlFlow::WaitUntil(tick),
+            None => ControlFlow::Wait,
+        });
+    }
 }
 
 impl Drop for BorderOverlay {
     fn drop(&mut self) {
-        if let Some(proxy) = self.proxy.take() {
-            proxy.send_event(UserEvent::Shutdown).ok();
-        }
-        if let Some(handle) = self.thread_handle.take() {
-            let _ = handle.join();
-        }
+        self.hide();
     }
 }
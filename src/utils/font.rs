@@ -0,0 +1,69 @@
+//! A tiny 5x7 bitmap font for drawing status text into softbuffer surfaces.
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+/// Horizontal gap between glyphs, in font pixels.
+const GLYPH_SPACING: u32 = 1;
+
+/// Returns the rows of a glyph; each row uses the low 5 bits, MSB on the left.
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        _ => [0x00; 7],
+    }
+}
+
+/// Width in screen pixels of `text` rendered at `scale`.
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    let count = text.chars().count() as u32;
+    if count == 0 {
+        return 0;
+    }
+    (count * (GLYPH_WIDTH + GLYPH_SPACING) - GLYPH_SPACING) * scale
+}
+
+/// Draws `text` into a `width`-pixel-wide 0RGB buffer with its top-left corner at (`x`, `y`).
+///
+/// Pixels falling outside the buffer are clipped.
+pub fn draw_text(
+    buffer: &mut [u32],
+    width: u32,
+    x: u32,
+    y: u32,
+    text: &str,
+    scale: u32,
+    color: u32,
+) {
+    let height = buffer.len() as u32 / width.max(1);
+    for (i, c) in text.chars().enumerate() {
+        let origin_x = x + i as u32 * (GLYPH_WIDTH + GLYPH_SPACING) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                let px = origin_x + col * scale;
+                let py = y + row as u32 * scale;
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (sx, sy) = (px + dx, py + dy);
+                        if sx < width && sy < height {
+                            buffer[(sy * width + sx) as usize] = color;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
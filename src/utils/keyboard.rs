@@ -1,7 +1,10 @@
 use rdev::Key;
-use serde::Deserializer;
+use serde::{Deserializer, Serializer};
 use std::collections::HashMap;
 
+/// F13 的虚拟键码，F13–F24 连续排列；rdev 没有为它们命名，报告为 `Key::Unknown(虚拟键码)`
+const VK_F13: u32 = 0x7C;
+
 pub fn key_from_str(s: &str) -> Option<Key> {
     let map: HashMap<&'static str, Key> = [
         ("Alt", Key::Alt),
@@ -115,6 +118,15 @@ pub fn key_from_str(s: &str) -> Option<Key> {
     .collect();
     if let Some(&key) = map.get(s) {
         Some(key)
+    } else if let Some(key) = map
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        .map(|(_, &key)| key)
+        .or_else(|| key_alias(&s.to_ascii_lowercase()))
+    {
+        Some(key)
+    } else if let Some(key) = extended_key(s) {
+        Some(key)
     } else if let Some(rest) = s.strip_prefix("Unknown(") {
         if let Some(num) = rest.strip_suffix(")") {
             if let Ok(val) = num.parse::<u32>() {
@@ -127,12 +139,230 @@ pub fn key_from_str(s: &str) -> Option<Key> {
     }
 }
 
+/// F13–F24 与按扫描码指定的键，如 `"F13"`、`"Scan(0x64)"`
+///
+/// 扫描码按 Set 1 标准布局查表：有名称的键得到对应的 `Key`，与直接写名称相同；
+/// 没有名称的键，例如宏键盘上的 F13–F24、媒体键，得到 `Key::Unknown(虚拟键码)`。
+fn extended_key(s: &str) -> Option<Key> {
+    if let Some(n) = s
+        .strip_prefix(['F', 'f'])
+        .and_then(|n| n.parse::<u32>().ok())
+        .filter(|n| (13..=24).contains(n))
+    {
+        return Some(Key::Unknown(VK_F13 + n - 13));
+    }
+    let code = s.strip_prefix("Scan(")?.strip_suffix(')')?;
+    let code = match code.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => code.parse().ok()?,
+    };
+    key_from_scan(code)
+}
+
+/// Set 1 扫描码对应的键，扩展键带 0xE0 前缀，如 0xE05B；与 rdev 在 Windows 上按虚拟键码报告的键一致
+fn key_from_scan(code: u32) -> Option<Key> {
+    const LETTERS: [Key; 26] = [
+        Key::KeyQ,
+        Key::KeyW,
+        Key::KeyE,
+        Key::KeyR,
+        Key::KeyT,
+        Key::KeyY,
+        Key::KeyU,
+        Key::KeyI,
+        Key::KeyO,
+        Key::KeyP,
+        Key::KeyA,
+        Key::KeyS,
+        Key::KeyD,
+        Key::KeyF,
+        Key::KeyG,
+        Key::KeyH,
+        Key::KeyJ,
+        Key::KeyK,
+        Key::KeyL,
+        Key::KeyZ,
+        Key::KeyX,
+        Key::KeyC,
+        Key::KeyV,
+        Key::KeyB,
+        Key::KeyN,
+        Key::KeyM,
+    ];
+    const DIGITS: [Key; 10] = [
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+        Key::Num0,
+    ];
+    const FUNCTION: [Key; 10] = [
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+    ];
+    let key = match code {
+        0x01 => Key::Escape,
+        0x02..=0x0B => DIGITS[(code - 0x02) as usize],
+        0x0C => Key::Minus,
+        0x0D => Key::Equal,
+        0x0E => Key::Backspace,
+        0x0F => Key::Tab,
+        0x10..=0x19 => LETTERS[(code - 0x10) as usize],
+        0x1A => Key::LeftBracket,
+        0x1B => Key::RightBracket,
+        // 小键盘的回车与主键盘的回车虚拟键码相同
+        0x1C | 0xE01C => Key::Return,
+        0x1D => Key::ControlLeft,
+        0x1E..=0x26 => LETTERS[(code - 0x1E + 10) as usize],
+        0x27 => Key::SemiColon,
+        0x28 => Key::Quote,
+        0x29 => Key::BackQuote,
+        0x2A => Key::ShiftLeft,
+        0x2B => Key::BackSlash,
+        0x2C..=0x32 => LETTERS[(code - 0x2C + 19) as usize],
+        0x33 => Key::Comma,
+        0x34 => Key::Dot,
+        0x35 => Key::Slash,
+        0x36 => Key::ShiftRight,
+        0x37 => Key::KpMultiply,
+        0x38 => Key::Alt,
+        0x39 => Key::Space,
+        0x3A => Key::CapsLock,
+        0x3B..=0x44 => FUNCTION[(code - 0x3B) as usize],
+        0x45 => Key::NumLock,
+        0x46 => Key::ScrollLock,
+        0x47 => Key::Kp7,
+        0x48 => Key::Kp8,
+        0x49 => Key::Kp9,
+        0x4A => Key::KpMinus,
+        0x4B => Key::Kp4,
+        0x4C => Key::Kp5,
+        0x4D => Key::Kp6,
+        0x4E => Key::KpPlus,
+        0x4F => Key::Kp1,
+        0x50 => Key::Kp2,
+        0x51 => Key::Kp3,
+        0x52 => Key::Kp0,
+        0x53 => Key::KpDelete,
+        0x56 => Key::IntlBackslash,
+        0x57 => Key::F11,
+        0x58 => Key::F12,
+        0x64..=0x6E => Key::Unknown(VK_F13 + code - 0x64),
+        0x76 => Key::Unknown(VK_F13 + 11),
+        // 日文键盘的假名、变换、无变换
+        0x70 => Key::Unknown(0x15),
+        0x79 => Key::Unknown(0x1C),
+        0x7B => Key::Unknown(0x1D),
+        0xE010 => Key::Unknown(0xB1),
+        0xE019 => Key::Unknown(0xB0),
+        0xE01D => Key::ControlRight,
+        0xE020 => Key::Unknown(0xAD),
+        0xE022 => Key::Unknown(0xB3),
+        0xE024 => Key::Unknown(0xB2),
+        0xE02E => Key::Unknown(0xAE),
+        0xE030 => Key::Unknown(0xAF),
+        0xE035 => Key::KpDivide,
+        0xE037 => Key::PrintScreen,
+        0xE038 => Key::AltGr,
+        0xE11D45 => Key::Pause,
+        0xE047 => Key::Home,
+        0xE048 => Key::UpArrow,
+        0xE049 => Key::PageUp,
+        0xE04B => Key::LeftArrow,
+        0xE04D => Key::RightArrow,
+        0xE04F => Key::End,
+        0xE050 => Key::DownArrow,
+        0xE051 => Key::PageDown,
+        0xE052 => Key::Insert,
+        0xE053 => Key::Delete,
+        0xE05B => Key::MetaLeft,
+        // rdev 在 Windows 上没有为右 Win 键和菜单键命名
+        0xE05C => Key::Unknown(0x5C),
+        0xE05D => Key::Unknown(0x5D),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// 常用的按键写法，`name` 已转为小写
+fn key_alias(name: &str) -> Option<Key> {
+    let key = match name {
+        // 不分左右的修饰键，热键按任一侧都能触发
+        "ctrl" | "control" => Key::ControlLeft,
+        "shift" => Key::ShiftLeft,
+        "win" | "meta" | "super" => Key::MetaLeft,
+        "enter" => Key::Return,
+        "esc" => Key::Escape,
+        "prtsc" | "prtscn" | "printscr" => Key::PrintScreen,
+        "del" => Key::Delete,
+        "ins" => Key::Insert,
+        "pgup" => Key::PageUp,
+        "pgdn" | "pgdown" => Key::PageDown,
+        "up" => Key::UpArrow,
+        "down" => Key::DownArrow,
+        "left" => Key::LeftArrow,
+        "right" => Key::RightArrow,
+        "caps" => Key::CapsLock,
+        "`" | "backtick" => Key::BackQuote,
+        "-" => Key::Minus,
+        "=" => Key::Equal,
+        "[" => Key::LeftBracket,
+        "]" => Key::RightBracket,
+        ";" => Key::SemiColon,
+        "'" => Key::Quote,
+        "\\" => Key::BackSlash,
+        "," => Key::Comma,
+        "." => Key::Dot,
+        "/" => Key::Slash,
+        // 单个字母或数字，如 "A"、"1"
+        _ if name.len() == 1 && name.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            let c = name.chars().next()?;
+            return key_from_str(&if c.is_ascii_digit() {
+                format!("Num{c}")
+            } else {
+                format!("Key{}", c.to_ascii_uppercase())
+            });
+        }
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// 按键在配置中的写法，`key_from_str` 可以解析回来
+pub fn key_to_str(key: Key) -> String {
+    match key {
+        Key::Unknown(vk) if (VK_F13..VK_F13 + 12).contains(&vk) => format!("F{}", vk - VK_F13 + 13),
+        key => format!("{key:?}"),
+    }
+}
+
 // 支持组合键字符串解析，如 "Ctrl+Alt+S"
 pub fn keys_from_str(s: &str) -> Option<Vec<Key>> {
     let keys: Option<Vec<Key>> = s.split('+').map(|part| key_from_str(part.trim())).collect();
     keys
 }
 
+/// 热键写回配置文件时使用与 `keys_from_str_de` 相同的写法，如 `["ControlLeft", "F13"]`，而不是 rdev 的内部表示
+pub fn keys_to_str_ser<S>(keys: &[Key], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(keys.iter().map(|&key| key_to_str(key)))
+}
+
 pub fn keys_from_str_de<'de, D>(deserializer: D) -> Result<Vec<Key>, D::Error>
 where
     D: Deserializer<'de>,
@@ -167,3 +397,39 @@ where
     }
     deserializer.deserialize_any(KeyVecVisitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_ignore_case() {
+        assert_eq!(key_from_str("KeyS"), Some(Key::KeyS));
+        assert_eq!(key_from_str("keys"), Some(Key::KeyS));
+        assert_eq!(key_from_str("CAPSLOCK"), Some(Key::CapsLock));
+        assert_eq!(key_from_str("NoSuchKey"), None);
+    }
+
+    #[test]
+    fn key_alias_covers_common_names() {
+        assert_eq!(key_alias("ctrl"), Some(Key::ControlLeft));
+        assert_eq!(key_alias("win"), Some(Key::MetaLeft));
+        assert_eq!(key_alias("esc"), Some(Key::Escape));
+        assert_eq!(key_alias("pgdn"), Some(Key::PageDown));
+        assert_eq!(key_alias("`"), Some(Key::BackQuote));
+        assert_eq!(key_alias("/"), Some(Key::Slash));
+        assert_eq!(key_alias("s"), Some(Key::KeyS));
+        assert_eq!(key_alias("1"), Some(Key::Num1));
+        assert_eq!(key_alias("ab"), None);
+    }
+
+    #[test]
+    fn keys_from_str_parses_combos() {
+        assert_eq!(
+            keys_from_str("Ctrl+Shift+S"),
+            Some(vec![Key::ControlLeft, Key::ShiftLeft, Key::KeyS])
+        );
+        assert_eq!(keys_from_str(" alt + F9 "), Some(vec![Key::Alt, Key::F9]));
+        assert_eq!(keys_from_str("Ctrl+Nope"), None);
+    }
+}
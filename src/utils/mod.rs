@@ -1,4 +1,7 @@
 pub mod border;
 pub mod file;
+pub mod font;
 pub mod keyboard;
+pub mod preview;
+pub mod selection;
 pub mod window;
@@ -0,0 +1,117 @@
+//! A small always-on-top thumbnail of a capture awaiting confirmation.
+
+use anyhow::{Result, anyhow};
+use image::RgbaImage;
+use softbuffer::{Context, Surface};
+use std::num::NonZeroU32;
+use std::rc::Rc;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event_loop::ActiveEventLoop,
+    window::{Window, WindowLevel},
+};
+
+const FRAME_COLOR: u32 = 0x00FFFFFF; // White
+const FRAME_THICKNESS: u32 = 3;
+/// Distance from the bottom-right corner of the primary monitor.
+const MARGIN: i32 = 32;
+
+pub(crate) struct PreviewRequest {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// Thumbnail pixels in softbuffer's 0RGB layout.
+    pub(crate) pixels: Vec<u32>,
+}
+
+impl PreviewRequest {
+    /// Scales `image` down to fit within `max_width` x `max_height`.
+    pub(crate) fn new(image: &RgbaImage, max_width: u32, max_height: u32) -> Self {
+        let scale = (max_width as f32 / image.width() as f32)
+            .min(max_height as f32 / image.height() as f32)
+            .min(1.0);
+        let width = ((image.width() as f32 * scale) as u32).max(1);
+        let height = ((image.height() as f32 * scale) as u32).max(1);
+        let thumbnail = image::imageops::thumbnail(image, width, height);
+        let pixels = thumbnail
+            .pixels()
+            .map(|p| (p[0] as u32) << 16 | (p[1] as u32) << 8 | p[2] as u32)
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+pub(crate) struct PreviewWindow {
+    pub(crate) window: Rc<Window>,
+    _context: Context<Rc<Window>>,
+    surface: Surface<Rc<Window>, Rc<Window>>,
+    width: u32,
+    height: u32,
+    pixels: Vec<u32>,
+}
+
+impl PreviewWindow {
+    pub(crate) fn create(event_loop: &ActiveEventLoop, request: PreviewRequest) -> Result<Self> {
+        let width = request.width + FRAME_THICKNESS * 2;
+        let height = request.height + FRAME_THICKNESS * 2;
+        let mut attributes = Window::default_attributes()
+            .with_decorations(false)
+            .with_resizable(false)
+            .with_inner_size(PhysicalSize::new(width, height))
+            .with_active(false)
+            .with_window_level(WindowLevel::AlwaysOnTop);
+        if let Some(monitor) = event_loop.primary_monitor() {
+            let (position, size) = (monitor.position(), monitor.size());
+            attributes = attributes.with_position(PhysicalPosition::new(
+                position.x + size.width as i32 - width as i32 - MARGIN,
+                position.y + size.height as i32 - height as i32 - MARGIN,
+            ));
+        }
+        let window = Rc::new(event_loop.create_window(attributes)?);
+        if let Err(e) = window.set_cursor_hittest(false) {
+            eprintln!("Failed to set cursor hittest: {e}");
+        }
+        let context = Context::new(window.clone()).map_err(|e| anyhow!("{e}"))?;
+        let surface = Surface::new(&context, window.clone()).map_err(|e| anyhow!("{e}"))?;
+        window.request_redraw();
+        Ok(Self {
+            window,
+            _context: context,
+            surface,
+            width: request.width,
+            height: request.height,
+            pixels: request.pixels,
+        })
+    }
+
+    pub(crate) fn redraw(&mut self) {
+        let outer_w = self.width + FRAME_THICKNESS * 2;
+        let outer_h = self.height + FRAME_THICKNESS * 2;
+        let (Some(width), Some(height)) = (NonZeroU32::new(outer_w), NonZeroU32::new(outer_h))
+        else {
+            return;
+        };
+        if let Err(e) = self.surface.resize(width, height) {
+            eprintln!("Failed to resize preview surface: {e}");
+            return;
+        }
+        let mut buffer = match self.surface.buffer_mut() {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                eprintln!("Failed to get preview buffer: {e}");
+                return;
+            }
+        };
+        buffer.fill(FRAME_COLOR);
+        for (y, row) in self.pixels.chunks_exact(self.width as usize).enumerate() {
+            let start = (y as u32 + FRAME_THICKNESS) * outer_w + FRAME_THICKNESS;
+            buffer[start as usize..start as usize + row.len()].copy_from_slice(row);
+        }
+        if let Err(e) = buffer.present() {
+            eprintln!("Failed to present preview: {e}");
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Click-and-drag region selection over a frozen screenshot.
+
+use crate::utils::border::{self, UserEvent};
+use anyhow::{Result, anyhow};
+use image::RgbaImage;
+use softbuffer::{Context, Surface};
+use std::num::NonZeroU32;
+use std::rc::Rc;
+use tokio::sync::oneshot;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, MouseButton, WindowEvent},
+    event_loop::ActiveEventLoop,
+    keyboard::{Key, NamedKey},
+    window::{CursorIcon, Window, WindowLevel},
+};
+
+const OUTLINE_COLOR: u32 = 0x00FF0000; // Red
+const OUTLINE_THICKNESS: u32 = 2;
+/// Selections smaller than this (in pixels) are treated as a cancel.
+const MIN_SELECTION: u32 = 4;
+
+/// Where to put the selection window so the frozen image lines up with the screen.
+#[derive(Clone, Debug)]
+pub enum Placement {
+    /// Top-left corner in physical screen coordinates.
+    Position(i32, i32),
+    /// Offset from the top-left corner of the monitor with this device name (e.g. `\\.\DISPLAY1`).
+    Monitor {
+        device_name: String,
+        offset: (i32, i32),
+    },
+}
+
+impl Placement {
+    /// Moves the placement, e.g. after cropping the top-left of the image away.
+    pub fn shifted(self, dx: i32, dy: i32) -> Self {
+        match self {
+            Self::Position(x, y) => Self::Position(x + dx, y + dy),
+            Self::Monitor {
+                device_name,
+                offset: (x, y),
+            } => Self::Monitor {
+                device_name,
+                offset: (x + dx, y + dy),
+            },
+        }
+    }
+}
+
+/// A rectangle in image pixels.
+#[derive(Clone, Copy, Debug)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub(crate) struct SelectRequest {
+    width: u32,
+    height: u32,
+    /// Image pixels in softbuffer's 0RGB layout.
+    pixels: Vec<u32>,
+    placement: Option<Placement>,
+    reply: oneshot::Sender<Option<Region>>,
+}
+
+/// Shows `image` in a borderless window at `placement` and lets the user drag out a rectangle.
+///
+/// Resolves to `None` when the selection is cancelled with Escape or a right click.
+pub async fn select_region(
+    image: &RgbaImage,
+    placement: Option<Placement>,
+) -> Result<Option<Region>> {
+    let (reply, rx) = oneshot::channel();
+    let pixels = image
+        .pixels()
+        .map(|p| (p[0] as u32) << 16 | (p[1] as u32) << 8 | p[2] as u32)
+        .collect();
+    border::post_event(UserEvent::Select(SelectRequest {
+        width: image.width(),
+        height: image.height(),
+        pixels,
+        placement,
+        reply,
+    }))?;
+    rx.await
+        .map_err(|_| anyhow!("Selection window closed unexpectedly"))
+}
+
+pub(crate) struct SelectionWindow {
+    pub(crate) window: Rc<Window>,
+    _context: Context<Rc<Window>>,
+    surface: Surface<Rc<Window>, Rc<Window>>,
+    width: u32,
+    height: u32,
+    pixels: Vec<u32>,
+    /// The image darkened, drawn outside the selection.
+    dimmed: Vec<u32>,
+    cursor: PhysicalPosition<f64>,
+    anchor: Option<PhysicalPosition<f64>>,
+    reply: Option<oneshot::Sender<Option<Region>>>,
+}
+
+impl SelectionWindow {
+    pub(crate) fn create(event_loop: &ActiveEventLoop, request: SelectRequest) -> Result<Self> {
+        let mut attributes = Window::default_attributes()
+            .with_decorations(false)
+            .with_resizable(false)
+            .with_inner_size(PhysicalSize::new(request.width, request.height))
+            .with_active(true)
+            .with_window_level(WindowLevel::AlwaysOnTop);
+        let origin = match request.placement {
+            Some(Placement::Position(x, y)) => Some(PhysicalPosition::new(x, y)),
+            Some(Placement::Monitor {
+                device_name,
+                offset: (dx, dy),
+            }) => event_loop
+                .available_monitors()
+                .find(|m| m.name().as_deref() == Some(device_name.as_str()))
+                .map(|m| {
+                    let position = m.position();
+                    PhysicalPosition::new(position.x + dx, position.y + dy)
+                }),
+            None => None,
+        };
+        if let Some(origin) = origin {
+            attributes = attributes.with_position(origin);
+        }
+        let window = Rc::new(event_loop.create_window(attributes)?);
+        window.set_cursor(CursorIcon::Crosshair);
+        window.focus_window();
+        let context = Context::new(window.clone()).map_err(|e| anyhow!("{e}"))?;
+        let surface = Surface::new(&context, window.clone()).map_err(|e| anyhow!("{e}"))?;
+        let dimmed = request
+            .pixels
+            .iter()
+            .map(|&p| (p >> 1) & 0x007F7F7F)
+            .collect();
+        window.request_redraw();
+        Ok(Self {
+            window,
+            _context: context,
+            surface,
+            width: request.width,
+            height: request.height,
+            pixels: request.pixels,
+            dimmed,
+            cursor: PhysicalPosition::new(0.0, 0.0),
+            anchor: None,
+            reply: Some(request.reply),
+        })
+    }
+
+    /// Handles a window event; returns `true` once the selection is finished.
+    pub(crate) fn handle(&mut self, event: WindowEvent) -> bool {
+        match event {
+            WindowEvent::RedrawRequested => self.redraw(),
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = position;
+                if self.anchor.is_some() {
+                    self.window.request_redraw();
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => self.anchor = Some(self.cursor),
+                ElementState::Released => {
+                    let region = self
+                        .selection()
+                        .filter(|r| r.width >= MIN_SELECTION && r.height >= MIN_SELECTION);
+                    return self.finish(region);
+                }
+            },
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => return self.finish(None),
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state == ElementState::Pressed
+                    && event.logical_key == Key::Named(NamedKey::Escape) =>
+            {
+                return self.finish(None);
+            }
+            WindowEvent::CloseRequested => {
+                return self.finish(None);
+            }
+            _ => (),
+        }
+        false
+    }
+
+    fn finish(&mut self, region: Option<Region>) -> bool {
+        if let Some(reply) = self.reply.take() {
+            reply.send(region).ok();
+        }
+        true
+    }
+
+    /// Current drag rectangle, clamped to the image.
+    fn selection(&self) -> Option<Region> {
+        let anchor = self.anchor?;
+        let clamp_x = |v: f64| (v.max(0.0) as u32).min(self.width);
+        let clamp_y = |v: f64| (v.max(0.0) as u32).min(self.height);
+        let (x0, x1) = (clamp_x(anchor.x), clamp_x(self.cursor.x));
+        let (y0, y1) = (clamp_y(anchor.y), clamp_y(self.cursor.y));
+        Some(Region {
+            x: x0.min(x1),
+            y: y0.min(y1),
+            width: x0.abs_diff(x1),
+            height: y0.abs_diff(y1),
+        })
+    }
+
+    fn redraw(&mut self) {
+        let (Some(width), Some(height)) =
+            (NonZeroU32::new(self.width), NonZeroU32::new(self.height))
+        else {
+            return;
+        };
+        if let Err(e) = self.surface.resize(width, height) {
+            eprintln!("Failed to resize selection surface: {e}");
+            return;
+        }
+        let selection = self.selection();
+        let mut buffer = match self.surface.buffer_mut() {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                eprintln!("Failed to get selection buffer: {e}");
+                return;
+            }
+        };
+        buffer.copy_from_slice(&self.dimmed);
+
+        if let Some(r) = selection {
+            let stride = self.width as usize;
+            for y in r.y..r.y + r.height {
+                let row = y as usize * stride;
+                let span = row + r.x as usize..row + (r.x + r.width) as usize;
+                buffer[span.clone()].copy_from_slice(&self.pixels[span]);
+            }
+            // Outline drawn just inside the selection
+            for y in r.y..r.y + r.height {
+                for x in r.x..r.x + r.width {
+                    if x < r.x + OUTLINE_THICKNESS
+                        || x + OUTLINE_THICKNESS >= r.x + r.width
+                        || y < r.y + OUTLINE_THICKNESS
+                        || y + OUTLINE_THICKNESS >= r.y + r.height
+                    {
+                        buffer[y as usize * stride + x as usize] = OUTLINE_COLOR;
+                    }
+                }
+            }
+        }
+
+        if let Err(e) = buffer.present() {
+            eprintln!("Failed to present selection: {e}");
+        }
+    }
+}
+
+impl Drop for SelectionWindow {
+    fn drop(&mut self) {
+        // Resolve as cancelled if the window goes away without a selection.
+        self.finish(None);
+    }
+}
@@ -9,3 +9,16 @@ pub fn get_foreground_window_name() -> String {
         Err(_) => String::new(),
     }
 }
+
+/// Whether the foreground window's title or process name contains any of `patterns`, ignoring case
+pub fn foreground_window_matches(patterns: &[String]) -> bool {
+    let Ok(window) = Window::foreground() else {
+        return false;
+    };
+    let title = window.title().unwrap_or_default().to_lowercase();
+    let process = window.process_name().unwrap_or_default().to_lowercase();
+    patterns
+        .iter()
+        .map(|pattern| pattern.to_lowercase())
+        .any(|pattern| title.contains(&pattern) || process.contains(&pattern))
+}
@@ -0,0 +1,224 @@
+use crate::audio::AudioRecorder;
+use crate::config::{AudioSource, Video};
+use anyhow::{Result, anyhow};
+use log::{debug, error};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use windows_capture::{
+    capture::{Context, GraphicsCaptureApiHandler},
+    encoder::{
+        AudioSettingsBuilder, ContainerSettingsBuilder, VideoEncoder, VideoSettingsBuilder,
+        VideoSettingsSubType,
+    },
+    frame::Frame,
+    graphics_capture_api::InternalCaptureControl,
+    settings::{
+        ColorFormat, CursorCaptureSettings, DirtyRegionSettings, DrawBorderSettings,
+        MinimumUpdateIntervalSettings, SecondaryWindowSettings, Settings,
+    },
+    window::Window,
+};
+
+/// 编码器的音频输入格式：48kHz 双声道 16 位 PCM
+const AUDIO_SAMPLE_RATE: u32 = 48000;
+const AUDIO_CHANNELS: u16 = 2;
+/// 帧时间戳以 100 纳秒为单位
+const TICKS_PER_SEC: i64 = 10_000_000;
+
+struct Flags {
+    path: PathBuf,
+    cfg: Video,
+    /// 环回采集线程写入、帧回调取走的声音采样
+    audio: Option<Arc<Mutex<VecDeque<i16>>>>,
+}
+
+struct Handler {
+    flags: Arc<Flags>,
+    encoder: Option<VideoEncoder>,
+    first_timestamp: Option<i64>,
+    audio_frames_sent: u64,
+    finished: bool,
+}
+
+impl Handler {
+    /// 按第一帧的尺寸创建编码器，录制期间窗口尺寸不应改变
+    fn create_encoder(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<VideoEncoder, Box<dyn std::error::Error + Send + Sync>> {
+        let cfg = &self.flags.cfg;
+        debug!("开始录制视频: {width}x{height}@{}fps", cfg.frame_rate);
+        // H.264 兼容性最好，Anki 自带的播放器可以直接播放
+        let video = VideoSettingsBuilder::new(width, height)
+            .sub_type(VideoSettingsSubType::H264)
+            .bitrate(cfg.bitrate)
+            .frame_rate(cfg.frame_rate.max(1));
+        let audio = AudioSettingsBuilder::new()
+            .sample_rate(AUDIO_SAMPLE_RATE)
+            .channel_count(AUDIO_CHANNELS as u32)
+            .disabled(self.flags.audio.is_none());
+        Ok(VideoEncoder::new(
+            video,
+            audio,
+            ContainerSettingsBuilder::new(),
+            &self.flags.path,
+        )?)
+    }
+
+    /// 取出覆盖到 `elapsed` 之后一帧的声音，采集不足的部分补静音，保证音画同步
+    fn take_audio(&mut self, queue: &Mutex<VecDeque<i16>>, elapsed: i64) -> Vec<u8> {
+        let channels = AUDIO_CHANNELS as usize;
+        let frame_ticks = TICKS_PER_SEC / self.flags.cfg.frame_rate.max(1) as i64;
+        let target = ((elapsed + frame_ticks) * AUDIO_SAMPLE_RATE as i64 / TICKS_PER_SEC) as u64;
+        let needed = target.saturating_sub(self.audio_frames_sent).max(1) as usize;
+        self.audio_frames_sent += needed as u64;
+
+        let mut queue = queue.lock().unwrap();
+        let available = queue.len() / channels * channels;
+        let mut bytes = Vec::with_capacity(needed * channels * 2);
+        for sample in queue.drain(..available.min(needed * channels)) {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        bytes.resize(needed * channels * 2, 0);
+        bytes
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.finished = true;
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl GraphicsCaptureApiHandler for Handler {
+    type Flags = Arc<Flags>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn new(context: Context<Self::Flags>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            flags: context.flags.clone(),
+            encoder: None,
+            first_timestamp: None,
+            audio_frames_sent: 0,
+            finished: false,
+        })
+    }
+
+    fn on_frame_arrived(
+        &mut self,
+        frame: &mut Frame,
+        capture_control: InternalCaptureControl,
+    ) -> Result<(), Self::Error> {
+        if self.finished {
+            return Ok(());
+        }
+        let timestamp = frame.timestamp().Duration;
+        let elapsed = timestamp - *self.first_timestamp.get_or_insert(timestamp);
+        if self.encoder.is_none() {
+            self.encoder = Some(self.create_encoder(frame.width(), frame.height())?);
+        }
+
+        let audio = self
+            .flags
+            .audio
+            .clone()
+            .map(|queue| self.take_audio(&queue, elapsed));
+        let Some(encoder) = self.encoder.as_mut() else {
+            return Ok(());
+        };
+        match audio {
+            Some(audio) => encoder.send_frame_with_audio(frame, &audio)?,
+            None => encoder.send_frame(frame)?,
+        }
+
+        if elapsed >= self.flags.cfg.duration_secs as i64 * TICKS_PER_SEC {
+            self.finish()?;
+            capture_control.stop();
+        }
+        Ok(())
+    }
+
+    fn on_closed(&mut self) -> Result<(), Self::Error> {
+        self.finish()
+    }
+}
+
+/// 录制焦点窗口一段时间并编码为 mp4，阻塞直到录制完成
+pub fn record_clip(cfg: &Video, path: &Path) -> Result<()> {
+    let window = Window::foreground()?;
+    debug!("录制窗口: {window:?}");
+
+    let is_recording = Arc::new(Mutex::new(true));
+    let audio = cfg
+        .record_audio
+        .then(|| Arc::new(Mutex::new(VecDeque::<i16>::new())));
+    if let Some(queue) = &audio {
+        let is_rec = Arc::clone(&is_recording);
+        let queue = Arc::clone(queue);
+        thread::Builder::new()
+            .name("VideoAudioCapture".into())
+            .spawn(move || {
+                let _ = wasapi::initialize_mta();
+                let on_samples = |samples: &[f32]| {
+                    queue.lock().unwrap().extend(
+                        samples
+                            .iter()
+                            .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16),
+                    );
+                };
+                if let Err(e) = AudioRecorder::capture_loop(
+                    is_rec,
+                    &AudioSource::Loopback,
+                    AUDIO_SAMPLE_RATE as usize,
+                    AUDIO_CHANNELS,
+                    on_samples,
+                ) {
+                    error!("Video audio capture failed: {e}");
+                }
+            })?;
+    }
+
+    let flags = Arc::new(Flags {
+        path: path.to_path_buf(),
+        cfg: cfg.clone(),
+        audio,
+    });
+    let settings = Settings::new(
+        window,
+        CursorCaptureSettings::WithoutCursor,
+        DrawBorderSettings::Default,
+        SecondaryWindowSettings::Default,
+        MinimumUpdateIntervalSettings::Custom(Duration::from_secs(1) / cfg.frame_rate.max(1)),
+        DirtyRegionSettings::Default,
+        ColorFormat::Bgra8,
+        flags,
+    );
+    let result = record_until_done(settings, cfg.duration_secs);
+    *is_recording.lock().unwrap() = false;
+    result
+}
+
+fn record_until_done(settings: Settings<Arc<Flags>, Window>, duration_secs: u32) -> Result<()> {
+    let control = Handler::start_free_threaded(settings)?;
+    let callback = control.callback();
+    // 画面静止时不会有新帧，最多多等一秒后由这里收尾
+    let deadline = Instant::now() + Duration::from_secs(duration_secs as u64 + 1);
+    while !control.is_finished() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(50));
+    }
+    control
+        .stop()
+        .map_err(|e| anyhow!("停止视频录制失败: {e}"))?;
+
+    let mut handler = callback.lock();
+    if handler.first_timestamp.is_none() {
+        return Err(anyhow!("录制超时，未收到任何画面"));
+    }
+    handler.finish().map_err(|e| anyhow!("视频编码失败: {e}"))
+}
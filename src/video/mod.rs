@@ -0,0 +1,159 @@
+use crate::{
+    anki::{AnkiClient, Capture, is_unreachable, render_media_value},
+    config::Video,
+    jobs::JobQueue,
+    utils::{
+        border::{BorderOverlay, OverlayOptions},
+        file::generate_safe_filename,
+    },
+};
+use anyhow::{Result, anyhow};
+use log::{debug, error, info, warn};
+use std::sync::Arc;
+mod capture;
+use capture::record_clip;
+
+#[derive(Clone)]
+pub struct AnkiVideo {
+    cfg: Video,
+    anki: Arc<AnkiClient>,
+    jobs: JobQueue,
+}
+
+impl AnkiVideo {
+    pub fn new(cfg: Video, anki: Arc<AnkiClient>, jobs: JobQueue) -> Self {
+        Self { cfg, anki, jobs }
+    }
+
+    pub async fn on_hotkey_clicked(&self) -> Result<()> {
+        let filename = generate_safe_filename(&self.cfg.field_name, "mp4");
+        let capture = self.anki.capture(&self.cfg.extra_fields);
+        // 边录边编码，本地模式直接写入媒体目录，上传模式先写到临时目录
+        // 连不上 AnkiConnect 时也先录到临时目录，之后进入离线队列
+        // 暂存模式下不访问 Anki
+        let local_path = if self.anki.is_staging() {
+            None
+        } else {
+            match self.anki.local_media_path(&filename).await {
+                Ok(path) => path,
+                Err(e) if is_unreachable(&*e) => None,
+                Err(e) => return Err(e),
+            }
+        };
+        let file_path = local_path
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join(&filename));
+
+        // 录制期间显示边框和计时，提示正在录制
+        let border = BorderOverlay::new(OverlayOptions {
+            show_timer: true,
+            clip_count: None,
+            show_level_meter: false,
+            flash_after: None,
+            countdown: None,
+            paused: false,
+        })
+        .inspect_err(|e| warn!("Failed to show video overlay: {e}"))
+        .ok();
+        let cfg = self.cfg.clone();
+        let path = file_path.clone();
+        let result = tokio::task::spawn_blocking(move || record_clip(&cfg, &path))
+            .await
+            .map_err(|e| anyhow!("视频录制线程异常: {e}"))
+            .and_then(|r| r);
+        if let Some(border) = border {
+            border.stop();
+        }
+        if let Err(e) = result {
+            // 不完整的文件没有用处
+            std::fs::remove_file(&file_path).ok();
+            return Err(e);
+        }
+        debug!("视频已保存到文件: {}", file_path.display());
+        let data = std::fs::read(&file_path);
+        if local_path.is_none() {
+            std::fs::remove_file(&file_path).ok();
+        }
+        let data = data?;
+
+        let this = self.clone();
+        self.jobs.submit("save video", async move {
+            let uploaded = local_path.is_none();
+            let media = [(filename.as_str(), data.as_slice())];
+            let value = render_media_value(&this.cfg.value_template, &filename, &capture.game);
+            let fields = [(this.cfg.field_name.clone(), value)];
+            if this.anki.is_staging() {
+                if let Err(e) =
+                    this.anki
+                        .stage(None, &media, &fields, &this.cfg.field_mode, &capture)
+                {
+                    error!("Failed to stage video: {e}");
+                }
+                return;
+            }
+            if let Err(e) = this
+                .save_to_anki(&filename, &data, local_path, &capture)
+                .await
+            {
+                // 本地模式下文件已在媒体目录中，只需补写字段
+                let queued: &[_] = if uploaded { &media } else { &[] };
+                if !this.anki.queue_if_unreachable(
+                    &*e,
+                    None,
+                    queued,
+                    &fields,
+                    &this.cfg.field_mode,
+                    &capture,
+                ) && !this
+                    .anki
+                    .save_fallback(&*e, None, &media, &fields, &capture)
+                {
+                    error!("Failed to save video: {e}");
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// `local_path` 为已直接写入媒体目录的文件，为空时需要上传
+    async fn save_to_anki(
+        &self,
+        filename: &str,
+        data: &[u8],
+        local_path: Option<std::path::PathBuf>,
+        capture: &Capture,
+    ) -> Result<()> {
+        let note_ids = self.anki.resolve_note_ids().await?;
+        let note_id = note_ids[0];
+        let duplicate = self
+            .anki
+            .is_duplicate_media(note_id, &self.cfg.field_name, data)
+            .await;
+        let filename = match local_path {
+            Some(path) if duplicate => {
+                std::fs::remove_file(path)?;
+                return Ok(());
+            }
+            Some(path) => {
+                if let Err(e) = self.anki.verify_media(filename).await {
+                    std::fs::remove_file(path).ok();
+                    return Err(e);
+                }
+                self.anki.archive_media(&path);
+                self.anki.remember_media(filename, data);
+                filename.to_string()
+            }
+            None if duplicate => return Ok(()),
+            None => self.anki.store_media(filename, data).await?,
+        };
+        // Anki 用 [sound:] 标签播放视频
+        let value = render_media_value(&self.cfg.value_template, &filename, &capture.game);
+        let fields = [(self.cfg.field_name.clone(), value)];
+        self.anki
+            .write_note_fields(&note_ids, &fields, &self.cfg.field_mode, capture)
+            .await?;
+
+        info!("视频已成功保存到Anki卡片 ID: {note_id}");
+        Ok(())
+    }
+}